@@ -0,0 +1,106 @@
+//! compiles the glsl shaders in `shaders/` to SPIR-V in `OUT_DIR` with glslc,
+//! falling back to the SPIR-V checked in next to them when glslc is missing,
+//! and tells the golden tests which reference images exist
+
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+const SHADER_EXTENSIONS: &[&str] = &["vert", "frag", "comp"];
+
+/// when the file was last modified, none when it is missing
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// passes a `golden_reference = "<name>"` cfg for every reference image in
+/// `tests/golden/`, the golden tests of the missing ones are ignored
+fn golden_references() {
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+
+    println!("cargo:rerun-if-changed=tests/golden");
+    println!("cargo::rustc-check-cfg=cfg(golden_reference, values(any()))");
+
+    for path in fs::read_dir(&golden).into_iter().flatten() {
+        let path = path.unwrap().path();
+        if path.extension().is_some_and(|e| e == "png") {
+            let name = path.file_stem().unwrap().to_str().unwrap();
+            println!("cargo:rustc-cfg=golden_reference=\"{}\"", name);
+        }
+    }
+}
+
+fn main() {
+    golden_references();
+
+    let shaders = Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders");
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    let glslc = env::var_os("GLSLC").unwrap_or_else(|| "glslc".into());
+
+    println!("cargo:rerun-if-changed=shaders");
+    println!("cargo:rerun-if-env-changed=GLSLC");
+
+    let mut sources = fs::read_dir(&shaders)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| {
+            p.extension()
+                .is_some_and(|e| SHADER_EXTENSIONS.iter().any(|s| e == *s))
+        })
+        .collect::<Vec<_>>();
+    sources.sort();
+
+    let mut missing_compiler = false;
+    for source in sources {
+        let name = source.file_name().unwrap().to_str().unwrap();
+        let output = out_dir.join(format!("{}.spv", name));
+        let checked_in = shaders.join(format!("{}.spv", name));
+
+        let result = Command::new(&glslc)
+            .arg("--target-env=vulkan1.2")
+            .arg("-I")
+            .arg(&shaders)
+            .arg("-o")
+            .arg(&output)
+            .arg(&source)
+            .output();
+
+        match result {
+            // the output of another glslc version differs byte for byte, so
+            // only a source edited after its SPIR-V counts as out of date
+            Ok(result) if result.status.success() => {
+                if modified(&checked_in) < modified(&source) {
+                    println!(
+                        "cargo:warning=shaders/{}.spv is out of date, run shaders/compile.sh",
+                        name
+                    );
+                }
+            }
+            // glslc reports every error as `file:line: error: message`
+            Ok(result) => panic!(
+                "Failed to compile shaders/{}:\n{}",
+                name,
+                String::from_utf8_lossy(&result.stderr)
+            ),
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                missing_compiler = true;
+                fs::copy(&checked_in, &output).unwrap_or_else(|e| {
+                    panic!(
+                        "glslc was not found and shaders/{}.spv is missing: {}",
+                        name, e
+                    )
+                });
+            }
+            Err(error) => panic!("Failed to run glslc: {}", error),
+        }
+    }
+
+    if missing_compiler {
+        println!(
+            "cargo:warning=glslc was not found, using the checked-in SPIR-V. Install the Vulkan SDK or set GLSLC to compile the shaders"
+        );
+    }
+}
@@ -1,2 +1,7 @@
-glslc shader.vert -o vert.spv
-glslc shader.frag -o frag.spv
+#!/bin/sh
+# refreshes the SPIR-V the build falls back to when glslc is missing
+cd "$(dirname "$0")"
+for shader in *.vert *.frag *.comp; do
+    [ -e "$shader" ] || continue
+    glslc --target-env=vulkan1.2 -I . "$shader" -o "$shader.spv"
+done
@@ -1,5 +1,6 @@
 use cgmath::{Deg, vec3};
-use std::time::Instant;
+use std::mem;
+use std::path::Path;
 use std::u64;
 
 use anyhow::{Result, anyhow};
@@ -14,23 +15,51 @@ use vulkanalia::{Entry, window};
 
 use winit::window::Window;
 
+use crate::clock::Clock;
+use crate::config::AppConfig;
+use crate::foundation::debug::{
+    DRAW_LABEL_COLOR, PASS_LABEL_COLOR, begin_label, end_label, name_objects, set_object_name,
+};
 use crate::foundation::device::{create_logical_device, pick_physical_device};
-use crate::foundation::instance::{VALIDATION_ENABLED, create_instance};
+use crate::foundation::instance::create_instance;
+use crate::foundation::offscreen::{create_offscreen_target, read_offscreen_pixels, save_png};
+use crate::foundation::registry::ResourceRegistry;
+use crate::foundation::requirements::{EnabledFeatures, Feature};
 use crate::foundation::swapchain::{create_swapchain, create_swapchain_image_views};
+use crate::foundation::validation::ValidationSink;
+use crate::pipeline::barriers::LayoutTracker;
+use crate::pipeline::block::push_constants;
 use crate::pipeline::buffers::{create_command_buffers, create_command_pool, create_framebuffers};
+use crate::pipeline::cache::PipelineCache;
+use crate::pipeline::descriptor_allocator::{
+    DEFAULT_POOL_RATIOS, DescriptorAllocator, DescriptorLayoutCache, DescriptorWriter,
+};
 use crate::pipeline::descriptors::{
-    Mat4, create_descriptor_pool, create_descriptor_set_layout, create_descriptor_sets,
-    create_uniform_buffers, update_uniform_buffer,
+    Mat4, ModelConstants, OpacityConstants, allocate_frame_descriptors,
+    create_descriptor_set_layout, create_scene_descriptors, update_uniform_buffer,
+};
+use crate::pipeline::image::{create_depth_objects, create_texture_image, get_depth_format};
+use crate::pipeline::memory::{Allocator, destroy_allocator, memory_statistics};
+use crate::pipeline::reload::{HOT_RELOAD_INTERVAL, ShaderWatcher};
+use crate::pipeline::render::{GraphicsTimeline, create_sync_objects, submit_graphics};
+use crate::pipeline::rendering::{
+    begin_pass, end_pass, finish_scene_targets, prepare_scene_targets, scene_pass, set_viewport,
 };
-use crate::pipeline::image::{create_depth_objects, create_texture_image};
-use crate::pipeline::render::create_sync_objects;
+use crate::pipeline::resources::{
+    Buffer, DeletionQueue, Image, ImageView, Pipeline, Sampler, destroy_deletions, flush_deletions,
+};
+use crate::pipeline::shader::SHADER_DIR_ENV;
 use crate::pipeline::texture::{create_texture_image_view, create_texture_sampler};
+use crate::pipeline::uniforms::{UNIFORM_FRAME_SIZE, UniformRing};
+use crate::pipeline::upload::{
+    PendingUpload, UploadBatch, collect_finished_uploads, destroy_uploads,
+};
 use crate::pipeline::vertex::{Vertex, create_index_buffer, create_vertex_buffer};
-use crate::pipeline::{create_pipeline, create_render_pass};
+use crate::pipeline::{SceneShaders, create_pipeline, create_render_pass, reload_pipeline};
 use crate::scenes::models::load_model;
 use crate::scenes::sampling::create_color_objects;
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct App {
     entry: Entry,
     instance: Instance,
@@ -38,55 +67,123 @@ pub struct App {
     data: AppData,
     frame: usize,
     pub resized: bool,
-    pub start: Instant,
+    pub clock: Clock,
     pub models: usize,
+    /// watches the shader sources when hot reloading is enabled
+    shader_watcher: Option<ShaderWatcher>,
 }
 
 pub const MAX_FRAMES_IN_FLIGHT: usize = 2;
 
 impl App {
     /// Creates the vulkan application
-    pub unsafe fn create(window: &Window) -> Result<Self> {
+    pub unsafe fn create(window: &Window, config: &AppConfig) -> Result<Self> {
+        unsafe { Self::create_for_target(Some(window), vk::Extent2D::default(), config) }
+    }
+
+    /// Creates the vulkan application without a window or surface, rendering
+    /// the scene into an offscreen color image of the given size instead of
+    /// a swapchain
+    ///
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    pub unsafe fn create_headless(width: u32, height: u32, config: &AppConfig) -> Result<Self> {
+        unsafe { Self::create_for_target(None, vk::Extent2D { width, height }, config) }
+    }
+
+    unsafe fn create_for_target(
+        window: Option<&Window>,
+        extent: vk::Extent2D,
+        config: &AppConfig,
+    ) -> Result<Self> {
         let loader = unsafe { LibloadingLoader::new(LIBRARY)? };
         let entry = Entry::new(loader).map_err(|b| anyhow!("{}", b))?;
-        let mut data = AppData::default();
+        let mut data = AppData {
+            registry: ResourceRegistry::new(config.track_resources),
+            frame_descriptors: (0..MAX_FRAMES_IN_FLIGHT)
+                .map(|_| DescriptorAllocator::new(DEFAULT_POOL_RATIOS))
+                .collect(),
+            ..Default::default()
+        };
 
-        let instance = unsafe { create_instance(window, &entry, &mut data)? };
+        let instance = unsafe { create_instance(window, &entry, &mut data, &config.validation)? };
 
         unsafe {
-            data.surface = vk_window::create_surface(&instance, &window, &window)?;
-            pick_physical_device(&instance, &mut data)?;
+            if let Some(window) = window {
+                data.surface = vk_window::create_surface(&instance, &window, &window)?;
+            }
+            pick_physical_device(&instance, &mut data, config)?;
         }
 
-        let device = unsafe { create_logical_device(&entry, &instance, &mut data)? };
+        let device =
+            unsafe { create_logical_device(&entry, &instance, &mut data, &config.requirements)? };
+
+        data.dynamic_rendering =
+            config.dynamic_rendering && data.enabled_features.has(Feature::DynamicRendering);
+        log::info!(
+            "Recording passes with {}",
+            if data.dynamic_rendering {
+                "dynamic rendering"
+            } else {
+                "render passes"
+            }
+        );
 
         unsafe {
-            create_swapchain(window, &instance, &device, &mut data)?;
-            create_swapchain_image_views(&device, &mut data)?;
-
-            create_render_pass(&instance, &device, &mut data)?;
+            if let Some(window) = window {
+                create_swapchain(window, &instance, &device, &mut data)?;
+                create_swapchain_image_views(&device, &mut data)?;
+            } else {
+                create_offscreen_target(
+                    &instance,
+                    &device,
+                    &mut data,
+                    extent.width,
+                    extent.height,
+                )?;
+            }
+
+            data.depth_format = get_depth_format(&instance, &data)?;
+            if !data.dynamic_rendering {
+                create_render_pass(&instance, &device, &mut data)?;
+            }
             create_descriptor_set_layout(&device, &mut data)?;
+            data.pipeline_cache =
+                PipelineCache::create(&instance, &device, &data, config.pipeline_cache.as_deref())?;
             create_pipeline(&device, &mut data)?;
             create_command_pool(&instance, &device, &mut data)?;
+            create_sync_objects(&device, &mut data)?;
 
             create_color_objects(&instance, &device, &mut data)?;
             create_depth_objects(&instance, &device, &mut data)?;
-            create_framebuffers(&device, &mut data)?;
+            if !data.dynamic_rendering {
+                create_framebuffers(&device, &mut data)?;
+            }
+
+            // every asset is uploaded in one batch on the transfer queue
+            let mut batch = UploadBatch::begin(&instance, &device, &data)?;
 
-            create_texture_image(&instance, &device, &mut data)?;
+            create_texture_image(&instance, &device, &mut data, &mut batch)?;
             create_texture_image_view(&device, &mut data)?;
             create_texture_sampler(&device, &mut data)?;
 
             load_model(&mut data)?;
-            create_vertex_buffer(&instance, &device, &mut data)?;
-            create_index_buffer(&instance, &device, &mut data)?;
+            create_vertex_buffer(&instance, &device, &mut data, &mut batch)?;
+            create_index_buffer(&instance, &device, &mut data, &mut batch)?;
 
-            create_uniform_buffers(&instance, &device, &mut data)?;
-            create_descriptor_pool(&device, &mut data)?;
-            create_descriptor_sets(&device, &mut data)?;
+            let upload = batch.submit(&device, &mut data)?;
+            data.pending_uploads.push(upload);
+
+            data.uniforms = UniformRing::create(&instance, &device, &data, UNIFORM_FRAME_SIZE)?;
+            create_scene_descriptors(&mut data)?;
 
             create_command_buffers(&device, &mut data)?;
-            create_sync_objects(&device, &mut data)?;
+
+            name_objects(&instance, &device, &data)?;
+
+            for heap in memory_statistics(&instance, &data) {
+                log::debug!("{}", heap);
+            }
         }
 
         Ok(Self {
@@ -96,8 +193,21 @@ impl App {
             data,
             frame: 0,
             resized: false,
-            start: Instant::now(),
+            clock: Clock::real_time(),
             models: 1,
+            shader_watcher: Some(&config.shader_dir)
+                .filter(|_| config.hot_reload && window.is_some())
+                .filter(|dir| {
+                    dir.is_dir() || {
+                        log::warn!(
+                            "Not hot reloading shaders, `{}` is not a directory. Set {} to the shader sources",
+                            dir.display(),
+                            SHADER_DIR_ENV
+                        );
+                        false
+                    }
+                })
+                .map(|dir| ShaderWatcher::new(dir, HOT_RELOAD_INTERVAL)),
         })
     }
 
@@ -105,57 +215,97 @@ impl App {
         &mut self,
         image_index: usize,
         model_index: usize,
+        time: f32,
+        uniform_offset: u32,
     ) -> Result<vk::CommandBuffer> {
         self.data
             .secondary_command_buffers
             .resize_with(image_index + 1, Vec::new);
-        let command_buffers = &mut self.data.secondary_command_buffers[image_index];
 
-        while model_index >= command_buffers.len() {
+        while model_index >= self.data.secondary_command_buffers[image_index].len() {
             let allocate_info = vk::CommandBufferAllocateInfo::builder()
                 .command_pool(self.data.command_pool)
                 .level(vk::CommandBufferLevel::SECONDARY)
                 .command_buffer_count(1);
 
             let command_buffer = self.device.allocate_command_buffers(&allocate_info)?[0];
-            command_buffers.push(command_buffer);
+            self.data.registry.created(command_buffer, 0);
+            let name = format!(
+                "frame[{}].model[{}]",
+                image_index,
+                self.data.secondary_command_buffers[image_index].len()
+            );
+            unsafe {
+                set_object_name(
+                    &self.instance,
+                    &self.device,
+                    &self.data,
+                    command_buffer,
+                    &name,
+                )?
+            };
+            self.data.secondary_command_buffers[image_index].push(command_buffer);
         }
 
-        let command_buffer = command_buffers[model_index];
+        let command_buffer = self.data.secondary_command_buffers[image_index][model_index];
 
         let y = (((model_index % 2) as f32) * 2.5) - 1.25;
         let z = (((model_index / 2) as f32) * -2.0) + 1.0;
 
-        let time = self.start.elapsed().as_secs_f32();
         let model = Mat4::from_translation(vec3(0.0, y, z))
             * Mat4::from_axis_angle(vec3(0.0, 0.0, 1.0), Deg(90.0) * time);
-        let model_bytes =
-            std::slice::from_raw_parts(&model as *const Mat4 as *const u8, size_of::<Mat4>());
-
-        let opacity = (model_index + 1) as f32 * 0.25;
-        let opacity_bytes = &opacity.to_ne_bytes()[..];
 
-        let inheritance_info = vk::CommandBufferInheritanceInfo::builder()
-            .render_pass(self.data.render_pass)
-            .subpass(0)
-            .framebuffer(self.data.framebuffers[image_index]);
+        let color_attachment_formats = &[self.data.swapchain_format];
+        let mut rendering_info = vk::CommandBufferInheritanceRenderingInfo::builder()
+            .color_attachment_formats(color_attachment_formats)
+            .depth_attachment_format(self.data.depth_format)
+            .rasterization_samples(self.data.msaa_samples);
+
+        let mut inheritance_info = vk::CommandBufferInheritanceInfo::builder();
+        if self.data.dynamic_rendering {
+            inheritance_info = inheritance_info.push_next(&mut rendering_info);
+        } else {
+            inheritance_info = inheritance_info
+                .render_pass(self.data.render_pass)
+                .subpass(0)
+                .framebuffer(self.data.framebuffers[image_index]);
+        }
 
         let info = vk::CommandBufferBeginInfo::builder()
             .flags(vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE)
             .inheritance_info(&inheritance_info);
 
         self.device.begin_command_buffer(command_buffer, &info)?;
+        let label = format!("model[{}]", model_index);
+        unsafe {
+            begin_label(
+                &self.instance,
+                &self.data,
+                command_buffer,
+                &label,
+                DRAW_LABEL_COLOR,
+            )?
+        };
 
         self.device.cmd_bind_pipeline(
             command_buffer,
             vk::PipelineBindPoint::GRAPHICS,
-            self.data.pipeline,
+            self.data.pipeline.handle(),
+        );
+        let area = vk::Rect2D::builder()
+            .offset(vk::Offset2D::default())
+            .extent(self.data.swapchain_extent)
+            .build();
+        unsafe { set_viewport(&self.device, command_buffer, area) };
+        self.device.cmd_bind_vertex_buffers(
+            command_buffer,
+            0,
+            &[self.data.vertex_buffer.handle()],
+            &[0],
         );
-        self.device
-            .cmd_bind_vertex_buffers(command_buffer, 0, &[self.data.vertex_buffer], &[0]);
         self.device.cmd_bind_index_buffer(
             command_buffer,
-            self.data.index_buffer,
+            self.data.index_buffer.handle(),
             0,
             vk::IndexType::UINT32,
         );
@@ -164,33 +314,39 @@ impl App {
             vk::PipelineBindPoint::GRAPHICS,
             self.data.pipeline_layout,
             0,
-            &[self.data.descriptor_sets[image_index]],
-            &[],
-        );
-        self.device.cmd_push_constants(
-            command_buffer,
-            self.data.pipeline_layout,
-            vk::ShaderStageFlags::VERTEX,
-            0,
-            model_bytes,
-        );
-        self.device.cmd_push_constants(
-            command_buffer,
-            self.data.pipeline_layout,
-            vk::ShaderStageFlags::FRAGMENT,
-            64,
-            &0.25f32.to_ne_bytes()[..],
+            &[self.data.descriptor_set],
+            &[uniform_offset],
         );
+        unsafe {
+            push_constants(
+                &self.device,
+                command_buffer,
+                self.data.pipeline_layout,
+                &ModelConstants { model },
+            );
+            push_constants(
+                &self.device,
+                command_buffer,
+                self.data.pipeline_layout,
+                &OpacityConstants { opacity: 0.25 },
+            );
+        }
         self.device
             .cmd_draw_indexed(command_buffer, self.data.indices.len() as u32, 1, 0, 0, 0);
 
+        unsafe { end_label(&self.instance, &self.data, command_buffer) };
         self.device.end_command_buffer(command_buffer)?;
         Ok(command_buffer)
     }
 
     /// # Safety
     /// This is a vulkan using function and thus is unsafe
-    unsafe fn update_command_buffer(&mut self, image_index: usize) -> Result<()> {
+    unsafe fn update_command_buffer(
+        &mut self,
+        image_index: usize,
+        time: f32,
+        uniform_offset: u32,
+    ) -> Result<()> {
         let command_buffer = self.data.command_buffers[image_index];
 
         self.device
@@ -201,44 +357,34 @@ impl App {
 
         self.device.begin_command_buffer(command_buffer, &info)?;
 
-        let render_area = vk::Rect2D::builder()
-            .offset(vk::Offset2D::default())
-            .extent(self.data.swapchain_extent);
-
-        let color_clear_value = vk::ClearValue {
-            color: vk::ClearColorValue {
-                float32: [0.0, 0.0, 0.0, 1.0],
-            },
+        let pass = scene_pass(&self.data, image_index);
+        unsafe {
+            prepare_scene_targets(&self.device, &mut self.data, command_buffer, image_index)?
         };
 
-        let depth_clear_value = vk::ClearValue {
-            depth_stencil: vk::ClearDepthStencilValue {
-                depth: 1.0,
-                stencil: 0,
-            },
+        unsafe {
+            begin_label(
+                &self.instance,
+                &self.data,
+                command_buffer,
+                "scene pass",
+                PASS_LABEL_COLOR,
+            )?
         };
-
-        let clear_values = &[color_clear_value, depth_clear_value];
-        let info = vk::RenderPassBeginInfo::builder()
-            .render_pass(self.data.render_pass)
-            .framebuffer(self.data.framebuffers[image_index])
-            .render_area(render_area)
-            .clear_values(clear_values);
-
-        self.device.cmd_begin_render_pass(
-            command_buffer,
-            &info,
-            vk::SubpassContents::SECONDARY_COMMAND_BUFFERS,
-        );
+        unsafe { begin_pass(&self.device, &self.data, command_buffer, image_index, &pass) };
 
         let secondary_command_buffer = (0..self.models)
-            .map(|i| self.update_secondary_command_buffer(image_index, i))
+            .map(|i| self.update_secondary_command_buffer(image_index, i, time, uniform_offset))
             .collect::<Result<Vec<_>, _>>()?;
 
         self.device
             .cmd_execute_commands(command_buffer, &secondary_command_buffer[..]);
 
-        self.device.cmd_end_render_pass(command_buffer);
+        unsafe {
+            end_pass(&self.device, &self.data, command_buffer);
+            end_label(&self.instance, &self.data, command_buffer);
+            finish_scene_targets(&self.device, &mut self.data, command_buffer, image_index)?;
+        }
 
         self.device.end_command_buffer(command_buffer)?;
 
@@ -250,83 +396,148 @@ impl App {
     pub unsafe fn recreate_swapchain(&mut self, window: &Window) -> Result<()> {
         unsafe {
             self.device.device_wait_idle()?;
-            self.device.destroy_buffer(self.data.vertex_buffer, None);
+            let format = self.data.swapchain_format;
             self.destroy_swapchain();
 
             // recreate the swapchain
             create_swapchain(window, &self.instance, &self.device, &mut self.data)?;
             create_swapchain_image_views(&self.device, &mut self.data)?;
-            create_render_pass(&self.instance, &self.device, &mut self.data)?;
-            create_descriptor_set_layout(&self.device, &mut self.data)?;
-            create_pipeline(&self.device, &mut self.data)?;
+
+            // the viewport and scissor are dynamic, so the pipeline only
+            // depends on the format, which a resize leaves alone
+            if self.data.swapchain_format != format {
+                self.destroy_pipeline();
+                if !self.data.dynamic_rendering {
+                    create_render_pass(&self.instance, &self.device, &mut self.data)?;
+                }
+                create_pipeline(&self.device, &mut self.data)?;
+            }
 
             create_color_objects(&self.instance, &self.device, &mut self.data)?;
             create_depth_objects(&self.instance, &self.device, &mut self.data)?;
-            create_framebuffers(&self.device, &mut self.data)?;
-            create_vertex_buffer(&self.instance, &self.device, &mut self.data)?;
-            create_index_buffer(&self.instance, &self.device, &mut self.data)?;
+            if !self.data.dynamic_rendering {
+                create_framebuffers(&self.device, &mut self.data)?;
+            }
 
-            create_uniform_buffers(&self.instance, &self.device, &mut self.data)?;
-            create_descriptor_pool(&self.device, &mut self.data)?;
-            create_descriptor_sets(&self.device, &mut self.data)?;
             create_command_buffers(&self.device, &mut self.data)?;
+
+            name_objects(&self.instance, &self.device, &self.data)?;
         }
 
         self.data
             .images_in_flight
-            .resize(self.data.swapchain_images.len(), vk::Fence::null());
+            .resize(self.data.swapchain_images.len(), 0);
 
         Ok(())
     }
 
     unsafe fn destroy_swapchain(&mut self) {
         unsafe {
+            // the owned resources retire themselves when dropped
+            drop(mem::take(&mut self.data.color_image_view));
+            drop(mem::take(&mut self.data.color_image));
+            drop(mem::take(&mut self.data.depth_image_view));
+            drop(mem::take(&mut self.data.depth_image));
+            drop(mem::take(&mut self.data.swapchain_image_views));
+            drop(mem::take(&mut self.data.offscreen_image));
+
+            let registry = &self.data.registry;
+
+            self.data.framebuffers.drain(..).for_each(|f| {
+                self.device.destroy_framebuffer(f, None);
+                registry.destroyed(f);
+            });
+
+            // secondary command buffers are recorded per image, so they go
+            // along with the primary ones
+            let command_buffers = self
+                .data
+                .command_buffers
+                .drain(..)
+                .chain(self.data.secondary_command_buffers.drain(..).flatten())
+                .collect::<Vec<_>>();
             self.device
-                .destroy_image_view(self.data.color_image_view, None);
-            self.device.free_memory(self.data.color_image_memory, None);
-            self.device
-                .destroy_image_view(self.data.color_image_view, None);
+                .free_command_buffers(self.data.command_pool, &command_buffers);
+            command_buffers.iter().for_each(|c| registry.destroyed(*c));
+
+            if !self.data.surface.is_null() {
+                self.data
+                    .swapchain_images
+                    .iter()
+                    .for_each(|i| self.data.layouts.forget(*i));
+                self.device.destroy_swapchain_khr(self.data.swapchain, None);
+                registry.destroyed(self.data.swapchain);
+            }
+            self.data.swapchain_images.clear();
+        }
+    }
 
-            self.device
-                .destroy_image_view(self.data.depth_image_view, None);
-            self.device.free_memory(self.data.depth_image_memory, None);
-            self.device.destroy_image(self.data.depth_image, None);
+    /// destroys the scene pipeline along with its layout and render pass
+    unsafe fn destroy_pipeline(&mut self) {
+        let registry = &self.data.registry;
 
+        drop(mem::take(&mut self.data.pipeline));
+        unsafe {
             self.device
-                .destroy_descriptor_pool(self.data.descriptor_pool, None);
+                .destroy_pipeline_layout(self.data.pipeline_layout, None);
+            self.device.destroy_render_pass(self.data.render_pass, None);
+        }
+        registry.destroyed(self.data.pipeline_layout);
+        registry.destroyed(self.data.render_pass);
+        self.data.render_pass = vk::RenderPass::null();
+    }
 
-            self.data
-                .uniform_buffers
-                .iter()
-                .for_each(|b| self.device.destroy_buffer(*b, None));
-            self.data
-                .uniform_buffers_memory
-                .iter()
-                .for_each(|m| self.device.free_memory(*m, None));
+    /// the semaphores and stages the next graphics submission waits on, which
+    /// are the given one when it isn't null and every queued graphics wait
+    fn take_graphics_waits(
+        &mut self,
+        semaphore: vk::Semaphore,
+        stage: vk::PipelineStageFlags,
+    ) -> (Vec<vk::Semaphore>, Vec<vk::PipelineStageFlags>) {
+        Some((semaphore, stage))
+            .filter(|(s, _)| !s.is_null())
+            .into_iter()
+            .chain(self.data.graphics_waits.drain(..))
+            .unzip()
+    }
 
-            self.data
-                .framebuffers
-                .iter()
-                .for_each(|f| self.device.destroy_framebuffer(*f, None));
+    /// recreates the scene pipeline when its shaders changed on disk, keeping
+    /// the old one when they fail to compile
+    unsafe fn reload_shaders(&mut self) {
+        let Some(watcher) = &mut self.shader_watcher else {
+            return;
+        };
 
-            self.device
-                .free_command_buffers(self.data.command_pool, &self.data.command_buffers);
-            self.device.destroy_pipeline(self.data.pipeline, None);
-            self.device
-                .destroy_pipeline_layout(self.data.pipeline_layout, None);
-            self.device.destroy_render_pass(self.data.render_pass, None);
-            self.data
-                .swapchain_image_views
-                .iter()
-                .for_each(|v| self.device.destroy_image_view(*v, None));
-            self.device.destroy_swapchain_khr(self.data.swapchain, None);
+        let changed = watcher.changed();
+        if changed.is_empty() {
+            return;
+        }
+
+        for path in &changed {
+            log::info!("Shader `{}` changed", path.display());
+        }
+
+        let dir = watcher.dir().to_path_buf();
+        match unsafe { reload_pipeline(&self.device, &mut self.data, &dir) } {
+            Ok(()) => log::info!("Reloaded the scene pipeline"),
+            Err(error) => log::error!("{:#}\nKeeping the old scene pipeline", error),
         }
     }
 
     /// renders the frame for the vulkan application
     pub unsafe fn render(&mut self, window: &Window) -> Result<()> {
-        self.device
-            .wait_for_fences(&[self.data.in_flight_fences[self.frame]], true, u64::MAX)?;
+        unsafe {
+            collect_finished_uploads(&self.device, &mut self.data)?;
+            flush_deletions(&self.device, &mut self.data)?;
+            self.reload_shaders();
+        }
+
+        // the frame slot is free once the last submission that used it is done
+        unsafe {
+            self.data
+                .timeline
+                .wait(&self.device, self.data.frame_values[self.frame])?
+        };
 
         let image_result = self.device.acquire_next_image_khr(
             self.data.swapchain,
@@ -341,37 +552,38 @@ impl App {
             Err(e) => return Err(anyhow!(e)),
         };
 
-        if !self.data.images_in_flight[image_index as usize].is_null() {
-            self.device.wait_for_fences(
-                &[self.data.images_in_flight[image_index as usize]],
-                true,
-                u64::MAX,
-            )?;
-        }
-
-        self.data.images_in_flight[image_index as usize] = self.data.in_flight_fences[self.frame];
+        unsafe {
+            self.data
+                .timeline
+                .wait(&self.device, self.data.images_in_flight[image_index])?
+        };
 
-        self.update_command_buffer(image_index)?;
-        update_uniform_buffer(&self.device, &self.start, &self.data, image_index)?;
+        // sample the clock once so every part of the frame sees the same time
+        let time = self.clock.seconds();
+        self.data.uniforms.begin_frame(self.frame);
+        unsafe { allocate_frame_descriptors(&self.device, &mut self.data, self.frame)? };
+        let uniform_offset = update_uniform_buffer(&mut self.data)?;
+        self.update_command_buffer(image_index, time, uniform_offset)?;
 
-        let wait_semaphores = &[self.data.image_available_semaphore[self.frame]];
-        let wait_stages = &[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-        let command_buffers = &[self.data.command_buffers[image_index as usize]];
+        let (wait_semaphores, wait_stages) = self.take_graphics_waits(
+            self.data.image_available_semaphore[self.frame],
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        );
+        let command_buffers = &[self.data.command_buffers[image_index]];
         let signal_semaphores = &[self.data.render_finished_semaphore[self.frame]];
-        let submit_info = vk::SubmitInfo::builder()
-            .wait_semaphores(wait_semaphores)
-            .wait_dst_stage_mask(wait_stages)
-            .command_buffers(command_buffers)
-            .signal_semaphores(signal_semaphores);
-
-        self.device
-            .reset_fences(&[self.data.in_flight_fences[self.frame]])?;
+        let value = unsafe {
+            submit_graphics(
+                &self.device,
+                &mut self.data,
+                command_buffers,
+                &wait_semaphores,
+                &wait_stages,
+                signal_semaphores,
+            )?
+        };
 
-        self.device.queue_submit(
-            self.data.graphics_queue,
-            &[submit_info],
-            self.data.in_flight_fences[self.frame],
-        )?;
+        self.data.frame_values[self.frame] = value;
+        self.data.images_in_flight[image_index] = value;
 
         let swapchains = &[self.data.swapchain];
         let image_indices = &[image_index as u32];
@@ -394,104 +606,250 @@ impl App {
         }
 
         self.frame = (self.frame + 1) % MAX_FRAMES_IN_FLIGHT;
+        self.clock.tick();
 
         Ok(())
     }
 
-    /// destroys the vulkan app
+    /// renders a frame into the offscreen target of a headless app, and
+    /// returns the rendered frame as tightly packed rgba pixels
+    ///
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    pub unsafe fn render_offscreen(&mut self) -> Result<Vec<u8>> {
+        unsafe {
+            collect_finished_uploads(&self.device, &mut self.data)?;
+            flush_deletions(&self.device, &mut self.data)?;
+
+            self.data
+                .timeline
+                .wait(&self.device, self.data.frame_values[self.frame])?;
+
+            let time = self.clock.seconds();
+            self.data.uniforms.begin_frame(self.frame);
+            allocate_frame_descriptors(&self.device, &mut self.data, self.frame)?;
+            let uniform_offset = update_uniform_buffer(&mut self.data)?;
+            self.update_command_buffer(0, time, uniform_offset)?;
+
+            let (wait_semaphores, wait_stages) =
+                self.take_graphics_waits(vk::Semaphore::null(), vk::PipelineStageFlags::empty());
+            let command_buffers = &[self.data.command_buffers[0]];
+            let value = submit_graphics(
+                &self.device,
+                &mut self.data,
+                command_buffers,
+                &wait_semaphores,
+                &wait_stages,
+                &[],
+            )?;
+
+            self.data.frame_values[self.frame] = value;
+            self.data.images_in_flight[0] = value;
+
+            // the pixels are read back as soon as this frame is finished
+            self.data.timeline.wait(&self.device, value)?;
+
+            self.frame = (self.frame + 1) % MAX_FRAMES_IN_FLIGHT;
+            self.clock.tick();
+
+            read_offscreen_pixels(&self.instance, &self.device, &mut self.data)
+        }
+    }
+
+    /// renders a frame into the offscreen target of a headless app, and
+    /// writes it out as a png image
+    ///
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    pub unsafe fn render_offscreen_png(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let pixels = unsafe { self.render_offscreen()? };
+        let extent = self.data.swapchain_extent;
+        save_png(path, extent.width, extent.height, &pixels)
+    }
+
+    /// the instance the app was created with
+    pub fn instance(&self) -> &Instance {
+        &self.instance
+    }
+
+    /// the vulkan objects the app owns, for work outside of the frame such
+    /// as running compute shaders
+    pub fn data(&self) -> &AppData {
+        &self.data
+    }
+
+    /// the vulkan objects the app owns, for creating objects that share its
+    /// caches such as compute pipelines
+    pub fn data_mut(&mut self) -> &mut AppData {
+        &mut self.data
+    }
+
+    /// the vulkan objects of the app that are alive, see [`ResourceRegistry`]
+    pub fn resources(&self) -> &ResourceRegistry {
+        &self.data.registry
+    }
+
+    /// destroys the vulkan app, reporting every object that is still alive
+    /// afterwards when resource tracking is enabled
     pub unsafe fn destroy(&mut self) {
+        if let Err(error) = unsafe { destroy_uploads(&self.device, &mut self.data) } {
+            log::error!("Failed to wait on pending uploads: {}", error);
+        }
+
         self.destroy_swapchain();
-        self.device.destroy_sampler(self.data.texture_sampler, None);
-        self.device
-            .destroy_image_view(self.data.texture_image_view, None);
+        unsafe {
+            self.destroy_pipeline();
+            if let Err(error) = self.data.pipeline_cache.save(&self.device) {
+                log::warn!("Failed to save the pipeline cache: {}", error);
+            }
+            self.data.pipeline_cache.destroy(&self.device);
+        }
+        self.data.registry.destroyed(self.data.pipeline_cache.cache);
+        drop(mem::take(&mut self.data.texture_sampler));
+        drop(mem::take(&mut self.data.texture_image_view));
+        drop(mem::take(&mut self.data.texture_image));
 
-        self.device.destroy_image(self.data.texture_image, None);
-        self.device
-            .free_memory(self.data.texture_image_memory, None);
+        unsafe {
+            for allocator in &mut self.data.frame_descriptors {
+                allocator.destroy(&self.device, &self.data.registry);
+            }
+            self.data
+                .descriptor_layouts
+                .destroy(&self.device, &self.data.registry);
+        }
+        drop(mem::take(&mut self.data.uniforms));
+        drop(mem::take(&mut self.data.index_buffer));
+        drop(mem::take(&mut self.data.vertex_buffer));
 
-        self.device
-            .destroy_descriptor_set_layout(self.data.descriptor_set_layout, None);
-        self.device.destroy_buffer(self.data.index_buffer, None);
-        self.device.free_memory(self.data.index_buffer_memory, None);
-        self.device.destroy_buffer(self.data.vertex_buffer, None);
-        self.device
-            .free_memory(self.data.vertex_buffer_memory, None);
+        // the device is idle, so nothing retired is in use anymore
+        unsafe { destroy_deletions(&self.device, &mut self.data) };
 
         // destroy the sync objects
-        self.data
-            .in_flight_fences
-            .iter()
-            .for_each(|f| self.device.destroy_fence(*f, None));
+        unsafe { self.data.timeline.destroy(&self.device) };
+        self.data.registry.destroyed(self.data.timeline.semaphore);
         self.data
             .render_finished_semaphore
             .iter()
-            .for_each(|s| self.device.destroy_semaphore(*s, None));
-        self.data
-            .image_available_semaphore
-            .iter()
-            .for_each(|s| self.device.destroy_semaphore(*s, None));
+            .chain(&self.data.image_available_semaphore)
+            .for_each(|s| {
+                self.device.destroy_semaphore(*s, None);
+                self.data.registry.destroyed(*s);
+            });
 
-        // destroy the command pool
-        self.device
-            .destroy_command_pool(self.data.command_pool, None);
+        // destroy the command pools
+        unsafe {
+            [
+                self.data.command_pool,
+                self.data.transfer_command_pool,
+                self.data.compute_command_pool,
+            ]
+            .into_iter()
+            .for_each(|p| {
+                self.device.destroy_command_pool(p, None);
+                self.data.registry.destroyed(p);
+            });
+            destroy_allocator(&self.device, &self.data);
+        }
         self.device.destroy_device(None);
 
-        if VALIDATION_ENABLED {
+        self.data.registry.report_leaks();
+
+        if !self.data.messenger.is_null() {
             self.instance
                 .destroy_debug_utils_messenger_ext(self.data.messenger, None);
         }
 
-        self.instance.destroy_surface_khr(self.data.surface, None);
+        if !self.data.surface.is_null() {
+            self.instance.destroy_surface_khr(self.data.surface, None);
+        }
         self.instance.destroy_instance(None);
     }
 }
 
 /// the vulkan handles and associated properties utilized
 /// by the vulkan application
-#[derive(Clone, Debug, Default)]
+#[derive(Debug, Default)]
 pub struct AppData {
     pub surface: vk::SurfaceKHR,
     pub messenger: vk::DebugUtilsMessengerEXT,
     pub physical_device: vk::PhysicalDevice,
     pub graphics_queue: vk::Queue,
     pub present_queue: vk::Queue,
+    pub transfer_queue: vk::Queue,
+    pub compute_queue: vk::Queue,
     pub swapchain: vk::SwapchainKHR,
     pub swapchain_images: Vec<vk::Image>,
     pub swapchain_format: vk::Format,
     pub swapchain_extent: vk::Extent2D,
-    pub swapchain_image_views: Vec<vk::ImageView>,
+    pub swapchain_image_views: Vec<ImageView>,
+    /// null when the scene is drawn with dynamic rendering
     pub render_pass: vk::RenderPass,
+    /// owned by the `descriptor_layouts`
     pub descriptor_set_layout: vk::DescriptorSetLayout,
+    /// every descriptor set layout, see [`DescriptorLayoutCache`]
+    pub descriptor_layouts: DescriptorLayoutCache,
     pub pipeline_layout: vk::PipelineLayout,
-    pub pipeline: vk::Pipeline,
+    pub pipeline: Pipeline,
+    pub scene_shaders: SceneShaders,
+    /// shared by every pipeline, see [`PipelineCache`]
+    pub pipeline_cache: PipelineCache,
+    /// empty when the scene is drawn with dynamic rendering
     pub framebuffers: Vec<vk::Framebuffer>,
     pub command_pool: vk::CommandPool,
+    pub transfer_command_pool: vk::CommandPool,
+    pub compute_command_pool: vk::CommandPool,
+    /// semaphores the next frame submitted to the graphics queue waits on,
+    /// such as the ones signalled by compute work it reads the output of
+    pub graphics_waits: Vec<(vk::Semaphore, vk::PipelineStageFlags)>,
+    pub pending_uploads: Vec<PendingUpload>,
     pub command_buffers: Vec<vk::CommandBuffer>,
     pub secondary_command_buffers: Vec<Vec<vk::CommandBuffer>>,
     pub image_available_semaphore: Vec<vk::Semaphore>,
     pub render_finished_semaphore: Vec<vk::Semaphore>,
-    pub in_flight_fences: Vec<vk::Fence>,
-    pub images_in_flight: Vec<vk::Fence>,
+    /// counts the graphics submissions, see [`GraphicsTimeline`]
+    pub timeline: GraphicsTimeline,
+    /// the timeline value of the last submission of each frame in flight
+    pub frame_values: Vec<u64>,
+    /// the timeline value of the last submission rendering to each image
+    pub images_in_flight: Vec<u64>,
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
-    pub vertex_buffer: vk::Buffer,
-    pub vertex_buffer_memory: vk::DeviceMemory,
-    pub index_buffer: vk::Buffer,
-    pub index_buffer_memory: vk::DeviceMemory,
-    pub uniform_buffers: Vec<vk::Buffer>,
-    pub uniform_buffers_memory: Vec<vk::DeviceMemory>,
-    pub descriptor_pool: vk::DescriptorPool,
-    pub descriptor_sets: Vec<vk::DescriptorSet>,
+    pub vertex_buffer: Buffer,
+    pub index_buffer: Buffer,
+    /// the per frame uniform data of the scene, see [`UniformRing`]
+    pub uniforms: UniformRing,
+    /// allocates the descriptor sets of each frame in flight, reset when the
+    /// frame starts
+    pub frame_descriptors: Vec<DescriptorAllocator>,
+    /// what the scene descriptor set of every frame is written with
+    pub scene_descriptors: DescriptorWriter,
+    /// the scene descriptor set of the current frame
+    pub descriptor_set: vk::DescriptorSet,
     pub mip_levels: u32,
-    pub texture_image: vk::Image,
-    pub texture_image_memory: vk::DeviceMemory,
-    pub texture_image_view: vk::ImageView,
-    pub texture_sampler: vk::Sampler,
-    pub depth_image: vk::Image,
-    pub depth_image_memory: vk::DeviceMemory,
-    pub depth_image_view: vk::ImageView,
+    pub texture_image: Image,
+    pub texture_image_view: ImageView,
+    pub texture_sampler: Sampler,
+    pub depth_image: Image,
+    pub depth_image_view: ImageView,
+    pub depth_format: vk::Format,
     pub msaa_samples: vk::SampleCountFlags,
-    pub color_image: vk::Image,
-    pub color_image_memory: vk::DeviceMemory,
-    pub color_image_view: vk::ImageView,
+    pub color_image: Image,
+    pub color_image_view: ImageView,
+    /// the image rendered into when headless, also the only entry of
+    /// `swapchain_images`
+    pub offscreen_image: Image,
+    pub enabled_features: EnabledFeatures,
+    /// whether passes are recorded with dynamic rendering instead of the
+    /// scene render pass and framebuffers
+    pub dynamic_rendering: bool,
+    pub validation_sink: Option<ValidationSink>,
+    /// the current layout and last access of every image barriers are
+    /// recorded for
+    pub layouts: LayoutTracker,
+    /// sub-allocates the memory of every buffer and image
+    pub allocator: Allocator,
+    /// owned resources that were dropped but may still be in use by the gpu
+    pub deletions: DeletionQueue,
+    /// every live vulkan object when resource tracking is enabled
+    pub registry: ResourceRegistry,
 }
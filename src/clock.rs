@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+/// the clock that the app and all of its animation read the current time
+/// from, so frames can either follow the wall clock or be reproduced exactly
+#[derive(Clone, Debug)]
+pub enum Clock {
+    /// follows the wall clock from the moment the clock was created
+    RealTime { start: Instant },
+    /// advances by a fixed step every rendered frame, regardless of how long
+    /// the frame actually took
+    FixedStep { step: Duration, frames: u64 },
+    /// only moves when it is explicitly set or advanced
+    Manual { time: Duration },
+}
+
+impl Clock {
+    /// a clock following the wall clock starting from now
+    pub fn real_time() -> Self {
+        Self::RealTime {
+            start: Instant::now(),
+        }
+    }
+
+    /// a clock starting at zero that advances by `step` every frame
+    pub fn fixed_step(step: Duration) -> Self {
+        Self::FixedStep { step, frames: 0 }
+    }
+
+    /// a clock frozen at `time` until it is set or advanced
+    pub fn manual(time: Duration) -> Self {
+        Self::Manual { time }
+    }
+
+    /// the current time of the clock
+    pub fn time(&self) -> Duration {
+        match self {
+            Self::RealTime { start } => start.elapsed(),
+            Self::FixedStep { step, frames } => u32::try_from(*frames)
+                .ok()
+                .and_then(|f| step.checked_mul(f))
+                .unwrap_or_else(|| {
+                    // past u32::MAX frames, which only a tiny step gets to
+                    Duration::try_from_secs_f64(step.as_secs_f64() * *frames as f64)
+                        .unwrap_or(Duration::MAX)
+                }),
+            Self::Manual { time } => *time,
+        }
+    }
+
+    /// the current time of the clock in seconds
+    pub fn seconds(&self) -> f32 {
+        self.time().as_secs_f32()
+    }
+
+    /// called once after every rendered frame, moves fixed step clocks on to
+    /// the next frame
+    pub fn tick(&mut self) {
+        if let Self::FixedStep { frames, .. } = self {
+            *frames = frames.saturating_add(1);
+        }
+    }
+
+    /// sets the current time of a manual clock, this jumps a fixed step clock
+    /// to the frame closest to `time` and has no effect on real time clocks
+    pub fn set(&mut self, time: Duration) {
+        match self {
+            Self::RealTime { .. } => {}
+            Self::FixedStep { step, frames } => {
+                *frames = if step.is_zero() {
+                    0
+                } else {
+                    (time.as_secs_f64() / step.as_secs_f64()).round() as u64
+                };
+            }
+            Self::Manual { time: current } => *current = time,
+        }
+    }
+
+    /// advances a manual clock by `delta`, see [`Clock::set`] for the other
+    /// kinds of clock. The time stops at `Duration::MAX` instead of
+    /// overflowing.
+    pub fn advance(&mut self, delta: Duration) {
+        let time = self.time().saturating_add(delta);
+        self.set(time);
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::real_time()
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+
+use crate::foundation::registry::TRACK_RESOURCES_ENV;
+use crate::foundation::requirements::{DeviceRequirements, Feature};
+use crate::foundation::selection::GpuSelector;
+use crate::foundation::validation::ValidationConfig;
+use crate::pipeline::cache::{PIPELINE_CACHE_ENV, default_cache_path};
+use crate::pipeline::reload::HOT_RELOAD_ENV;
+use crate::pipeline::shader::{DEFAULT_SHADER_DIR, SHADER_DIR_ENV};
+
+/// the environment variable used to pick a specific GPU, see [`GpuSelector`]
+/// for the accepted formats
+pub const GPU_ENV: &str = "CHOAM_GPU";
+
+/// the environment variable turning dynamic rendering off with `0`, or back
+/// on with `1`
+pub const DYNAMIC_RENDERING_ENV: &str = "CHOAM_DYNAMIC_RENDERING";
+
+/// the options the vulkan application is created with
+#[derive(Clone, Debug)]
+pub struct AppConfig {
+    /// picks a specific physical device instead of the highest scoring one
+    pub gpu: Option<GpuSelector>,
+    /// the extensions and features the device is created with, the swapchain
+    /// extension is added on top of these when rendering to a window
+    pub requirements: DeviceRequirements,
+    /// how the validation layers are set up, on by default in debug builds
+    pub validation: ValidationConfig,
+    /// records passes with dynamic rendering when the device supports it,
+    /// falling back to a render pass and framebuffers otherwise
+    pub dynamic_rendering: bool,
+    /// records every created vulkan object and reports the ones still alive
+    /// on shutdown, on by default in debug builds
+    pub track_resources: bool,
+    /// the file compiled pipelines are loaded from on startup and saved to on
+    /// shutdown, in the user cache directory by default
+    pub pipeline_cache: Option<PathBuf>,
+    /// recompiles the shaders when they change on disk and recreates the
+    /// pipelines using them, on by default in debug builds
+    pub hot_reload: bool,
+    /// the directory the shader sources are hot reloaded from, `shaders` in
+    /// the working directory by default
+    pub shader_dir: PathBuf,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            gpu: None,
+            // frames are paced with a timeline semaphore instead of fences,
+            // which limits the app to vulkan 1.2 devices
+            requirements: DeviceRequirements::new()
+                .require_feature(Feature::TimelineSemaphore)
+                .optional_feature(Feature::SamplerAnisotropy)
+                .optional_feature(Feature::DynamicRendering)
+                .optional_feature(Feature::Synchronization2),
+            validation: ValidationConfig::default(),
+            dynamic_rendering: true,
+            track_resources: cfg!(debug_assertions),
+            pipeline_cache: default_cache_path(),
+            hot_reload: cfg!(debug_assertions),
+            shader_dir: PathBuf::from(DEFAULT_SHADER_DIR),
+        }
+    }
+}
+
+impl AppConfig {
+    /// the config with every option that is set in the environment applied
+    pub fn from_env() -> Result<Self> {
+        let mut config = Self::default();
+
+        if let Ok(gpu) = std::env::var(GPU_ENV) {
+            config.gpu = Some(
+                gpu.parse()
+                    .map_err(|e| anyhow!("Invalid {} `{}`: {}", GPU_ENV, gpu, e))?,
+            );
+        }
+
+        match std::env::var(DYNAMIC_RENDERING_ENV).ok().as_deref() {
+            None => {}
+            Some("1") | Some("true") => config.dynamic_rendering = true,
+            Some("0") | Some("false") => config.dynamic_rendering = false,
+            Some(value) => {
+                return Err(anyhow!(
+                    "Invalid {} `{}`, expected 0 or 1",
+                    DYNAMIC_RENDERING_ENV,
+                    value
+                ));
+            }
+        }
+
+        match std::env::var(TRACK_RESOURCES_ENV).ok().as_deref() {
+            None => {}
+            Some("1") | Some("true") => config.track_resources = true,
+            Some("0") | Some("false") => config.track_resources = false,
+            Some(value) => {
+                return Err(anyhow!(
+                    "Invalid {} `{}`, expected 0 or 1",
+                    TRACK_RESOURCES_ENV,
+                    value
+                ));
+            }
+        }
+
+        match std::env::var(HOT_RELOAD_ENV).ok().as_deref() {
+            None => {}
+            Some("1") | Some("true") => config.hot_reload = true,
+            Some("0") | Some("false") => config.hot_reload = false,
+            Some(value) => {
+                return Err(anyhow!(
+                    "Invalid {} `{}`, expected 0 or 1",
+                    HOT_RELOAD_ENV,
+                    value
+                ));
+            }
+        }
+
+        match std::env::var_os(PIPELINE_CACHE_ENV) {
+            None => {}
+            Some(path) if path == "0" || path == "false" || path.is_empty() => {
+                config.pipeline_cache = None
+            }
+            Some(path) => config.pipeline_cache = Some(PathBuf::from(path)),
+        }
+
+        if let Some(dir) = std::env::var_os(SHADER_DIR_ENV).filter(|d| !d.is_empty()) {
+            config.shader_dir = PathBuf::from(dir);
+        }
+
+        config.validation.apply_env()?;
+
+        Ok(config)
+    }
+}
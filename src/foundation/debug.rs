@@ -0,0 +1,237 @@
+use std::ffi::CString;
+
+use anyhow::Result;
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::ExtDebugUtilsExtensionInstanceCommands;
+
+use crate::app::AppData;
+use crate::pipeline::resources::ImageView;
+use crate::scenes::models::MODEL_NAME;
+
+/// the colors command buffer labels are shown with in graphics debuggers
+pub const PASS_LABEL_COLOR: [f32; 4] = [0.2, 0.6, 1.0, 1.0];
+pub const DRAW_LABEL_COLOR: [f32; 4] = [1.0, 0.6, 0.2, 1.0];
+
+/// the debug utils extension is only loaded alongside the validation layers,
+/// so objects are only named and labelled when the messenger exists
+pub fn debug_utils_enabled(data: &AppData) -> bool {
+    !data.messenger.is_null()
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Gives the object a name that validation messages and graphics debuggers
+/// refer to it by, does nothing when debug utils are not enabled.
+pub unsafe fn set_object_name<H>(
+    instance: &Instance,
+    device: &Device,
+    data: &AppData,
+    handle: H,
+    name: &str,
+) -> Result<()>
+where
+    H: vk::Handle,
+    H::Repr: TryInto<u64>,
+{
+    data.registry.named(handle, name);
+
+    if !debug_utils_enabled(data) || handle.is_null() {
+        return Ok(());
+    }
+
+    let Ok(object_handle) = handle.as_raw().try_into() else {
+        return Ok(());
+    };
+
+    let name = CString::new(name)?;
+    let info = vk::DebugUtilsObjectNameInfoEXT::builder()
+        .object_type(H::TYPE)
+        .object_handle(object_handle)
+        .object_name(name.as_bytes_with_nul());
+
+    unsafe { instance.set_debug_utils_object_name_ext(device.handle(), &info)? };
+    Ok(())
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Names every handle in the slice after its index, e.g. `framebuffer[0]`.
+pub unsafe fn set_object_names<H>(
+    instance: &Instance,
+    device: &Device,
+    data: &AppData,
+    handles: &[H],
+    name: &str,
+) -> Result<()>
+where
+    H: vk::Handle,
+    H::Repr: TryInto<u64>,
+{
+    for (i, handle) in handles.iter().enumerate() {
+        unsafe { set_object_name(instance, device, data, *handle, &format!("{}[{}]", name, i))? };
+    }
+
+    Ok(())
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Opens a labelled region in the command buffer, which must be closed again
+/// with [`end_label`] in the same command buffer.
+pub unsafe fn begin_label(
+    instance: &Instance,
+    data: &AppData,
+    command_buffer: vk::CommandBuffer,
+    name: &str,
+    color: [f32; 4],
+) -> Result<()> {
+    if !debug_utils_enabled(data) {
+        return Ok(());
+    }
+
+    let name = CString::new(name)?;
+    let label = vk::DebugUtilsLabelEXT::builder()
+        .label_name(name.as_bytes_with_nul())
+        .color(color);
+
+    unsafe { instance.cmd_begin_debug_utils_label_ext(command_buffer, &label) };
+    Ok(())
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+pub unsafe fn end_label(instance: &Instance, data: &AppData, command_buffer: vk::CommandBuffer) {
+    if debug_utils_enabled(data) {
+        unsafe { instance.cmd_end_debug_utils_label_ext(command_buffer) };
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Names every long lived object held by the app data, called again after the
+/// swapchain is recreated so the recreated objects are named as well.
+pub unsafe fn name_objects(instance: &Instance, device: &Device, data: &AppData) -> Result<()> {
+    if !debug_utils_enabled(data) && !data.registry.is_enabled() {
+        return Ok(());
+    }
+
+    unsafe {
+        if data.surface.is_null() {
+            set_object_names(instance, device, data, &data.swapchain_images, "offscreen")?;
+        } else {
+            set_object_name(instance, device, data, data.swapchain, "swapchain")?;
+            set_object_names(instance, device, data, &data.swapchain_images, "swapchain")?;
+        }
+        set_object_names(
+            instance,
+            device,
+            data,
+            &data
+                .swapchain_image_views
+                .iter()
+                .map(ImageView::handle)
+                .collect::<Vec<_>>(),
+            "swapchain.view",
+        )?;
+        set_object_names(instance, device, data, &data.framebuffers, "framebuffer")?;
+
+        set_object_name(instance, device, data, data.render_pass, "scene")?;
+        set_object_name(instance, device, data, data.descriptor_set_layout, "scene")?;
+        set_object_name(instance, device, data, data.pipeline_layout, "scene")?;
+        set_object_name(instance, device, data, data.pipeline.handle(), "scene")?;
+
+        set_object_name(instance, device, data, data.command_pool, "graphics")?;
+        set_object_name(
+            instance,
+            device,
+            data,
+            data.transfer_command_pool,
+            "transfer",
+        )?;
+        set_object_name(instance, device, data, data.compute_command_pool, "compute")?;
+        set_object_names(instance, device, data, &data.command_buffers, "frame")?;
+
+        set_object_names(
+            instance,
+            device,
+            data,
+            &data.image_available_semaphore,
+            "image_available",
+        )?;
+        set_object_names(
+            instance,
+            device,
+            data,
+            &data.render_finished_semaphore,
+            "render_finished",
+        )?;
+        set_object_name(instance, device, data, data.timeline.semaphore, "timeline")?;
+
+        set_object_name(instance, device, data, data.uniforms.buffer(), "uniforms")?;
+
+        let model = |suffix: &str| format!("{}.{}", MODEL_NAME, suffix);
+        set_object_name(
+            instance,
+            device,
+            data,
+            data.vertex_buffer.handle(),
+            &model("vertex"),
+        )?;
+        set_object_name(
+            instance,
+            device,
+            data,
+            data.index_buffer.handle(),
+            &model("index"),
+        )?;
+        set_object_name(
+            instance,
+            device,
+            data,
+            data.texture_image.handle(),
+            &model("texture"),
+        )?;
+        set_object_name(
+            instance,
+            device,
+            data,
+            data.texture_image_view.handle(),
+            &model("texture.view"),
+        )?;
+        set_object_name(
+            instance,
+            device,
+            data,
+            data.texture_sampler.handle(),
+            &model("sampler"),
+        )?;
+
+        set_object_name(instance, device, data, data.color_image.handle(), "color")?;
+        set_object_name(
+            instance,
+            device,
+            data,
+            data.color_image_view.handle(),
+            "color.view",
+        )?;
+        set_object_name(instance, device, data, data.depth_image.handle(), "depth")?;
+        set_object_name(
+            instance,
+            device,
+            data,
+            data.depth_image_view.handle(),
+            "depth.view",
+        )?;
+
+        // resources share memory blocks, so the blocks are named instead
+        for (memory, name) in data.allocator.memory_names() {
+            set_object_name(instance, device, data, memory, &name)?;
+        }
+    }
+
+    Ok(())
+}
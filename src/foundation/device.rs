@@ -1,52 +1,150 @@
 use std::collections::HashSet;
 
 use anyhow::{Result, anyhow};
+use log::*;
 use thiserror::Error;
 use vulkanalia::Entry;
 use vulkanalia::vk;
 use vulkanalia::vk::DeviceV1_0;
+use vulkanalia::vk::Handle;
 use vulkanalia::vk::HasBuilder;
 use vulkanalia::vk::InstanceV1_0;
 use vulkanalia::vk::KhrSurfaceExtensionInstanceCommands;
-use vulkanalia::{Device, Instance};
+use vulkanalia::{Device, Instance, Version};
 
 use crate::app::AppData;
+use crate::config::AppConfig;
 use crate::foundation::instance::PORTABILITY_MACOS_VERSION;
-use crate::foundation::instance::VALIDATION_ENABLED;
 use crate::foundation::instance::VALIDATION_LAYER;
+use crate::foundation::requirements::{
+    DYNAMIC_RENDERING_EXTENSION, DeviceRequirements, FEATURES_V1_2_VERSION, FEATURES_V1_3_VERSION,
+    FeatureStructs,
+};
+use crate::foundation::selection::{device_type_name, score_physical_device};
 use crate::foundation::swapchain::SwapchainSupport;
 use crate::scenes::sampling::get_max_msaa_samples;
 
-pub const DEVICE_EXTENSIONS: &[vk::ExtensionName] = &[vk::KHR_SWAPCHAIN_EXTENSION.name];
+/// the device requirements of the app on top of the configured ones, headless
+/// apps (no surface) have nothing to present to and so do not need the
+/// swapchain extension
+pub fn device_requirements(
+    data: &AppData,
+    requirements: &DeviceRequirements,
+) -> DeviceRequirements {
+    let requirements = requirements.clone();
+    if data.surface.is_null() {
+        requirements
+    } else {
+        requirements.require_extension(vk::KHR_SWAPCHAIN_EXTENSION.name)
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// The vulkan version the app can use the physical device with, which is
+/// capped by the version the instance was created with.
+pub unsafe fn get_device_version(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> Version {
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    Version::from(properties.api_version).min(instance.version())
+}
 
 #[derive(Debug, Error)]
 #[error("Missing {0}.")]
 pub struct SuitabilityError(pub &'static str);
 
-pub unsafe fn pick_physical_device(instance: &Instance, data: &mut AppData) -> Result<()> {
-    for physical_device in instance.enumerate_physical_devices()? {
-        let properties = instance.get_physical_device_properties(physical_device);
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Picks the highest scoring suitable physical device, or when a selector is
+/// given the highest scoring suitable device that it matches. The reason each
+/// device was skipped or where it was ranked is logged.
+pub unsafe fn pick_physical_device(
+    instance: &Instance,
+    data: &mut AppData,
+    config: &AppConfig,
+) -> Result<()> {
+    let selector = config.gpu.as_ref();
+    let requirements = device_requirements(data, &config.requirements);
+    let mut candidates = vec![];
 
-        if let Err(error) = check_physical_device(instance, data, physical_device) {
-            println!(
-                "Skipping physical device (`{}`): {}",
-                properties.device_name, error
-            );
-        } else {
-            println!("Selected physical device (`{}`).", properties.device_name);
-            data.physical_device = physical_device;
-            data.msaa_samples = get_max_msaa_samples(instance, data);
-            return Ok(());
+    unsafe {
+        for (index, physical_device) in instance
+            .enumerate_physical_devices()?
+            .into_iter()
+            .enumerate()
+        {
+            let properties = instance.get_physical_device_properties(physical_device);
+
+            if let Some(selector) = selector.filter(|s| !s.matches(index, &properties)) {
+                info!(
+                    "Skipping physical device #{} (`{}`): does not match the GPU selector ({}).",
+                    index, properties.device_name, selector
+                );
+            } else if let Err(error) =
+                check_physical_device(instance, data, &requirements, physical_device)
+            {
+                info!(
+                    "Skipping physical device #{} (`{}`): {}",
+                    index, properties.device_name, error
+                );
+            } else {
+                let score = score_physical_device(instance, physical_device);
+                candidates.push((index, physical_device, properties, score));
+            }
         }
     }
 
-    Err(anyhow!("Failed to find suitable physical device"))
+    // highest score first, ties keep the enumeration order
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.3));
+
+    for (rank, (index, _, properties, score)) in candidates.iter().enumerate() {
+        info!(
+            "Ranked physical device #{} (`{}`) {} of {}: {}, {} MiB device local memory, {}x MSAA.",
+            index,
+            properties.device_name,
+            rank + 1,
+            candidates.len(),
+            device_type_name(properties.device_type),
+            score.device_local_memory / (1024 * 1024),
+            score.max_msaa_samples,
+        );
+    }
+
+    let Some((index, physical_device, properties, _)) = candidates.first() else {
+        return Err(match selector {
+            Some(selector) => anyhow!(
+                "Failed to find suitable physical device matching the GPU selector ({})",
+                selector
+            ),
+            None => anyhow!("Failed to find suitable physical device"),
+        });
+    };
+
+    info!(
+        "Selected physical device #{} (`{}`).",
+        index, properties.device_name
+    );
+
+    data.physical_device = *physical_device;
+    data.msaa_samples = unsafe { get_max_msaa_samples(instance, *physical_device) };
+
+    Ok(())
 }
 
 #[derive(Copy, Clone, Debug)]
 pub struct QueueFamilyIndices {
     pub graphics: u32,
     pub present: u32,
+    /// a transfer only family when the device has one, so uploads run
+    /// alongside rendering, otherwise the graphics family
+    pub transfer: u32,
+    /// a compute family without graphics when the device has one, so compute
+    /// work runs alongside rendering, otherwise a family that has both
+    pub compute: u32,
 }
 
 impl QueueFamilyIndices {
@@ -61,21 +159,68 @@ impl QueueFamilyIndices {
             .position(|p| p.queue_flags.contains(vk::QueueFlags::GRAPHICS))
             .map(|i| i as u32);
 
-        // get presentation check done
+        // prefer a family dedicated to transfers, then any transfer family
+        // without graphics, every graphics family supports transfers anyway
+        let transfer = properties
+            .iter()
+            .position(|p| {
+                p.queue_flags.contains(vk::QueueFlags::TRANSFER)
+                    && !p
+                        .queue_flags
+                        .intersects(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+            })
+            .or_else(|| {
+                properties.iter().position(|p| {
+                    p.queue_flags.contains(vk::QueueFlags::TRANSFER)
+                        && !p.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+                })
+            })
+            .map(|i| i as u32)
+            .or(graphics);
+
+        // prefer an async compute family, then the graphics family when it
+        // also supports compute, which every device with graphics must have
+        let compute = properties
+            .iter()
+            .position(|p| {
+                p.queue_flags.contains(vk::QueueFlags::COMPUTE)
+                    && !p.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+            })
+            .or_else(|| {
+                properties.iter().position(|p| {
+                    p.queue_flags
+                        .contains(vk::QueueFlags::COMPUTE | vk::QueueFlags::GRAPHICS)
+                })
+            })
+            .map(|i| i as u32);
+
+        // get presentation check done, without a surface there is nothing to
+        // present to so the graphics queue doubles as the present queue
         let mut present = None;
-        for (index, properties) in properties.iter().enumerate() {
-            if instance.get_physical_device_surface_support_khr(
-                physical_device,
-                index as u32,
-                data.surface,
-            )? {
-                present = Some(index as u32);
-                break;
+        if data.surface.is_null() {
+            present = graphics;
+        } else {
+            for (index, _) in properties.iter().enumerate() {
+                if instance.get_physical_device_surface_support_khr(
+                    physical_device,
+                    index as u32,
+                    data.surface,
+                )? {
+                    present = Some(index as u32);
+                    break;
+                }
             }
         }
 
-        if let (Some(graphics), Some(present)) = (graphics, present) {
-            Ok(Self { graphics, present })
+        if let (Some(graphics), Some(present), Some(transfer), Some(compute)) =
+            (graphics, present, transfer, compute)
+        {
+            Ok(Self {
+                graphics,
+                present,
+                transfer,
+                compute,
+            })
         } else {
             Err(anyhow!(SuitabilityError("required queue families.")))
         }
@@ -85,92 +230,160 @@ impl QueueFamilyIndices {
 unsafe fn check_physical_device(
     instance: &Instance,
     data: &AppData,
+    requirements: &DeviceRequirements,
     physical_device: vk::PhysicalDevice,
 ) -> Result<()> {
-    QueueFamilyIndices::get(instance, data, physical_device)?;
-    check_physical_device_extensions(instance, physical_device)?;
+    unsafe {
+        QueueFamilyIndices::get(instance, data, physical_device)?;
+        check_physical_device_extensions(instance, requirements, physical_device)?;
 
-    // swapchain support check
-    let support = SwapchainSupport::get(instance, data, physical_device)?;
-    if support.formats.is_empty() || support.present_modes.is_empty() {
-        return Err(anyhow!(SuitabilityError("Insufficient swapchain support.")));
-    }
+        // swapchain support check
+        if !data.surface.is_null() {
+            let support = SwapchainSupport::get(instance, data, physical_device)?;
+            if support.formats.is_empty() || support.present_modes.is_empty() {
+                return Err(anyhow!(SuitabilityError("Insufficient swapchain support.")));
+            }
+        }
 
-    // features support check
-    let features = instance.get_physical_device_features(physical_device);
-    if features.sampler_anisotropy != vk::TRUE {
-        return Err(anyhow!(SuitabilityError("No sampler anisotropy.")));
+        // features support check
+        let version = get_device_version(instance, physical_device);
+        let extensions = get_device_extensions(instance, physical_device)?;
+        let features = FeatureStructs::get(instance, physical_device, version, &extensions);
+        if let Some(feature) = requirements.missing_features(&features).first() {
+            return Err(anyhow!(SuitabilityError(feature.name())));
+        }
     }
 
     Ok(())
 }
 
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+pub unsafe fn get_device_extensions(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> Result<HashSet<vk::ExtensionName>> {
+    Ok(unsafe {
+        instance
+            .enumerate_device_extension_properties(physical_device, None)?
+            .iter()
+            .map(|e| e.extension_name)
+            .collect::<HashSet<_>>()
+    })
+}
+
 unsafe fn check_physical_device_extensions(
     instance: &Instance,
+    requirements: &DeviceRequirements,
     physical_device: vk::PhysicalDevice,
 ) -> Result<()> {
-    let extensions = instance
-        .enumerate_device_extension_properties(physical_device, None)?
-        .iter()
-        .map(|e| e.extension_name)
-        .collect::<HashSet<_>>();
+    let extensions = unsafe { get_device_extensions(instance, physical_device)? };
+    let missing = requirements.missing_extensions(&extensions);
 
-    if DEVICE_EXTENSIONS.iter().all(|e| extensions.contains(e)) {
+    if missing.is_empty() {
         Ok(())
     } else {
+        debug!("Missing device extensions: {:?}", missing);
         Err(anyhow!(SuitabilityError(
             "missing required device extensions"
         )))
     }
 }
 
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Creates the logical device with every required extension and feature and
+/// the optional ones the physical device supports, what ended up enabled is
+/// stored in the app data.
 pub unsafe fn create_logical_device(
     entry: &Entry,
     instance: &Instance,
     data: &mut AppData,
+    requirements: &DeviceRequirements,
 ) -> Result<Device> {
-    let indices = QueueFamilyIndices::get(instance, data, data.physical_device)?;
+    unsafe {
+        let indices = QueueFamilyIndices::get(instance, data, data.physical_device)?;
 
-    let mut unique_indices = HashSet::new();
-    unique_indices.insert(indices.graphics);
-    unique_indices.insert(indices.present);
+        let mut unique_indices = HashSet::new();
+        unique_indices.insert(indices.graphics);
+        unique_indices.insert(indices.present);
+        unique_indices.insert(indices.transfer);
+        unique_indices.insert(indices.compute);
 
-    let queue_priorities = &[1.0];
-    let layers = if VALIDATION_ENABLED {
-        vec![VALIDATION_LAYER.as_ptr()]
-    } else {
-        vec![]
-    };
+        let queue_priorities = &[1.0];
+        let layers = if instance.layers().contains(&VALIDATION_LAYER) {
+            vec![VALIDATION_LAYER.as_ptr()]
+        } else {
+            vec![]
+        };
 
-    let mut extensions = DEVICE_EXTENSIONS
-        .iter()
-        .map(|n| n.as_ptr())
-        .collect::<Vec<_>>();
+        // resolve which of the requested extensions and features to enable
 
-    if cfg!(target_os = "macos") && entry.version()? >= PORTABILITY_MACOS_VERSION {
-        extensions.push(vk::KHR_PORTABILITY_SUBSET_EXTENSION.name.as_ptr());
-    }
+        let requirements = device_requirements(data, requirements);
+        let version = get_device_version(instance, data.physical_device);
+        let supported_extensions = get_device_extensions(instance, data.physical_device)?;
+        let supported_features = FeatureStructs::get(
+            instance,
+            data.physical_device,
+            version,
+            &supported_extensions,
+        );
+        let mut enabled = requirements.resolve(&supported_extensions, &supported_features);
+        enabled.enable_promoted(version);
 
-    let features = vk::PhysicalDeviceFeatures::builder().sampler_anisotropy(true);
+        let mut extensions = enabled
+            .extensions
+            .iter()
+            .map(|n| n.as_ptr())
+            .collect::<Vec<_>>();
 
-    let queue_infos = unique_indices
-        .iter()
-        .map(|i| {
-            vk::DeviceQueueCreateInfo::builder()
-                .queue_family_index(*i)
-                .queue_priorities(queue_priorities)
-        })
-        .collect::<Vec<_>>();
+        if cfg!(target_os = "macos") && entry.version()? >= PORTABILITY_MACOS_VERSION {
+            extensions.push(vk::KHR_PORTABILITY_SUBSET_EXTENSION.name.as_ptr());
+        }
 
-    let info = vk::DeviceCreateInfo::builder()
-        .queue_create_infos(&queue_infos)
-        .enabled_layer_names(&layers)
-        .enabled_extension_names(&extensions)
-        .enabled_features(&features);
+        let queue_infos = unique_indices
+            .iter()
+            .map(|i| {
+                vk::DeviceQueueCreateInfo::builder()
+                    .queue_family_index(*i)
+                    .queue_priorities(queue_priorities)
+            })
+            .collect::<Vec<_>>();
 
-    let device = instance.create_device(data.physical_device, &info, None)?;
-    data.graphics_queue = device.get_device_queue(indices.graphics, 0);
-    data.present_queue = device.get_device_queue(indices.present, 0);
+        let mut info = vk::DeviceCreateInfo::builder()
+            .queue_create_infos(&queue_infos)
+            .enabled_layer_names(&layers)
+            .enabled_extension_names(&extensions);
+
+        // the newer feature structs can only be chained through features2,
+        // which then replaces the plain 1.0 features
+        let mut features = enabled.features;
+        let mut features2 = vk::PhysicalDeviceFeatures2::builder().features(features.v1_0);
+        if version >= FEATURES_V1_2_VERSION {
+            features2 = features2
+                .push_next(&mut features.v1_1)
+                .push_next(&mut features.v1_2);
+
+            if version >= FEATURES_V1_3_VERSION {
+                features2 = features2.push_next(&mut features.v1_3);
+            } else if enabled.has_extension(DYNAMIC_RENDERING_EXTENSION) {
+                features2 = features2.push_next(&mut features.khr_dynamic_rendering);
+            }
 
-    Ok(device)
+            info = info.push_next(&mut features2);
+        } else {
+            info = info.enabled_features(&enabled.features.v1_0);
+        }
+
+        let device = instance.create_device(data.physical_device, &info, None)?;
+        data.graphics_queue = device.get_device_queue(indices.graphics, 0);
+        data.present_queue = device.get_device_queue(indices.present, 0);
+        data.transfer_queue = device.get_device_queue(indices.transfer, 0);
+        data.compute_queue = device.get_device_queue(indices.compute, 0);
+
+        data.enabled_features = enabled;
+
+        Ok(device)
+    }
 }
@@ -0,0 +1,585 @@
+use std::fmt;
+
+use anyhow::{Result, anyhow};
+use vulkanalia::Version;
+use vulkanalia::loader::{LIBRARY, LibloadingLoader};
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::KhrSurfaceExtensionInstanceCommands;
+use vulkanalia::window as vk_window;
+use winit::window::Window;
+
+use crate::app::AppData;
+use crate::foundation::device::get_device_extensions;
+use crate::foundation::instance::create_instance;
+use crate::foundation::selection::device_type_name;
+use crate::foundation::swapchain::SwapchainSupport;
+use crate::foundation::validation::ValidationConfig;
+use crate::pipeline::image::get_depth_format;
+use crate::scenes::sampling::get_max_msaa_samples;
+
+/// everything the instance reports about the machine, used to triage bug
+/// reports from machines we don't have
+#[derive(Clone, Debug)]
+pub struct SystemReport {
+    pub instance_version: Version,
+    /// a device that could not be queried is reported with its error, so one
+    /// broken device does not hide the others
+    pub devices: Vec<std::result::Result<DeviceReport, DeviceError>>,
+}
+
+/// why a device is missing from the report
+#[derive(Clone, Debug)]
+pub struct DeviceError {
+    pub index: usize,
+    pub name: String,
+    pub error: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct DeviceReport {
+    pub index: usize,
+    pub name: String,
+    pub device_type: vk::PhysicalDeviceType,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub api_version: Version,
+    pub driver_version: u32,
+    pub limits: vk::PhysicalDeviceLimits,
+    pub queue_families: Vec<QueueFamilyReport>,
+    pub memory_heaps: Vec<vk::MemoryHeap>,
+    pub memory_types: Vec<vk::MemoryType>,
+    /// only reported when a window, and so a surface, was available
+    pub surface: Option<SurfaceReport>,
+    pub depth_format: Option<vk::Format>,
+    pub max_msaa_samples: vk::SampleCountFlags,
+    pub extensions: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct QueueFamilyReport {
+    pub flags: vk::QueueFlags,
+    pub queue_count: u32,
+    pub timestamp_valid_bits: u32,
+    /// whether the family can present to the surface, when there is one
+    pub present: Option<bool>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SurfaceReport {
+    pub formats: Vec<vk::SurfaceFormatKHR>,
+    pub present_modes: Vec<vk::PresentModeKHR>,
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Creates a throwaway instance, and a surface for the window when one is
+/// given, and reports every physical device it enumerates.
+pub unsafe fn collect_system_report(window: Option<&Window>) -> Result<SystemReport> {
+    let loader = unsafe { LibloadingLoader::new(LIBRARY)? };
+    let entry = unsafe { Entry::new(loader) }.map_err(|b| anyhow!("{}", b))?;
+    let mut data = AppData::default();
+
+    let validation = ValidationConfig {
+        enabled: false,
+        ..Default::default()
+    };
+
+    unsafe {
+        let instance = create_instance(window, &entry, &mut data, &validation)?;
+        if let Some(window) = window {
+            data.surface = vk_window::create_surface(&instance, &window, &window)?;
+        }
+
+        let devices = instance
+            .enumerate_physical_devices()
+            .map_err(|e| anyhow!(e))
+            .map(|devices| {
+                devices
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, d)| {
+                        report_physical_device(&instance, &mut data, i, d).map_err(|error| {
+                            DeviceError {
+                                index: i,
+                                name: instance
+                                    .get_physical_device_properties(d)
+                                    .device_name
+                                    .to_string(),
+                                error: format!("{:#}", error),
+                            }
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            });
+
+        let report = devices.map(|devices| SystemReport {
+            instance_version: instance.version(),
+            devices,
+        });
+
+        if !data.surface.is_null() {
+            instance.destroy_surface_khr(data.surface, None);
+        }
+        instance.destroy_instance(None);
+
+        report
+    }
+}
+
+unsafe fn report_physical_device(
+    instance: &Instance,
+    data: &mut AppData,
+    index: usize,
+    physical_device: vk::PhysicalDevice,
+) -> Result<DeviceReport> {
+    data.physical_device = physical_device;
+
+    unsafe {
+        let properties = instance.get_physical_device_properties(physical_device);
+        let memory = instance.get_physical_device_memory_properties(physical_device);
+
+        let queue_families = instance
+            .get_physical_device_queue_family_properties(physical_device)
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let present = if data.surface.is_null() {
+                    None
+                } else {
+                    Some(instance.get_physical_device_surface_support_khr(
+                        physical_device,
+                        i as u32,
+                        data.surface,
+                    )?)
+                };
+
+                Ok(QueueFamilyReport {
+                    flags: p.queue_flags,
+                    queue_count: p.queue_count,
+                    timestamp_valid_bits: p.timestamp_valid_bits,
+                    present,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let surface = if data.surface.is_null() {
+            None
+        } else {
+            let support = SwapchainSupport::get(instance, data, physical_device)?;
+            Some(SurfaceReport {
+                formats: support.formats,
+                present_modes: support.present_modes,
+            })
+        };
+
+        let mut extensions = get_device_extensions(instance, physical_device)?
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        extensions.sort();
+
+        Ok(DeviceReport {
+            index,
+            name: properties.device_name.to_string(),
+            device_type: properties.device_type,
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            api_version: Version::from(properties.api_version),
+            driver_version: properties.driver_version,
+            limits: properties.limits,
+            queue_families,
+            memory_heaps: memory.memory_heaps[..memory.memory_heap_count as usize].to_vec(),
+            memory_types: memory.memory_types[..memory.memory_type_count as usize].to_vec(),
+            surface,
+            depth_format: get_depth_format(instance, data).ok(),
+            max_msaa_samples: get_max_msaa_samples(instance, physical_device),
+            extensions,
+        })
+    }
+}
+
+impl fmt::Display for SystemReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Instance version: {}", self.instance_version)?;
+        writeln!(f, "Physical devices: {}", self.devices.len())?;
+        self.devices.iter().try_for_each(|d| match d {
+            Ok(device) => write!(f, "\n{}", device),
+            Err(error) => write!(f, "\n{}", error),
+        })
+    }
+}
+
+impl fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "#{} `{}`", self.index, self.name)?;
+        writeln!(f, "  Error: {}", self.error)
+    }
+}
+
+impl fmt::Display for DeviceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "#{} `{}`", self.index, self.name)?;
+        writeln!(f, "  Type: {}", device_type_name(self.device_type))?;
+        writeln!(f, "  Id: {:04x}:{:04x}", self.vendor_id, self.device_id)?;
+        writeln!(f, "  API version: {}", self.api_version)?;
+        writeln!(f, "  Driver version: {:#x}", self.driver_version)?;
+
+        writeln!(f, "  Limits:")?;
+        for (name, value) in reported_limits(&self.limits) {
+            writeln!(f, "    {}: {}", name, value)?;
+        }
+
+        writeln!(f, "  Queue families:")?;
+        for (i, family) in self.queue_families.iter().enumerate() {
+            write!(
+                f,
+                "    [{}] {:?} x{}, {} timestamp bits",
+                i, family.flags, family.queue_count, family.timestamp_valid_bits
+            )?;
+            match family.present {
+                Some(true) => writeln!(f, ", present")?,
+                _ => writeln!(f)?,
+            }
+        }
+
+        writeln!(f, "  Memory heaps:")?;
+        for (i, heap) in self.memory_heaps.iter().enumerate() {
+            writeln!(
+                f,
+                "    [{}] {} MiB {:?}",
+                i,
+                heap.size / (1024 * 1024),
+                heap.flags
+            )?;
+        }
+
+        writeln!(f, "  Memory types:")?;
+        for (i, type_) in self.memory_types.iter().enumerate() {
+            writeln!(
+                f,
+                "    [{}] heap {} {:?}",
+                i, type_.heap_index, type_.property_flags
+            )?;
+        }
+
+        match &self.surface {
+            Some(surface) => {
+                writeln!(f, "  Surface formats:")?;
+                for format in &surface.formats {
+                    writeln!(f, "    {:?} {:?}", format.format, format.color_space)?;
+                }
+                writeln!(f, "  Present modes: {:?}", surface.present_modes)?;
+            }
+            None => writeln!(f, "  Surface: unavailable")?,
+        }
+
+        match self.depth_format {
+            Some(format) => writeln!(f, "  Depth format: {:?}", format)?,
+            None => writeln!(f, "  Depth format: unsupported")?,
+        }
+        writeln!(f, "  Max MSAA samples: {}x", self.max_msaa_samples.bits())?;
+
+        writeln!(f, "  Extensions ({}):", self.extensions.len())?;
+        self.extensions
+            .iter()
+            .try_for_each(|e| writeln!(f, "    {}", e))
+    }
+}
+
+impl SystemReport {
+    /// the report as a json document
+    pub fn to_json(&self) -> String {
+        Json::Object(vec![
+            (
+                "instance_version",
+                Json::String(self.instance_version.to_string()),
+            ),
+            (
+                "devices",
+                Json::Array(
+                    self.devices
+                        .iter()
+                        .map(|d| match d {
+                            Ok(device) => device.to_json(),
+                            Err(error) => Json::Object(vec![
+                                ("index", Json::Number(error.index as u64)),
+                                ("name", Json::String(error.name.clone())),
+                                ("error", Json::String(error.error.clone())),
+                            ]),
+                        })
+                        .collect(),
+                ),
+            ),
+        ])
+        .to_string()
+    }
+}
+
+impl DeviceReport {
+    fn to_json(&self) -> Json {
+        let debug = |value: &dyn fmt::Debug| Json::String(format!("{:?}", value));
+
+        let queue_families = self
+            .queue_families
+            .iter()
+            .map(|q| {
+                Json::Object(vec![
+                    ("flags", debug(&q.flags)),
+                    ("queue_count", Json::Number(q.queue_count.into())),
+                    (
+                        "timestamp_valid_bits",
+                        Json::Number(q.timestamp_valid_bits.into()),
+                    ),
+                    ("present", q.present.map_or(Json::Null, Json::Bool)),
+                ])
+            })
+            .collect();
+
+        let memory_heaps = self
+            .memory_heaps
+            .iter()
+            .map(|h| {
+                Json::Object(vec![
+                    ("size", Json::Number(h.size)),
+                    ("flags", debug(&h.flags)),
+                ])
+            })
+            .collect();
+
+        let memory_types = self
+            .memory_types
+            .iter()
+            .map(|t| {
+                Json::Object(vec![
+                    ("heap_index", Json::Number(t.heap_index.into())),
+                    ("flags", debug(&t.property_flags)),
+                ])
+            })
+            .collect();
+
+        let surface = self.surface.as_ref().map_or(Json::Null, |s| {
+            let formats = s
+                .formats
+                .iter()
+                .map(|f| {
+                    Json::Object(vec![
+                        ("format", debug(&f.format)),
+                        ("color_space", debug(&f.color_space)),
+                    ])
+                })
+                .collect();
+
+            Json::Object(vec![
+                ("formats", Json::Array(formats)),
+                (
+                    "present_modes",
+                    Json::Array(s.present_modes.iter().map(|m| debug(m)).collect()),
+                ),
+            ])
+        });
+
+        Json::Object(vec![
+            ("index", Json::Number(self.index as u64)),
+            ("name", Json::String(self.name.clone())),
+            (
+                "device_type",
+                Json::String(device_type_name(self.device_type).into()),
+            ),
+            ("vendor_id", Json::Number(self.vendor_id.into())),
+            ("device_id", Json::Number(self.device_id.into())),
+            ("api_version", Json::String(self.api_version.to_string())),
+            ("driver_version", Json::Number(self.driver_version.into())),
+            ("limits", Json::Object(reported_limits(&self.limits))),
+            ("queue_families", Json::Array(queue_families)),
+            ("memory_heaps", Json::Array(memory_heaps)),
+            ("memory_types", Json::Array(memory_types)),
+            ("surface", surface),
+            (
+                "depth_format",
+                self.depth_format.map_or(Json::Null, |f| debug(&f)),
+            ),
+            (
+                "max_msaa_samples",
+                Json::Number(self.max_msaa_samples.bits().into()),
+            ),
+            (
+                "extensions",
+                Json::Array(self.extensions.iter().cloned().map(Json::String).collect()),
+            ),
+        ])
+    }
+}
+
+/// the device limits that matter when triaging a bug report, by their name in
+/// the vulkan spec
+fn reported_limits(limits: &vk::PhysicalDeviceLimits) -> Vec<(&'static str, Json)> {
+    let number = |n: u64| Json::Number(n);
+    let numbers = |n: &[u32]| Json::Array(n.iter().map(|n| Json::Number((*n).into())).collect());
+
+    vec![
+        (
+            "maxImageDimension2D",
+            number(limits.max_image_dimension_2d.into()),
+        ),
+        (
+            "maxImageArrayLayers",
+            number(limits.max_image_array_layers.into()),
+        ),
+        (
+            "maxUniformBufferRange",
+            number(limits.max_uniform_buffer_range.into()),
+        ),
+        (
+            "maxStorageBufferRange",
+            number(limits.max_storage_buffer_range.into()),
+        ),
+        (
+            "maxPushConstantsSize",
+            number(limits.max_push_constants_size.into()),
+        ),
+        (
+            "maxMemoryAllocationCount",
+            number(limits.max_memory_allocation_count.into()),
+        ),
+        (
+            "maxSamplerAllocationCount",
+            number(limits.max_sampler_allocation_count.into()),
+        ),
+        (
+            "bufferImageGranularity",
+            number(limits.buffer_image_granularity),
+        ),
+        (
+            "maxBoundDescriptorSets",
+            number(limits.max_bound_descriptor_sets.into()),
+        ),
+        (
+            "maxPerStageDescriptorSamplers",
+            number(limits.max_per_stage_descriptor_samplers.into()),
+        ),
+        (
+            "maxPerStageDescriptorUniformBuffers",
+            number(limits.max_per_stage_descriptor_uniform_buffers.into()),
+        ),
+        (
+            "maxPerStageDescriptorStorageBuffers",
+            number(limits.max_per_stage_descriptor_storage_buffers.into()),
+        ),
+        (
+            "maxPerStageDescriptorSampledImages",
+            number(limits.max_per_stage_descriptor_sampled_images.into()),
+        ),
+        (
+            "maxPerStageDescriptorStorageImages",
+            number(limits.max_per_stage_descriptor_storage_images.into()),
+        ),
+        (
+            "maxVertexInputAttributes",
+            number(limits.max_vertex_input_attributes.into()),
+        ),
+        (
+            "maxComputeWorkGroupCount",
+            numbers(&limits.max_compute_work_group_count),
+        ),
+        (
+            "maxComputeWorkGroupSize",
+            numbers(&limits.max_compute_work_group_size),
+        ),
+        (
+            "maxComputeWorkGroupInvocations",
+            number(limits.max_compute_work_group_invocations.into()),
+        ),
+        (
+            "maxSamplerAnisotropy",
+            Json::Float(limits.max_sampler_anisotropy.into()),
+        ),
+        ("maxViewports", number(limits.max_viewports.into())),
+        (
+            "maxFramebufferWidth",
+            number(limits.max_framebuffer_width.into()),
+        ),
+        (
+            "maxFramebufferHeight",
+            number(limits.max_framebuffer_height.into()),
+        ),
+        (
+            "maxColorAttachments",
+            number(limits.max_color_attachments.into()),
+        ),
+        (
+            "minUniformBufferOffsetAlignment",
+            number(limits.min_uniform_buffer_offset_alignment),
+        ),
+        (
+            "minStorageBufferOffsetAlignment",
+            number(limits.min_storage_buffer_offset_alignment),
+        ),
+        ("nonCoherentAtomSize", number(limits.non_coherent_atom_size)),
+        (
+            "timestampPeriod",
+            Json::Float(limits.timestamp_period.into()),
+        ),
+    ]
+}
+
+/// just enough of json to write the report out, without pulling in a
+/// serialization library for a single command
+enum Json {
+    Null,
+    Bool(bool),
+    Number(u64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let string = |f: &mut fmt::Formatter<'_>, s: &str| {
+            write!(f, "\"")?;
+            for c in s.chars() {
+                match c {
+                    '"' => write!(f, "\\\"")?,
+                    '\\' => write!(f, "\\\\")?,
+                    '\n' => write!(f, "\\n")?,
+                    c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                    c => write!(f, "{}", c)?,
+                }
+            }
+            write!(f, "\"")
+        };
+
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Bool(b) => write!(f, "{}", b),
+            Self::Number(n) => write!(f, "{}", n),
+            // json has no representation of infinity or nan
+            Self::Float(n) if !n.is_finite() => write!(f, "null"),
+            Self::Float(n) => write!(f, "{}", n),
+            Self::String(s) => string(f, s),
+            Self::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Self::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
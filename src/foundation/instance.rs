@@ -13,24 +13,37 @@ use std::os::raw::c_void;
 use vulkanalia::vk::{ExtDebugUtilsExtensionInstanceCommands, KhrSurfaceExtensionInstanceCommands};
 
 use crate::app::AppData;
+use crate::foundation::validation::{ValidationConfig, ValidationMessage, ValidationSink};
 
 // minimum version constant
 pub const PORTABILITY_MACOS_VERSION: Version = Version::new(1, 3, 216);
+// the newest vulkan version the app knows how to use
+pub const MAX_API_VERSION: Version = Version::new(1, 3, 0);
 // validation layer constants
-pub const VALIDATION_ENABLED: bool = cfg!(debug_assertions);
 pub const VALIDATION_LAYER: vk::ExtensionName =
     vk::ExtensionName::from_bytes(b"VK_LAYER_KHRONOS_validation");
 
-// debug callback function for instance debugging
+// debug callback function for instance debugging, messages are also pushed
+// into the validation sink when one is passed as the user data
 extern "system" fn debug_callback(
     severity: vk::DebugUtilsMessageSeverityFlagsEXT,
     type_: vk::DebugUtilsMessageTypeFlagsEXT,
     data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _: *mut c_void,
+    user_data: *mut c_void,
 ) -> vk::Bool32 {
     let data = unsafe { *data };
     let message = unsafe { CStr::from_ptr(data.message) }.to_string_lossy();
 
+    if !user_data.is_null() {
+        let message = ValidationMessage {
+            severity,
+            type_,
+            message: message.to_string(),
+        };
+
+        unsafe { ValidationSink::push_to_user_data(user_data, message) };
+    }
+
     if severity >= vk::DebugUtilsMessageSeverityFlagsEXT::ERROR {
         error!("({:?}) {}", type_, message);
     } else if severity >= vk::DebugUtilsMessageSeverityFlagsEXT::WARNING {
@@ -44,26 +57,39 @@ extern "system" fn debug_callback(
     vk::FALSE
 }
 
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// When no window is given the instance is created without any of the surface
+/// extensions, for rendering offscreen on machines without a display.
 pub unsafe fn create_instance(
-    window: &Window,
+    window: Option<&Window>,
     entry: &Entry,
     data: &mut AppData,
+    validation: &ValidationConfig,
 ) -> Result<Instance> {
-    // we need to create appinfo for the vulkan instance
+    // we need to create appinfo for the vulkan instance, asking for the newest
+    // version the app knows about that the loader supports
+    let loader_version = entry.version()?;
+    let api_version =
+        Version::new(loader_version.major, loader_version.minor, 0).min(MAX_API_VERSION);
+
     let application_info = vk::ApplicationInfo::builder()
         .application_name(b"CHOAM\0")
         .application_version(vk::make_version(1, 0, 0))
         .engine_name(b"NO_ENGINE\0")
         .engine_version(vk::make_version(1, 0, 0))
-        .api_version(vk::make_version(1, 0, 0));
+        .api_version(api_version.into());
 
     // the extensions we want to load into the instance
-    let mut extensions = vk_window::get_required_instance_extensions(window)
+    let mut extensions = window
+        .map(|w| vk_window::get_required_instance_extensions(w))
+        .unwrap_or(&[])
         .iter()
         .map(|e| e.as_ptr())
         .collect::<Vec<_>>();
 
-    if VALIDATION_ENABLED {
+    if validation.enabled {
         extensions.push(vk::EXT_DEBUG_UTILS_EXTENSION.name.as_ptr());
     }
 
@@ -90,16 +116,51 @@ pub unsafe fn create_instance(
         .map(|l| l.layer_name)
         .collect::<HashSet<_>>();
 
-    if VALIDATION_ENABLED && !available_layers.contains(&VALIDATION_LAYER) {
+    if validation.enabled && !available_layers.contains(&VALIDATION_LAYER) {
         return Err(anyhow!("Validation layer requested but not supported"));
     }
 
-    let layers = if VALIDATION_ENABLED {
+    let layers = if validation.enabled {
         vec![VALIDATION_LAYER.as_ptr()]
     } else {
         Vec::new()
     };
 
+    // gpu assisted and synchronization validation are provided by the
+    // validation layer through the validation features extension
+
+    let validation_features = if validation.enabled {
+        validation.enabled_validation_features()
+    } else {
+        Vec::new()
+    };
+
+    // the validation features extension is deprecated in favour of layer
+    // settings, but it is still what the validation layer reads these from
+    #[allow(deprecated)]
+    let validation_features_extension = vk::EXT_VALIDATION_FEATURES_EXTENSION.name;
+
+    let validation_features_supported = !validation_features.is_empty()
+        && unsafe {
+            entry.enumerate_instance_extension_properties(Some(VALIDATION_LAYER.as_bytes()))?
+        }
+        .iter()
+        .any(|e| e.extension_name == validation_features_extension);
+
+    if !validation_features.is_empty() && !validation_features_supported {
+        warn!("Validation features requested but not supported, ignoring them");
+    }
+
+    if validation_features_supported {
+        extensions.push(validation_features_extension.as_ptr());
+    }
+
+    let user_data = validation
+        .sink
+        .as_ref()
+        .map(|s| s.as_user_data())
+        .unwrap_or(std::ptr::null_mut());
+
     // creeation of the actual vulkan instance
     let mut info = vk::InstanceCreateInfo::builder()
         .application_info(&application_info)
@@ -107,32 +168,38 @@ pub unsafe fn create_instance(
         .enabled_extension_names(&extensions)
         .flags(flags);
 
+    let message_type = vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+        | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+        | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE;
+
     let mut debug_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
-        .message_severity(vk::DebugUtilsMessageSeverityFlagsEXT::all())
-        .message_type(
-            vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
-                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
-                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
-        )
+        .message_severity(validation.severities())
+        .message_type(message_type)
         .user_callback(Some(debug_callback));
+    debug_info.user_data = user_data;
+
+    let mut validation_features_info =
+        vk::ValidationFeaturesEXT::builder().enabled_validation_features(&validation_features);
 
-    if VALIDATION_ENABLED {
+    if validation.enabled {
         info = info.push_next(&mut debug_info);
     }
 
+    if validation_features_supported {
+        info = info.push_next(&mut validation_features_info);
+    }
+
     let instance = entry.create_instance(&info, None)?;
 
-    if VALIDATION_ENABLED {
-        let debug_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
-            .message_severity(vk::DebugUtilsMessageSeverityFlagsEXT::all())
-            .message_type(
-                vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
-                    | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
-                    | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
-            )
+    if validation.enabled {
+        let mut debug_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
+            .message_severity(validation.severities())
+            .message_type(message_type)
             .user_callback(Some(debug_callback));
+        debug_info.user_data = user_data;
 
         data.messenger = instance.create_debug_utils_messenger_ext(&debug_info, None)?;
+        data.validation_sink = validation.sink.clone();
     }
 
     Ok(instance)
@@ -1,3 +1,10 @@
+pub mod debug;
 pub mod device;
+pub mod info;
 pub mod instance;
+pub mod offscreen;
+pub mod registry;
+pub mod requirements;
+pub mod selection;
 pub mod swapchain;
+pub mod validation;
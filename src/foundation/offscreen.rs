@@ -0,0 +1,171 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::ptr::copy_nonoverlapping as memcpy;
+
+use anyhow::Result;
+use vulkanalia::{
+    Device, Instance,
+    vk::{self, DeviceV1_0, HasBuilder},
+};
+
+use crate::{
+    app::AppData,
+    pipeline::{
+        barriers::{ImageState, Subresources, transition_image},
+        buffers::{begin_onetime_command, end_onetime_command},
+        image::{create_image, create_image_view},
+        memory::free_allocation,
+        resources::{Image, ImageView},
+        vertex::create_buffer,
+    },
+};
+
+/// the format of the offscreen color target, picked so that the pixels read
+/// back from it are already laid out as rgba bytes
+pub const OFFSCREEN_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Creates a single device local color image that stands in for the swapchain
+/// images when rendering headless, it is stored in the swapchain fields of
+/// the app data so the render pass, framebuffers and color/depth objects can
+/// be created exactly like they are for a window.
+pub unsafe fn create_offscreen_target(
+    instance: &Instance,
+    device: &Device,
+    data: &mut AppData,
+    width: u32,
+    height: u32,
+) -> Result<()> {
+    unsafe {
+        let (image, image_memory) = create_image(
+            instance,
+            device,
+            data,
+            width,
+            height,
+            1,
+            vk::SampleCountFlags::_1,
+            OFFSCREEN_FORMAT,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let image_view = create_image_view(
+            device,
+            data,
+            image,
+            OFFSCREEN_FORMAT,
+            vk::ImageAspectFlags::COLOR,
+            1,
+        )?;
+
+        data.swapchain_format = OFFSCREEN_FORMAT;
+        data.swapchain_extent = vk::Extent2D { width, height };
+        data.swapchain_images = vec![image];
+        data.layouts.register(
+            image,
+            vk::ImageAspectFlags::COLOR,
+            1,
+            1,
+            ImageState::UNDEFINED,
+        );
+        data.swapchain_image_views = vec![ImageView::new(&data.deletions, image_view)];
+        data.offscreen_image = Image::new(&data.deletions, image, image_memory);
+    }
+
+    Ok(())
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Copies the last rendered offscreen frame back to the host, returning
+/// tightly packed rgba pixels row by row from the top left corner.
+pub unsafe fn read_offscreen_pixels(
+    instance: &Instance,
+    device: &Device,
+    data: &mut AppData,
+) -> Result<Vec<u8>> {
+    let width = data.swapchain_extent.width;
+    let height = data.swapchain_extent.height;
+    let size = width as u64 * height as u64 * 4;
+
+    unsafe {
+        let (staging_buffer, staging_buffer_memory) = create_buffer(
+            instance,
+            device,
+            data,
+            size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+        )?;
+
+        let command_buffer = begin_onetime_command(device, data)?;
+
+        // make the resolve writes visible to the copy, the frame already left
+        // the image in the transfer src layout
+        transition_image(
+            device,
+            data,
+            command_buffer,
+            data.swapchain_images[0],
+            Subresources::ALL,
+            ImageState::TRANSFER_SRC,
+        )?;
+
+        let subresource = vk::ImageSubresourceLayers::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .mip_level(0)
+            .base_array_layer(0)
+            .layer_count(1);
+
+        let region = vk::BufferImageCopy::builder()
+            .buffer_offset(0)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(subresource)
+            .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+            .image_extent(vk::Extent3D {
+                width,
+                height,
+                depth: 1,
+            });
+
+        device.cmd_copy_image_to_buffer(
+            command_buffer,
+            data.swapchain_images[0],
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            staging_buffer,
+            &[region],
+        );
+
+        end_onetime_command(device, data, command_buffer)?;
+
+        let mut pixels = vec![0u8; size as usize];
+        let memory = data.allocator.mapped(staging_buffer_memory)?;
+        memcpy(memory.cast(), pixels.as_mut_ptr(), pixels.len());
+
+        device.destroy_buffer(staging_buffer, None);
+        data.registry.destroyed(staging_buffer);
+        free_allocation(device, data, staging_buffer_memory);
+
+        Ok(pixels)
+    }
+}
+
+/// writes tightly packed rgba pixels out to a png file
+pub fn save_png(path: impl AsRef<Path>, width: u32, height: u32, pixels: &[u8]) -> Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+
+    Ok(())
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::panic::Location;
+use std::sync::{Arc, Mutex};
+
+use vulkanalia::vk;
+
+/// the environment variable turning resource tracking on with `1`, or off
+/// with `0`
+pub const TRACK_RESOURCES_ENV: &str = "CHOAM_TRACK_RESOURCES";
+
+/// a vulkan object that was created and not destroyed yet
+#[derive(Clone, Debug)]
+pub struct LiveResource {
+    pub object_type: vk::ObjectType,
+    pub handle: u64,
+    /// the debug name given to the object, if any
+    pub name: Option<String>,
+    /// the bytes of device memory the object uses, 0 when unknown
+    pub size: vk::DeviceSize,
+    /// where the helper creating the object was called from
+    pub location: &'static Location<'static>,
+    sequence: u64,
+}
+
+impl fmt::Display for LiveResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {:#x}", self.object_type, self.handle)?;
+        if let Some(name) = &self.name {
+            write!(f, " `{}`", name)?;
+        }
+        if self.size > 0 {
+            write!(f, " ({} bytes)", self.size)?;
+        }
+
+        write!(f, " created at {}", self.location)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Resources {
+    live: HashMap<(vk::ObjectType, u64), LiveResource>,
+    created: u64,
+}
+
+/// records every vulkan object created through the `pipeline` and
+/// `foundation` helpers, so objects still alive on shutdown can be reported
+///
+/// Does nothing unless enabled, which it is by default in debug builds.
+/// Clones share the same records.
+#[derive(Clone, Debug, Default)]
+pub struct ResourceRegistry {
+    enabled: bool,
+    resources: Arc<Mutex<Resources>>,
+}
+
+fn raw_handle<H>(handle: H) -> Option<u64>
+where
+    H: vk::Handle,
+    H::Repr: TryInto<u64>,
+{
+    if handle.is_null() {
+        return None;
+    }
+
+    handle.as_raw().try_into().ok()
+}
+
+impl ResourceRegistry {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// records a created object, along with the call site of the helper that
+    /// created it
+    #[track_caller]
+    pub fn created<H>(&self, handle: H, size: vk::DeviceSize)
+    where
+        H: vk::Handle,
+        H::Repr: TryInto<u64>,
+    {
+        let location = Location::caller();
+        if !self.enabled {
+            return;
+        }
+
+        let Some(raw) = raw_handle(handle) else {
+            return;
+        };
+
+        let mut resources = self.resources.lock().unwrap();
+        resources.created += 1;
+        let resource = LiveResource {
+            object_type: H::TYPE,
+            handle: raw,
+            name: None,
+            size,
+            location,
+            sequence: resources.created,
+        };
+
+        resources.live.insert((H::TYPE, raw), resource);
+    }
+
+    /// records the debug name given to an object
+    pub fn named<H>(&self, handle: H, name: &str)
+    where
+        H: vk::Handle,
+        H::Repr: TryInto<u64>,
+    {
+        let Some(raw) = raw_handle(handle).filter(|_| self.enabled) else {
+            return;
+        };
+
+        if let Some(resource) = self.resources.lock().unwrap().live.get_mut(&(H::TYPE, raw)) {
+            resource.name = Some(name.to_string());
+        }
+    }
+
+    /// records that an object was destroyed or freed
+    pub fn destroyed<H>(&self, handle: H)
+    where
+        H: vk::Handle,
+        H::Repr: TryInto<u64>,
+    {
+        let Some(raw) = raw_handle(handle).filter(|_| self.enabled) else {
+            return;
+        };
+
+        self.resources.lock().unwrap().live.remove(&(H::TYPE, raw));
+    }
+
+    /// every object that is still alive, in the order they were created
+    pub fn live(&self) -> Vec<LiveResource> {
+        let mut live = self
+            .resources
+            .lock()
+            .unwrap()
+            .live
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        live.sort_by_key(|r| r.sequence);
+        live
+    }
+
+    /// the bytes of device memory that are still allocated
+    pub fn memory_footprint(&self) -> vk::DeviceSize {
+        self.resources
+            .lock()
+            .unwrap()
+            .live
+            .values()
+            .filter(|r| r.object_type == vk::ObjectType::DEVICE_MEMORY)
+            .map(|r| r.size)
+            .sum()
+    }
+
+    /// logs every live object along with the number and size of the live
+    /// objects of each type
+    pub fn dump(&self) {
+        if !self.enabled {
+            log::info!(
+                "Resource tracking is disabled, set {}=1",
+                TRACK_RESOURCES_ENV
+            );
+            return;
+        }
+
+        let live = self.live();
+        let mut totals = HashMap::<vk::ObjectType, (usize, vk::DeviceSize)>::new();
+
+        for resource in &live {
+            log::info!("{}", resource);
+
+            let total = totals.entry(resource.object_type).or_default();
+            total.0 += 1;
+            total.1 += resource.size;
+        }
+
+        let mut totals = totals.into_iter().collect::<Vec<_>>();
+        totals.sort_by_key(|(_, (count, _))| std::cmp::Reverse(*count));
+        for (object_type, (count, size)) in totals {
+            log::info!("{:?}: {} live using {} bytes", object_type, count, size);
+        }
+
+        log::info!(
+            "{} live resources, {} bytes of device memory allocated",
+            live.len(),
+            self.memory_footprint()
+        );
+    }
+
+    /// logs every object that is still alive as a leak, returning how many
+    /// there are
+    pub fn report_leaks(&self) -> usize {
+        let live = self.live();
+        for resource in &live {
+            log::warn!("Leaked {}", resource);
+        }
+
+        if !live.is_empty() {
+            log::warn!(
+                "{} resources were not destroyed, {} bytes of device memory leaked",
+                live.len(),
+                self.memory_footprint()
+            );
+        }
+
+        live.len()
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashSet;
+
+use vulkanalia::vk::{self, HasBuilder, InstanceV1_0, InstanceV1_1};
+use vulkanalia::{Instance, Version};
+
+/// the vulkan version the 1.1 and 1.2 feature structs can be queried and
+/// enabled from, the 1.1 struct only exists from 1.2 onwards
+pub const FEATURES_V1_2_VERSION: Version = Version::new(1, 2, 0);
+/// the vulkan version the 1.3 feature struct can be queried and enabled from
+pub const FEATURES_V1_3_VERSION: Version = Version::new(1, 3, 0);
+/// the extension dynamic rendering was promoted to 1.3 from, which provides
+/// it on older devices
+pub const DYNAMIC_RENDERING_EXTENSION: vk::ExtensionName = vk::KHR_DYNAMIC_RENDERING_EXTENSION.name;
+
+/// the core feature structs for every vulkan version the app knows about
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FeatureStructs {
+    pub v1_0: vk::PhysicalDeviceFeatures,
+    pub v1_1: vk::PhysicalDeviceVulkan11Features,
+    pub v1_2: vk::PhysicalDeviceVulkan12Features,
+    pub v1_3: vk::PhysicalDeviceVulkan13Features,
+    /// the dynamic rendering feature of the extension, only used on devices
+    /// older than 1.3, the feature itself is read from `v1_3`
+    pub khr_dynamic_rendering: vk::PhysicalDeviceDynamicRenderingFeatures,
+}
+
+impl FeatureStructs {
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Queries the features the physical device supports, features from
+    /// versions newer than `version` are left disabled unless one of the
+    /// `extensions` provides them.
+    pub unsafe fn get(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        version: Version,
+        extensions: &HashSet<vk::ExtensionName>,
+    ) -> Self {
+        let mut features = Self::default();
+
+        unsafe {
+            if version < FEATURES_V1_2_VERSION {
+                features.v1_0 = instance.get_physical_device_features(physical_device);
+                return features;
+            }
+
+            let mut features2 = vk::PhysicalDeviceFeatures2::builder()
+                .push_next(&mut features.v1_1)
+                .push_next(&mut features.v1_2);
+
+            if version >= FEATURES_V1_3_VERSION {
+                features2 = features2.push_next(&mut features.v1_3);
+            } else if extensions.contains(&DYNAMIC_RENDERING_EXTENSION) {
+                features2 = features2.push_next(&mut features.khr_dynamic_rendering);
+            }
+
+            let mut features2 = features2.build();
+            instance.get_physical_device_features2(physical_device, &mut features2);
+            features.v1_0 = features2.features;
+        }
+
+        features.v1_3.dynamic_rendering |= features.khr_dynamic_rendering.dynamic_rendering;
+        features.unlink();
+        features
+    }
+
+    /// clears the pointer chain left behind by querying or enabling features,
+    /// so the structs can be stored and copied around safely
+    fn unlink(&mut self) {
+        self.v1_1.next = std::ptr::null_mut();
+        self.v1_2.next = std::ptr::null_mut();
+        self.v1_3.next = std::ptr::null_mut();
+        self.khr_dynamic_rendering.next = std::ptr::null_mut();
+    }
+}
+
+macro_rules! features {
+    ($($feature:ident => $version:ident.$field:ident, $name:literal;)*) => {
+        /// a device feature that can be required or optionally requested
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum Feature {
+            $($feature,)*
+        }
+
+        impl Feature {
+            /// a readable name for the feature
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$feature => $name,)*
+                }
+            }
+
+            /// whether the feature is set in the feature structs
+            pub fn get(self, features: &FeatureStructs) -> bool {
+                match self {
+                    $(Self::$feature => features.$version.$field == vk::TRUE,)*
+                }
+            }
+
+            /// sets the feature in the feature structs
+            pub fn set(self, features: &mut FeatureStructs) {
+                match self {
+                    $(Self::$feature => features.$version.$field = vk::TRUE,)*
+                }
+            }
+        }
+    };
+}
+
+features! {
+    SamplerAnisotropy => v1_0.sampler_anisotropy, "sampler anisotropy";
+    SampleRateShading => v1_0.sample_rate_shading, "sample rate shading";
+    FillModeNonSolid => v1_0.fill_mode_non_solid, "non solid fill modes";
+    WideLines => v1_0.wide_lines, "wide lines";
+    DepthClamp => v1_0.depth_clamp, "depth clamp";
+    DepthBiasClamp => v1_0.depth_bias_clamp, "depth bias clamp";
+    GeometryShader => v1_0.geometry_shader, "geometry shaders";
+    TessellationShader => v1_0.tessellation_shader, "tessellation shaders";
+    MultiDrawIndirect => v1_0.multi_draw_indirect, "multi draw indirect";
+    ShaderInt64 => v1_0.shader_int64, "64 bit shader integers";
+    PipelineStatisticsQuery => v1_0.pipeline_statistics_query, "pipeline statistics queries";
+    Multiview => v1_1.multiview, "multiview";
+    ShaderDrawParameters => v1_1.shader_draw_parameters, "shader draw parameters";
+    StorageBuffer16BitAccess => v1_1.storage_buffer_16bit_access, "16 bit storage buffer access";
+    DescriptorIndexing => v1_2.descriptor_indexing, "descriptor indexing";
+    ScalarBlockLayout => v1_2.scalar_block_layout, "scalar block layout";
+    ImagelessFramebuffer => v1_2.imageless_framebuffer, "imageless framebuffers";
+    SeparateDepthStencilLayouts => v1_2.separate_depth_stencil_layouts, "separate depth stencil layouts";
+    HostQueryReset => v1_2.host_query_reset, "host query reset";
+    TimelineSemaphore => v1_2.timeline_semaphore, "timeline semaphores";
+    BufferDeviceAddress => v1_2.buffer_device_address, "buffer device addresses";
+    DrawIndirectCount => v1_2.draw_indirect_count, "draw indirect count";
+    DynamicRendering => v1_3.dynamic_rendering, "dynamic rendering";
+    Synchronization2 => v1_3.synchronization2, "synchronization2";
+    Maintenance4 => v1_3.maintenance4, "maintenance4";
+}
+
+/// the extensions and features a physical device must have to be picked,
+/// and the ones that are enabled when a device happens to support them
+#[derive(Clone, Debug, Default)]
+pub struct DeviceRequirements {
+    pub required_extensions: Vec<vk::ExtensionName>,
+    pub optional_extensions: Vec<vk::ExtensionName>,
+    pub required_features: Vec<Feature>,
+    pub optional_features: Vec<Feature>,
+}
+
+impl DeviceRequirements {
+    /// requirements with no extensions or features
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn require_extension(mut self, extension: vk::ExtensionName) -> Self {
+        self.required_extensions.push(extension);
+        self
+    }
+
+    pub fn optional_extension(mut self, extension: vk::ExtensionName) -> Self {
+        self.optional_extensions.push(extension);
+        self
+    }
+
+    pub fn require_feature(mut self, feature: Feature) -> Self {
+        self.required_features.push(feature);
+        self
+    }
+
+    pub fn optional_feature(mut self, feature: Feature) -> Self {
+        self.optional_features.push(feature);
+        self
+    }
+
+    /// the required extensions the device does not support
+    pub fn missing_extensions(
+        &self,
+        supported: &HashSet<vk::ExtensionName>,
+    ) -> Vec<vk::ExtensionName> {
+        self.required_extensions
+            .iter()
+            .filter(|e| !supported.contains(e))
+            .cloned()
+            .collect()
+    }
+
+    /// the required features the device does not support
+    pub fn missing_features(&self, supported: &FeatureStructs) -> Vec<Feature> {
+        self.required_features
+            .iter()
+            .filter(|f| !f.get(supported))
+            .cloned()
+            .collect()
+    }
+
+    /// the extensions and features to enable on a device that supports the
+    /// given ones, which is every required one and the supported optional ones
+    pub fn resolve(
+        &self,
+        supported_extensions: &HashSet<vk::ExtensionName>,
+        supported_features: &FeatureStructs,
+    ) -> EnabledFeatures {
+        let mut enabled = EnabledFeatures::default();
+
+        self.required_extensions
+            .iter()
+            .chain(
+                self.optional_extensions
+                    .iter()
+                    .filter(|e| supported_extensions.contains(e)),
+            )
+            .for_each(|e| {
+                enabled.extensions.insert(*e);
+            });
+
+        self.required_features
+            .iter()
+            .chain(
+                self.optional_features
+                    .iter()
+                    .filter(|f| f.get(supported_features)),
+            )
+            .for_each(|f| f.set(&mut enabled.features));
+
+        enabled
+    }
+}
+
+/// the extensions and features that were enabled on the logical device, so
+/// the rest of the renderer can branch on what is available
+#[derive(Clone, Debug, Default)]
+pub struct EnabledFeatures {
+    pub extensions: HashSet<vk::ExtensionName>,
+    pub features: FeatureStructs,
+}
+
+impl EnabledFeatures {
+    /// whether the feature was enabled on the device
+    pub fn has(&self, feature: Feature) -> bool {
+        feature.get(&self.features)
+    }
+
+    /// whether the extension was enabled on the device
+    pub fn has_extension(&self, extension: vk::ExtensionName) -> bool {
+        self.extensions.contains(&extension)
+    }
+
+    /// enables the extensions that provide the enabled 1.3 features on a
+    /// device older than 1.3, along with their own feature structs
+    pub fn enable_promoted(&mut self, version: Version) {
+        if version < FEATURES_V1_3_VERSION && self.has(Feature::DynamicRendering) {
+            self.extensions.insert(DYNAMIC_RENDERING_EXTENSION);
+            self.features.khr_dynamic_rendering.dynamic_rendering = vk::TRUE;
+        }
+    }
+}
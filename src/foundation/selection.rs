@@ -0,0 +1,157 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use vulkanalia::Instance;
+use vulkanalia::vk;
+use vulkanalia::vk::InstanceV1_0;
+
+use crate::scenes::sampling::get_max_msaa_samples;
+
+/// picks a specific physical device instead of the highest scoring one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GpuSelector {
+    /// the device at this index in the order the instance enumerates them
+    Index(usize),
+    /// the device with this vendor and device id
+    Id { vendor_id: u32, device_id: u32 },
+    /// the highest scoring device whose name contains this (case insensitive)
+    Name(String),
+}
+
+impl GpuSelector {
+    /// whether the device at `index` with the given properties is selected
+    pub fn matches(&self, index: usize, properties: &vk::PhysicalDeviceProperties) -> bool {
+        match self {
+            Self::Index(i) => *i == index,
+            Self::Id {
+                vendor_id,
+                device_id,
+            } => properties.vendor_id == *vendor_id && properties.device_id == *device_id,
+            Self::Name(name) => properties
+                .device_name
+                .to_string()
+                .to_lowercase()
+                .contains(&name.to_lowercase()),
+        }
+    }
+}
+
+/// parses `index:<index>`, `<vendor id>:<device id>` (in hex, optionally
+/// prefixed with `0x`) or otherwise a device name substring, so names made of
+/// digits like `1080` are still names
+impl FromStr for GpuSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(anyhow!("Empty GPU selector"));
+        }
+
+        if let Some(index) = s.strip_prefix("index:") {
+            return index
+                .trim()
+                .parse()
+                .map(Self::Index)
+                .map_err(|_| anyhow!("Invalid GPU index `{}`", index));
+        }
+
+        let hex = |s: &str| {
+            let s = s.trim_start_matches("0x").trim_start_matches("0X");
+            u32::from_str_radix(s, 16)
+        };
+
+        if let Some((vendor_id, device_id)) = s.split_once(':')
+            && let (Ok(vendor_id), Ok(device_id)) = (hex(vendor_id), hex(device_id))
+        {
+            return Ok(Self::Id {
+                vendor_id,
+                device_id,
+            });
+        }
+
+        Ok(Self::Name(s.to_string()))
+    }
+}
+
+impl fmt::Display for GpuSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "index {}", index),
+            Self::Id {
+                vendor_id,
+                device_id,
+            } => write!(f, "id {:04x}:{:04x}", vendor_id, device_id),
+            Self::Name(name) => write!(f, "name containing `{}`", name),
+        }
+    }
+}
+
+/// how well a physical device suits the app, devices are compared by their
+/// type first, then their device local memory, then the msaa they support
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DeviceScore {
+    pub device_type: u32,
+    pub device_local_memory: vk::DeviceSize,
+    pub max_msaa_samples: u32,
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+pub unsafe fn score_physical_device(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> DeviceScore {
+    unsafe {
+        let properties = instance.get_physical_device_properties(physical_device);
+        let memory = instance.get_physical_device_memory_properties(physical_device);
+
+        score_device(
+            &properties,
+            &memory,
+            get_max_msaa_samples(instance, physical_device),
+        )
+    }
+}
+
+/// the score of a device with the given properties
+pub fn score_device(
+    properties: &vk::PhysicalDeviceProperties,
+    memory: &vk::PhysicalDeviceMemoryProperties,
+    max_msaa_samples: vk::SampleCountFlags,
+) -> DeviceScore {
+    let device_local_memory = memory.memory_heaps[..memory.memory_heap_count as usize]
+        .iter()
+        .filter(|h| h.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+        .map(|h| h.size)
+        .sum();
+
+    DeviceScore {
+        device_type: device_type_rank(properties.device_type),
+        device_local_memory,
+        max_msaa_samples: max_msaa_samples.bits(),
+    }
+}
+
+/// discrete > integrated > virtual > cpu > anything else
+fn device_type_rank(device_type: vk::PhysicalDeviceType) -> u32 {
+    match device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+        vk::PhysicalDeviceType::CPU => 1,
+        _ => 0,
+    }
+}
+
+/// a readable name for the type of device, used when logging the ranking
+pub fn device_type_name(device_type: vk::PhysicalDeviceType) -> &'static str {
+    match device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => "discrete GPU",
+        vk::PhysicalDeviceType::INTEGRATED_GPU => "integrated GPU",
+        vk::PhysicalDeviceType::VIRTUAL_GPU => "virtual GPU",
+        vk::PhysicalDeviceType::CPU => "CPU",
+        _ => "other device",
+    }
+}
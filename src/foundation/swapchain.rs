@@ -9,7 +9,9 @@ use vulkanalia::{
 use winit::window::Window;
 
 use crate::{
-    app::AppData, foundation::device::QueueFamilyIndices, pipeline::image::create_image_view,
+    app::AppData,
+    foundation::device::QueueFamilyIndices,
+    pipeline::{barriers::ImageState, image::create_image_view, resources::ImageView},
 };
 
 #[derive(Clone, Debug)]
@@ -118,7 +120,20 @@ pub unsafe fn create_swapchain(
         .old_swapchain(vk::SwapchainKHR::null());
 
     data.swapchain = device.create_swapchain_khr(&info, None)?;
+    data.registry.created(data.swapchain, 0);
     data.swapchain_images = device.get_swapchain_images_khr(data.swapchain)?;
+
+    // the first transition of an acquired image has to wait on the stage the
+    // acquire semaphore is waited on at, just like every later one
+    let acquired = ImageState::new(
+        vk::ImageLayout::UNDEFINED,
+        ImageState::PRESENT.stages,
+        ImageState::PRESENT.access,
+    );
+    data.swapchain_images.iter().for_each(|i| {
+        data.layouts
+            .register(*i, vk::ImageAspectFlags::COLOR, 1, 1, acquired)
+    });
     data.swapchain_format = surface_format.format;
     data.swapchain_extent = extent;
 
@@ -134,11 +149,13 @@ pub unsafe fn create_swapchain_image_views(device: &Device, data: &mut AppData)
         .map(|i| unsafe {
             create_image_view(
                 device,
+                data,
                 *i,
                 data.swapchain_format,
                 vk::ImageAspectFlags::COLOR,
                 1,
             )
+            .map(|v| ImageView::new(&data.deletions, v))
         })
         .collect::<Result<Vec<_>, _>>()?;
 
@@ -0,0 +1,174 @@
+use std::os::raw::c_void;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Result, anyhow};
+use vulkanalia::vk;
+
+/// the environment variables used to configure validation, `CHOAM_VALIDATION`
+/// takes `0` or `1`, the severity takes `verbose`, `info`, `warning` or `error`
+/// and the remaining ones take `1` to enable the extra validation
+pub const VALIDATION_ENV: &str = "CHOAM_VALIDATION";
+pub const VALIDATION_SEVERITY_ENV: &str = "CHOAM_VALIDATION_SEVERITY";
+pub const VALIDATION_GPU_ASSISTED_ENV: &str = "CHOAM_VALIDATION_GPU_ASSISTED";
+pub const VALIDATION_SYNC_ENV: &str = "CHOAM_VALIDATION_SYNC";
+
+/// how the validation layers are set up when the instance is created
+#[derive(Clone, Debug)]
+pub struct ValidationConfig {
+    /// whether the validation layer and debug messenger are enabled at all
+    pub enabled: bool,
+    /// the least severe messages that are reported
+    pub min_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    /// enables gpu assisted validation of shader resource accesses
+    pub gpu_assisted: bool,
+    /// enables validation of synchronization hazards between commands
+    pub synchronization: bool,
+    /// collects every reported message on top of logging it
+    pub sink: Option<ValidationSink>,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: cfg!(debug_assertions),
+            min_severity: vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+            gpu_assisted: false,
+            synchronization: false,
+            sink: None,
+        }
+    }
+}
+
+impl ValidationConfig {
+    /// applies every validation option that is set in the environment
+    pub fn apply_env(&mut self) -> Result<()> {
+        let flag = |name: &str| -> Result<Option<bool>> {
+            match std::env::var(name).ok().as_deref() {
+                None => Ok(None),
+                Some("1") | Some("true") => Ok(Some(true)),
+                Some("0") | Some("false") => Ok(Some(false)),
+                Some(value) => Err(anyhow!("Invalid {} `{}`, expected 0 or 1", name, value)),
+            }
+        };
+
+        if let Some(enabled) = flag(VALIDATION_ENV)? {
+            self.enabled = enabled;
+        }
+
+        if let Some(gpu_assisted) = flag(VALIDATION_GPU_ASSISTED_ENV)? {
+            self.gpu_assisted = gpu_assisted;
+        }
+
+        if let Some(synchronization) = flag(VALIDATION_SYNC_ENV)? {
+            self.synchronization = synchronization;
+        }
+
+        if let Ok(severity) = std::env::var(VALIDATION_SEVERITY_ENV) {
+            self.min_severity = parse_severity(&severity)?;
+        }
+
+        Ok(())
+    }
+
+    /// every severity at or above the minimum severity
+    pub fn severities(&self) -> vk::DebugUtilsMessageSeverityFlagsEXT {
+        [
+            vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+            vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
+            vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+            vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+        ]
+        .iter()
+        .filter(|s| **s >= self.min_severity)
+        .fold(vk::DebugUtilsMessageSeverityFlagsEXT::empty(), |a, s| {
+            a | *s
+        })
+    }
+
+    /// the extra validation features to enable through `VK_EXT_validation_features`
+    pub fn enabled_validation_features(&self) -> Vec<vk::ValidationFeatureEnableEXT> {
+        let mut features = vec![];
+
+        if self.gpu_assisted {
+            features.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED);
+        }
+
+        if self.synchronization {
+            features.push(vk::ValidationFeatureEnableEXT::SYNCHRONIZATION_VALIDATION);
+        }
+
+        features
+    }
+}
+
+fn parse_severity(severity: &str) -> Result<vk::DebugUtilsMessageSeverityFlagsEXT> {
+    match severity.to_lowercase().as_str() {
+        "verbose" => Ok(vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE),
+        "info" => Ok(vk::DebugUtilsMessageSeverityFlagsEXT::INFO),
+        "warning" => Ok(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING),
+        "error" => Ok(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR),
+        _ => Err(anyhow!(
+            "Invalid {} `{}`, expected verbose, info, warning or error",
+            VALIDATION_SEVERITY_ENV,
+            severity
+        )),
+    }
+}
+
+/// a message reported by the validation layers
+#[derive(Clone, Debug)]
+pub struct ValidationMessage {
+    pub severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    pub type_: vk::DebugUtilsMessageTypeFlagsEXT,
+    pub message: String,
+}
+
+/// a thread safe collection of validation messages, clones share the same
+/// messages so a test can keep one and hand another to the app
+#[derive(Clone, Debug, Default)]
+pub struct ValidationSink {
+    messages: Arc<Mutex<Vec<ValidationMessage>>>,
+}
+
+impl ValidationSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// every message collected so far
+    pub fn messages(&self) -> Vec<ValidationMessage> {
+        self.messages.lock().unwrap().clone()
+    }
+
+    /// every error message collected so far
+    pub fn errors(&self) -> Vec<ValidationMessage> {
+        self.messages
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|m| m.severity >= vk::DebugUtilsMessageSeverityFlagsEXT::ERROR)
+            .cloned()
+            .collect()
+    }
+
+    /// removes and returns every message collected so far
+    pub fn take(&self) -> Vec<ValidationMessage> {
+        std::mem::take(&mut *self.messages.lock().unwrap())
+    }
+
+    /// the pointer handed to the debug messenger as its user data, the sink
+    /// must be kept alive for as long as the messenger is
+    pub(crate) fn as_user_data(&self) -> *mut c_void {
+        Arc::as_ptr(&self.messages) as *mut c_void
+    }
+
+    /// # Safety
+    /// `user_data` must come from [`ValidationSink::as_user_data`] on a sink
+    /// that is still alive
+    pub(crate) unsafe fn push_to_user_data(user_data: *mut c_void, message: ValidationMessage) {
+        let messages = unsafe { &*(user_data as *const Mutex<Vec<ValidationMessage>>) };
+        if let Ok(mut messages) = messages.lock() {
+            messages.push(message);
+        }
+    }
+}
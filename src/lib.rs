@@ -0,0 +1,6 @@
+pub mod app;
+pub mod clock;
+pub mod config;
+pub mod foundation;
+pub mod pipeline;
+pub mod scenes;
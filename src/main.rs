@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use vulkanalia::vk::DeviceV1_0;
 use winit::{
     dpi::LogicalSize,
@@ -8,16 +8,53 @@ use winit::{
     window::WindowBuilder,
 };
 
-use crate::app::App;
+use std::time::Duration;
 
-pub mod app;
-pub mod foundation;
-pub mod pipeline;
-pub mod scenes;
+use choam::app::App;
+use choam::clock::Clock;
+use choam::config::AppConfig;
+use choam::foundation::info::collect_system_report;
 
 fn main() -> Result<()> {
     pretty_env_logger::init();
 
+    let args = std::env::args().collect::<Vec<_>>();
+
+    // device capability report, for triaging bug reports from other machines,
+    // which has to work even when the config it helps to fix is broken
+
+    if args.iter().any(|a| a == "--info") {
+        if let Err(error) = parse_config(&args) {
+            eprintln!("Invalid configuration, ignored for --info: {:#}", error);
+        }
+        return print_info(&args);
+    }
+
+    let config = parse_config(&args)?;
+
+    // headless rendering, renders a single frame to a png without a window
+
+    if let Some(index) = args.iter().position(|a| a == "--headless") {
+        let path = args
+            .get(index + 1)
+            .ok_or_else(|| anyhow!("--headless requires an output png path"))?;
+        let (width, height) = parse_size(&args)?;
+
+        unsafe {
+            let mut app = App::create_headless(width, height, &config)?;
+            if let Some(clock) = parse_clock(&args)? {
+                app.clock = clock;
+            }
+
+            let result = app.render_offscreen_png(path);
+            app.device.device_wait_idle()?;
+            app.destroy();
+            result?;
+        }
+
+        return Ok(());
+    }
+
     // window creation
 
     let event_loop = EventLoop::new()?;
@@ -29,7 +66,11 @@ fn main() -> Result<()> {
 
     // app creation
 
-    let mut app = unsafe { App::create(&window)? };
+    let mut app = unsafe { App::create(&window, &config)? };
+    if let Some(clock) = parse_clock(&args)? {
+        app.clock = clock;
+    }
+
     let mut minimized = false;
     event_loop.run(move |event, elwt| {
         match event {
@@ -65,6 +106,8 @@ fn main() -> Result<()> {
                             PhysicalKey::Code(KeyCode::ArrowRight) if app.models < 4 => {
                                 app.models += 1
                             }
+                            // dumps every live vulkan object to the log
+                            PhysicalKey::Code(KeyCode::F12) => app.resources().dump(),
                             _ => {}
                         }
                     }
@@ -78,3 +121,96 @@ fn main() -> Result<()> {
     println!("Hello, world!");
     Ok(())
 }
+
+/// prints every physical device and what it supports, as json with `--json`,
+/// surface support is only reported when a window can be created
+fn print_info(args: &[String]) -> Result<()> {
+    let event_loop = EventLoop::new().ok();
+    let window = event_loop.as_ref().and_then(|event_loop| {
+        WindowBuilder::new()
+            .with_title("CHOAM (VK)")
+            .with_visible(false)
+            .build(event_loop)
+            .ok()
+    });
+
+    let report = unsafe { collect_system_report(window.as_ref())? };
+
+    if args.iter().any(|a| a == "--json") {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report);
+    }
+
+    Ok(())
+}
+
+/// the app config from the environment, with `--gpu <selector>` taking
+/// precedence over the environment
+fn parse_config(args: &[String]) -> Result<AppConfig> {
+    let mut config = AppConfig::from_env()?;
+
+    if let Some(index) = args.iter().position(|a| a == "--gpu") {
+        let gpu = args
+            .get(index + 1)
+            .ok_or_else(|| anyhow!("--gpu requires a device index, id or name"))?;
+        config.gpu = Some(gpu.parse()?);
+    }
+
+    Ok(config)
+}
+
+/// parses the `--size <width>x<height>` argument, defaulting to the size of
+/// the window when it is not given
+fn parse_size(args: &[String]) -> Result<(u32, u32)> {
+    let Some(index) = args.iter().position(|a| a == "--size") else {
+        return Ok((1024, 768));
+    };
+
+    let size = args
+        .get(index + 1)
+        .ok_or_else(|| anyhow!("--size requires a <width>x<height> value"))?;
+
+    let (width, height) = match size.split_once('x') {
+        Some((width, height)) => (width.parse::<u32>()?, height.parse::<u32>()?),
+        None => {
+            return Err(anyhow!(
+                "Invalid size `{}`, expected <width>x<height>",
+                size
+            ));
+        }
+    };
+
+    if width == 0 || height == 0 {
+        return Err(anyhow!(
+            "Invalid size `{}`, the width and height must be at least 1",
+            size
+        ));
+    }
+
+    Ok((width, height))
+}
+
+/// parses the clock arguments, `--time <seconds>` freezes the animation at the
+/// given time and `--fixed-step <seconds>` advances it by a fixed step every
+/// frame, otherwise the app follows the wall clock
+fn parse_clock(args: &[String]) -> Result<Option<Clock>> {
+    let seconds = |flag: &str| -> Result<Option<Duration>> {
+        let Some(index) = args.iter().position(|a| a == flag) else {
+            return Ok(None);
+        };
+
+        let value = args
+            .get(index + 1)
+            .ok_or_else(|| anyhow!("{} requires a value in seconds", flag))?;
+        Ok(Some(Duration::try_from_secs_f32(value.parse()?)?))
+    };
+
+    if let Some(time) = seconds("--time")? {
+        Ok(Some(Clock::manual(time)))
+    } else if let Some(step) = seconds("--fixed-step")? {
+        Ok(Some(Clock::fixed_step(step)))
+    } else {
+        Ok(None)
+    }
+}
@@ -0,0 +1,497 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow};
+use vulkanalia::{
+    Device,
+    vk::{self, DeviceV1_0, DeviceV1_3, HasBuilder},
+};
+
+use crate::{
+    app::AppData,
+    foundation::requirements::Feature,
+    pipeline::buffers::{begin_onetime_command, end_onetime_command},
+};
+
+/// the accesses that write to an image, a subresource last accessed with any
+/// of these needs a barrier before it is used again even without a layout
+/// change
+const WRITE_ACCESS: vk::AccessFlags2 = vk::AccessFlags2::from_bits_truncate(
+    vk::AccessFlags2::SHADER_WRITE.bits()
+        | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE.bits()
+        | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE.bits()
+        | vk::AccessFlags2::TRANSFER_WRITE.bits()
+        | vk::AccessFlags2::HOST_WRITE.bits()
+        | vk::AccessFlags2::MEMORY_WRITE.bits(),
+);
+
+/// how an image subresource is used, the layout it is in and the stages and
+/// accesses of that use
+///
+/// Only stages and accesses that also exist in the original synchronization
+/// api are used, so the same state can be recorded without synchronization2.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ImageState {
+    pub layout: vk::ImageLayout,
+    pub stages: vk::PipelineStageFlags2,
+    pub access: vk::AccessFlags2,
+}
+
+impl ImageState {
+    pub const UNDEFINED: Self = Self::new(
+        vk::ImageLayout::UNDEFINED,
+        vk::PipelineStageFlags2::NONE,
+        vk::AccessFlags2::NONE,
+    );
+
+    pub const TRANSFER_DST: Self = Self::new(
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::PipelineStageFlags2::ALL_TRANSFER,
+        vk::AccessFlags2::TRANSFER_WRITE,
+    );
+
+    pub const TRANSFER_SRC: Self = Self::new(
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        vk::PipelineStageFlags2::ALL_TRANSFER,
+        vk::AccessFlags2::TRANSFER_READ,
+    );
+
+    pub const FRAGMENT_SHADER_READ: Self = Self::new(
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        vk::PipelineStageFlags2::FRAGMENT_SHADER,
+        vk::AccessFlags2::SHADER_READ,
+    );
+
+    pub const COLOR_ATTACHMENT: Self = Self::new(
+        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        vk::AccessFlags2::from_bits_truncate(
+            vk::AccessFlags2::COLOR_ATTACHMENT_READ.bits()
+                | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE.bits(),
+        ),
+    );
+
+    pub const DEPTH_ATTACHMENT: Self = Self::new(
+        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        vk::PipelineStageFlags2::from_bits_truncate(
+            vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS.bits()
+                | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS.bits(),
+        ),
+        vk::AccessFlags2::from_bits_truncate(
+            vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ.bits()
+                | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE.bits(),
+        ),
+    );
+
+    /// ready to be presented, the stage is the one the acquire semaphore is
+    /// waited on at so the next use of the image chains onto that wait
+    pub const PRESENT: Self = Self::new(
+        vk::ImageLayout::PRESENT_SRC_KHR,
+        vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        vk::AccessFlags2::NONE,
+    );
+
+    pub const COMPUTE_READ_WRITE: Self = Self::new(
+        vk::ImageLayout::GENERAL,
+        vk::PipelineStageFlags2::COMPUTE_SHADER,
+        vk::AccessFlags2::from_bits_truncate(
+            vk::AccessFlags2::SHADER_READ.bits() | vk::AccessFlags2::SHADER_WRITE.bits(),
+        ),
+    );
+
+    pub const fn new(
+        layout: vk::ImageLayout,
+        stages: vk::PipelineStageFlags2,
+        access: vk::AccessFlags2,
+    ) -> Self {
+        Self {
+            layout,
+            stages,
+            access,
+        }
+    }
+
+    /// whether the use writes to the image
+    pub fn writes(&self) -> bool {
+        self.access.intersects(WRITE_ACCESS)
+    }
+}
+
+/// the mip levels and array layers of an image a transition applies to,
+/// `vk::REMAINING_MIP_LEVELS` and `vk::REMAINING_ARRAY_LAYERS` cover the rest
+/// of the image
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Subresources {
+    pub base_mip_level: u32,
+    pub level_count: u32,
+    pub base_array_layer: u32,
+    pub layer_count: u32,
+}
+
+impl Subresources {
+    pub const ALL: Self = Self {
+        base_mip_level: 0,
+        level_count: vk::REMAINING_MIP_LEVELS,
+        base_array_layer: 0,
+        layer_count: vk::REMAINING_ARRAY_LAYERS,
+    };
+
+    /// a single mip level of every layer
+    pub const fn mip(level: u32) -> Self {
+        Self {
+            base_mip_level: level,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: vk::REMAINING_ARRAY_LAYERS,
+        }
+    }
+}
+
+/// a layout transition or memory dependency for a range of subresources that
+/// were all in the same state
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ImageBarrier {
+    pub image: vk::Image,
+    pub range: vk::ImageSubresourceRange,
+    pub old: ImageState,
+    pub new: ImageState,
+}
+
+impl ImageBarrier {
+    fn barrier2(&self) -> vk::ImageMemoryBarrier2 {
+        vk::ImageMemoryBarrier2::builder()
+            .src_stage_mask(self.old.stages)
+            .src_access_mask(self.old.access)
+            .dst_stage_mask(self.new.stages)
+            .dst_access_mask(self.new.access)
+            .old_layout(self.old.layout)
+            .new_layout(self.new.layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.image)
+            .subresource_range(self.range)
+            .build()
+    }
+
+    fn barrier(&self) -> vk::ImageMemoryBarrier {
+        vk::ImageMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::from_bits_truncate(
+                self.old.access.bits() as u32
+            ))
+            .dst_access_mask(vk::AccessFlags::from_bits_truncate(
+                self.new.access.bits() as u32
+            ))
+            .old_layout(self.old.layout)
+            .new_layout(self.new.layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.image)
+            .subresource_range(self.range)
+            .build()
+    }
+}
+
+#[derive(Clone, Debug)]
+struct TrackedImage {
+    aspect_mask: vk::ImageAspectFlags,
+    mip_levels: u32,
+    array_layers: u32,
+    /// the state of every subresource, indexed by `layer * mip_levels + mip`
+    states: Vec<ImageState>,
+}
+
+impl TrackedImage {
+    fn index(&self, mip: u32, layer: u32) -> usize {
+        (layer * self.mip_levels + mip) as usize
+    }
+
+    /// the mip levels and array layers the range covers
+    fn resolve(&self, range: Subresources) -> (u32, u32, u32, u32) {
+        let mip_end = if range.level_count == vk::REMAINING_MIP_LEVELS {
+            self.mip_levels
+        } else {
+            range.base_mip_level + range.level_count
+        };
+
+        let layer_end = if range.layer_count == vk::REMAINING_ARRAY_LAYERS {
+            self.array_layers
+        } else {
+            range.base_array_layer + range.layer_count
+        };
+
+        (
+            range.base_mip_level,
+            mip_end.min(self.mip_levels),
+            range.base_array_layer,
+            layer_end.min(self.array_layers),
+        )
+    }
+}
+
+/// remembers the layout and last access of every mip level and array layer
+/// of the images registered with it, so transitions only need the state an
+/// image is going to be used in
+#[derive(Clone, Debug, Default)]
+pub struct LayoutTracker {
+    images: HashMap<vk::Image, TrackedImage>,
+}
+
+impl LayoutTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// starts tracking the image with every subresource in the given state,
+    /// replacing whatever was tracked for it before
+    pub fn register(
+        &mut self,
+        image: vk::Image,
+        aspect_mask: vk::ImageAspectFlags,
+        mip_levels: u32,
+        array_layers: u32,
+        state: ImageState,
+    ) {
+        self.images.insert(
+            image,
+            TrackedImage {
+                aspect_mask,
+                mip_levels,
+                array_layers,
+                states: vec![state; (mip_levels * array_layers) as usize],
+            },
+        );
+    }
+
+    /// stops tracking the image, to be called when it is destroyed
+    pub fn forget(&mut self, image: vk::Image) {
+        self.images.remove(&image);
+    }
+
+    pub fn is_tracked(&self, image: vk::Image) -> bool {
+        self.images.contains_key(&image)
+    }
+
+    /// the state of a single subresource
+    pub fn state(&self, image: vk::Image, mip_level: u32, array_layer: u32) -> Option<ImageState> {
+        let tracked = self.images.get(&image)?;
+        if mip_level >= tracked.mip_levels || array_layer >= tracked.array_layers {
+            return None;
+        }
+
+        Some(tracked.states[tracked.index(mip_level, array_layer)])
+    }
+
+    /// records that something other than the tracker, such as a render pass,
+    /// left the subresources in the state
+    pub fn assume(&mut self, image: vk::Image, range: Subresources, state: ImageState) {
+        if let Some(tracked) = self.images.get_mut(&image) {
+            let (mip_start, mip_end, layer_start, layer_end) = tracked.resolve(range);
+            for layer in layer_start..layer_end {
+                for mip in mip_start..mip_end {
+                    let index = tracked.index(mip, layer);
+                    tracked.states[index] = state;
+                }
+            }
+        }
+    }
+
+    /// marks the contents of the whole image as no longer needed, the next
+    /// transition then starts from an undefined layout while still waiting on
+    /// the last use of the image
+    pub fn discard(&mut self, image: vk::Image) {
+        if let Some(tracked) = self.images.get_mut(&image) {
+            tracked
+                .states
+                .iter_mut()
+                .for_each(|s| s.layout = vk::ImageLayout::UNDEFINED);
+        }
+    }
+
+    /// moves the subresources into the new state, returning the fewest
+    /// barriers needed to get there
+    ///
+    /// Subresources already in the layout are skipped when neither their last
+    /// use nor the new one writes, their uses are merged instead so a later
+    /// write waits on every read. Neighbouring mip levels and array layers in
+    /// the same state share a barrier.
+    pub fn transition(
+        &mut self,
+        image: vk::Image,
+        range: Subresources,
+        state: ImageState,
+    ) -> Result<Vec<ImageBarrier>> {
+        let tracked = self
+            .images
+            .get_mut(&image)
+            .ok_or_else(|| anyhow!("Image {:?} is not tracked", image))?;
+
+        let (mip_start, mip_end, layer_start, layer_end) = tracked.resolve(range);
+        let mut barriers: Vec<ImageBarrier> = vec![];
+
+        for layer in layer_start..layer_end {
+            let mut mip = mip_start;
+            while mip < mip_end {
+                let old = tracked.states[tracked.index(mip, layer)];
+                let mut run_end = mip + 1;
+                while run_end < mip_end && tracked.states[tracked.index(run_end, layer)] == old {
+                    run_end += 1;
+                }
+
+                let needs_barrier = old.layout != state.layout || old.writes() || state.writes();
+                let new = if needs_barrier {
+                    state
+                } else {
+                    ImageState::new(
+                        state.layout,
+                        old.stages | state.stages,
+                        old.access | state.access,
+                    )
+                };
+
+                if needs_barrier {
+                    let merged = barriers.iter_mut().find(|b| {
+                        b.old == old
+                            && b.range.base_mip_level == mip
+                            && b.range.level_count == run_end - mip
+                            && b.range.base_array_layer + b.range.layer_count == layer
+                    });
+
+                    match merged {
+                        Some(barrier) => barrier.range.layer_count += 1,
+                        None => barriers.push(ImageBarrier {
+                            image,
+                            range: vk::ImageSubresourceRange::builder()
+                                .aspect_mask(tracked.aspect_mask)
+                                .base_mip_level(mip)
+                                .level_count(run_end - mip)
+                                .base_array_layer(layer)
+                                .layer_count(1)
+                                .build(),
+                            old,
+                            new,
+                        }),
+                    }
+                }
+
+                for m in mip..run_end {
+                    let index = tracked.index(m, layer);
+                    tracked.states[index] = new;
+                }
+
+                mip = run_end;
+            }
+        }
+
+        Ok(barriers)
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Records the barriers, in a single `vkCmdPipelineBarrier2` when
+/// synchronization2 is available and otherwise in a single
+/// `vkCmdPipelineBarrier` over the union of their stages.
+pub unsafe fn record_image_barriers(
+    device: &Device,
+    synchronization2: bool,
+    command_buffer: vk::CommandBuffer,
+    barriers: &[ImageBarrier],
+) {
+    if barriers.is_empty() {
+        return;
+    }
+
+    if synchronization2 {
+        let image_barriers = barriers.iter().map(|b| b.barrier2()).collect::<Vec<_>>();
+        let info = vk::DependencyInfo::builder().image_memory_barriers(&image_barriers);
+
+        unsafe { device.cmd_pipeline_barrier2(command_buffer, &info) };
+        return;
+    }
+
+    let src_stage_mask = barriers
+        .iter()
+        .fold(vk::PipelineStageFlags::empty(), |s, b| {
+            s | vk::PipelineStageFlags::from_bits_truncate(b.old.stages.bits() as u32)
+        });
+    let dst_stage_mask = barriers
+        .iter()
+        .fold(vk::PipelineStageFlags::empty(), |s, b| {
+            s | vk::PipelineStageFlags::from_bits_truncate(b.new.stages.bits() as u32)
+        });
+
+    // the original api has no empty stage mask, so nothing to wait on becomes
+    // the top of the pipe and nothing waiting becomes the bottom
+    let src_stage_mask = if src_stage_mask.is_empty() {
+        vk::PipelineStageFlags::TOP_OF_PIPE
+    } else {
+        src_stage_mask
+    };
+    let dst_stage_mask = if dst_stage_mask.is_empty() {
+        vk::PipelineStageFlags::BOTTOM_OF_PIPE
+    } else {
+        dst_stage_mask
+    };
+
+    let image_barriers = barriers.iter().map(|b| b.barrier()).collect::<Vec<_>>();
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            src_stage_mask,
+            dst_stage_mask,
+            vk::DependencyFlags::empty(),
+            &[] as &[vk::MemoryBarrier],
+            &[] as &[vk::BufferMemoryBarrier],
+            &image_barriers,
+        );
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Moves the tracked subresources into the state, recording whatever barriers
+/// that takes into the command buffer.
+pub unsafe fn transition_image(
+    device: &Device,
+    data: &mut AppData,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    range: Subresources,
+    state: ImageState,
+) -> Result<()> {
+    let barriers = data.layouts.transition(image, range, state)?;
+    let synchronization2 = data.enabled_features.has(Feature::Synchronization2);
+
+    unsafe { record_image_barriers(device, synchronization2, command_buffer, &barriers) };
+
+    Ok(())
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Moves the whole tracked image into the state in a one time command buffer
+/// and waits for it to finish.
+pub unsafe fn transition_image_layout(
+    device: &Device,
+    data: &mut AppData,
+    image: vk::Image,
+    state: ImageState,
+) -> Result<()> {
+    unsafe {
+        let command_buffer = begin_onetime_command(device, data)?;
+        transition_image(
+            device,
+            data,
+            command_buffer,
+            image,
+            Subresources::ALL,
+            state,
+        )?;
+        end_onetime_command(device, data, command_buffer)?;
+    }
+
+    Ok(())
+}
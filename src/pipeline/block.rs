@@ -0,0 +1,274 @@
+use anyhow::{Result, anyhow};
+use cgmath::{Matrix2, Matrix3, Matrix4, Vector2, Vector3, Vector4};
+use vulkanalia::{
+    Device,
+    vk::{self, DeviceV1_0},
+};
+
+use crate::pipeline::{memory::align_up, reflect::BlockMember};
+
+/// the rules a buffer block is laid out by, uniform buffers use std140 while
+/// push constants and storage buffers use std430
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlockLayout {
+    Std140,
+    Std430,
+}
+
+impl BlockLayout {
+    /// the alignment of arrays and structs, which std140 rounds up to that
+    /// of a vec4
+    pub fn aggregate_align(self, align: usize) -> usize {
+        match self {
+            Self::Std140 => align.max(16),
+            Self::Std430 => align,
+        }
+    }
+}
+
+/// a value that can be written into a buffer block
+pub trait BlockValue: Copy {
+    fn align(layout: BlockLayout) -> usize;
+    fn size(layout: BlockLayout) -> usize;
+    /// writes the value to the start of the bytes, which are at least `size`
+    /// long
+    fn write(&self, layout: BlockLayout, bytes: &mut [u8]);
+}
+
+/// a struct that is the whole of a buffer block, declared with
+/// [`crate::gpu_block`]
+pub trait Block: BlockValue {
+    /// the name and offset of every member
+    fn members(layout: BlockLayout) -> Vec<(&'static str, usize)>;
+
+    fn to_bytes(&self, layout: BlockLayout) -> Vec<u8> {
+        let mut bytes = vec![0; Self::size(layout)];
+        self.write(layout, &mut bytes);
+        bytes
+    }
+}
+
+/// a block of push constants, see [`push_constants`]
+pub trait PushConstants: Block {
+    /// the stages reading the block
+    const STAGES: vk::ShaderStageFlags;
+    /// where the block starts within the push constants of the layout
+    const OFFSET: u32 = 0;
+}
+
+macro_rules! scalar {
+    ($($ty:ty),*) => {$(
+        impl BlockValue for $ty {
+            fn align(_: BlockLayout) -> usize {
+                4
+            }
+
+            fn size(_: BlockLayout) -> usize {
+                4
+            }
+
+            fn write(&self, _: BlockLayout, bytes: &mut [u8]) {
+                bytes[..4].copy_from_slice(&self.to_ne_bytes());
+            }
+        }
+    )*};
+}
+
+scalar!(f32, i32, u32);
+
+macro_rules! vector {
+    ($($vector:ident, $count:literal, $align:literal, [$($field:ident),*];)*) => {$(
+        impl<T: BlockValue> BlockValue for $vector<T> {
+            fn align(layout: BlockLayout) -> usize {
+                T::align(layout) * $align
+            }
+
+            fn size(layout: BlockLayout) -> usize {
+                T::size(layout) * $count
+            }
+
+            fn write(&self, layout: BlockLayout, bytes: &mut [u8]) {
+                let size = T::size(layout);
+                for (i, value) in [$(self.$field),*].iter().enumerate() {
+                    value.write(layout, &mut bytes[i * size..]);
+                }
+            }
+        }
+    )*};
+}
+
+vector! {
+    Vector2, 2, 2, [x, y];
+    Vector3, 3, 4, [x, y, z];
+    Vector4, 4, 4, [x, y, z, w];
+}
+
+// arrays are laid out with every element aligned to the array alignment,
+// and matrices like arrays of their column vectors
+impl<T: BlockValue, const N: usize> BlockValue for [T; N] {
+    fn align(layout: BlockLayout) -> usize {
+        layout.aggregate_align(T::align(layout))
+    }
+
+    fn size(layout: BlockLayout) -> usize {
+        align_up(T::size(layout) as u64, Self::align(layout) as u64) as usize * N
+    }
+
+    fn write(&self, layout: BlockLayout, bytes: &mut [u8]) {
+        let stride = align_up(T::size(layout) as u64, Self::align(layout) as u64) as usize;
+        for (i, value) in self.iter().enumerate() {
+            value.write(layout, &mut bytes[i * stride..]);
+        }
+    }
+}
+
+macro_rules! matrix {
+    ($($matrix:ident, $column:ident, $count:literal, [$($field:ident),*];)*) => {$(
+        impl BlockValue for $matrix<f32> {
+            fn align(layout: BlockLayout) -> usize {
+                <[$column<f32>; $count]>::align(layout)
+            }
+
+            fn size(layout: BlockLayout) -> usize {
+                <[$column<f32>; $count]>::size(layout)
+            }
+
+            fn write(&self, layout: BlockLayout, bytes: &mut [u8]) {
+                [$(self.$field),*].write(layout, bytes);
+            }
+        }
+    )*};
+}
+
+matrix! {
+    Matrix2, Vector2, 2, [x, y];
+    Matrix3, Vector3, 3, [x, y, z];
+    Matrix4, Vector4, 4, [x, y, z, w];
+}
+
+/// declares a struct that is laid out as a std140 or std430 buffer block,
+/// implementing [`Block`] for it
+///
+/// ```ignore
+/// gpu_block! {
+///     pub struct Light {
+///         pub position: Vector3<f32>,
+///         pub intensity: f32,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! gpu_block {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_attr:meta])* $field_vis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, Debug)]
+        $vis struct $name {
+            $($(#[$field_attr])* $field_vis $field: $ty),*
+        }
+
+        impl $crate::pipeline::block::BlockValue for $name {
+            fn align(layout: $crate::pipeline::block::BlockLayout) -> usize {
+                layout.aggregate_align(
+                    1 $(.max(<$ty as $crate::pipeline::block::BlockValue>::align(layout)))*
+                )
+            }
+
+            fn size(layout: $crate::pipeline::block::BlockLayout) -> usize {
+                let mut offset = 0;
+                $(
+                    offset = $crate::pipeline::memory::align_up(
+                        offset as u64,
+                        <$ty as $crate::pipeline::block::BlockValue>::align(layout) as u64,
+                    ) as usize + <$ty as $crate::pipeline::block::BlockValue>::size(layout);
+                )*
+                $crate::pipeline::memory::align_up(offset as u64, Self::align(layout) as u64)
+                    as usize
+            }
+
+            fn write(&self, layout: $crate::pipeline::block::BlockLayout, bytes: &mut [u8]) {
+                let mut offset = 0;
+                $(
+                    offset = $crate::pipeline::memory::align_up(
+                        offset as u64,
+                        <$ty as $crate::pipeline::block::BlockValue>::align(layout) as u64,
+                    ) as usize;
+                    $crate::pipeline::block::BlockValue::write(
+                        &self.$field,
+                        layout,
+                        &mut bytes[offset..],
+                    );
+                    offset += <$ty as $crate::pipeline::block::BlockValue>::size(layout);
+                )*
+                let _ = offset;
+            }
+        }
+
+        impl $crate::pipeline::block::Block for $name {
+            fn members(layout: $crate::pipeline::block::BlockLayout) -> Vec<(&'static str, usize)> {
+                let mut offset = 0;
+                let mut members = vec![];
+                $(
+                    offset = $crate::pipeline::memory::align_up(
+                        offset as u64,
+                        <$ty as $crate::pipeline::block::BlockValue>::align(layout) as u64,
+                    ) as usize;
+                    members.push((stringify!($field), offset));
+                    offset += <$ty as $crate::pipeline::block::BlockValue>::size(layout);
+                )*
+                let _ = offset;
+                members
+            }
+        }
+    };
+}
+
+/// fails when the members of a block reflected from a shader are not at the
+/// offsets the struct puts them at, `base` is where the struct starts within
+/// the reflected block
+pub fn check_block<T: Block>(
+    layout: BlockLayout,
+    members: &[BlockMember],
+    base: u32,
+) -> Result<()> {
+    let offsets = T::members(layout);
+    let name = std::any::type_name::<T>();
+
+    for member in members {
+        let offset = offsets
+            .iter()
+            .find(|(n, _)| *n == member.name)
+            .map(|(_, offset)| *offset as u32 + base)
+            .ok_or_else(|| anyhow!("The shader reads `{}`, which `{}` lacks", member.name, name))?;
+
+        if offset != member.offset {
+            return Err(anyhow!(
+                "The shader reads `{}` at offset {}, but `{}` puts it at {}",
+                member.name,
+                member.offset,
+                name,
+                offset
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Pushes the block with the std430 layout at its offset for its stages.
+pub unsafe fn push_constants<T: PushConstants>(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    layout: vk::PipelineLayout,
+    value: &T,
+) {
+    let bytes = value.to_bytes(BlockLayout::Std430);
+    unsafe { device.cmd_push_constants(command_buffer, layout, T::STAGES, T::OFFSET, &bytes) };
+}
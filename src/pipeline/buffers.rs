@@ -14,7 +14,11 @@ pub unsafe fn create_framebuffers(device: &Device, data: &mut AppData) -> Result
         .swapchain_image_views
         .iter()
         .map(|i| {
-            let attachments = &[data.color_image_view, data.depth_image_view, *i];
+            let attachments = &[
+                data.color_image_view.handle(),
+                data.depth_image_view.handle(),
+                i.handle(),
+            ];
             let create_info = vk::FramebufferCreateInfo::builder()
                 .render_pass(data.render_pass)
                 .attachments(attachments)
@@ -25,12 +29,18 @@ pub unsafe fn create_framebuffers(device: &Device, data: &mut AppData) -> Result
             device.create_framebuffer(&create_info, None)
         })
         .collect::<Result<Vec<_>, _>>()?;
+    data.framebuffers
+        .iter()
+        .for_each(|f| data.registry.created(*f, 0));
 
     Ok(())
 }
 
 /// # Safety
 /// This is a vulkan using function and thus is unsafe
+///
+/// Creates the command pool for rendering on the graphics queue, the one for
+/// uploads on the transfer queue and the one for the compute queue.
 pub unsafe fn create_command_pool(
     instance: &Instance,
     device: &Device,
@@ -42,6 +52,21 @@ pub unsafe fn create_command_pool(
         .queue_family_index(indices.graphics);
 
     data.command_pool = device.create_command_pool(&info, None)?;
+    data.registry.created(data.command_pool, 0);
+
+    let info = vk::CommandPoolCreateInfo::builder()
+        .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+        .queue_family_index(indices.transfer);
+
+    data.transfer_command_pool = unsafe { device.create_command_pool(&info, None)? };
+    data.registry.created(data.transfer_command_pool, 0);
+
+    let info = vk::CommandPoolCreateInfo::builder()
+        .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+        .queue_family_index(indices.compute);
+
+    data.compute_command_pool = unsafe { device.create_command_pool(&info, None)? };
+    data.registry.created(data.compute_command_pool, 0);
     Ok(())
 }
 
@@ -52,9 +77,12 @@ pub unsafe fn create_command_buffers(device: &Device, data: &mut AppData) -> Res
         let allocate_info = vk::CommandBufferAllocateInfo::builder()
             .command_pool(data.command_pool)
             .level(vk::CommandBufferLevel::PRIMARY)
-            .command_buffer_count(data.framebuffers.len() as u32);
+            .command_buffer_count(data.swapchain_images.len() as u32);
 
         data.command_buffers = device.allocate_command_buffers(&allocate_info)?;
+        data.command_buffers
+            .iter()
+            .for_each(|c| data.registry.created(*c, 0));
         data.secondary_command_buffers = vec![vec![]; data.swapchain_images.len()];
     }
 
@@ -71,6 +99,7 @@ pub unsafe fn begin_onetime_command(
         .command_buffer_count(1);
 
     let command_buffer = device.allocate_command_buffers(&info)?[0];
+    data.registry.created(command_buffer, 0);
     let info =
         vk::CommandBufferBeginInfo::builder().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
 
@@ -92,6 +121,7 @@ pub unsafe fn end_onetime_command(
     device.queue_submit(data.graphics_queue, &[info], vk::Fence::null())?;
     device.queue_wait_idle(data.graphics_queue)?;
     device.free_command_buffers(data.command_pool, &[command_buffer]);
+    data.registry.destroyed(command_buffer);
 
     Ok(())
 }
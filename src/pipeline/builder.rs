@@ -0,0 +1,509 @@
+use anyhow::{Result, anyhow};
+use vulkanalia::{
+    Device,
+    vk::{self, DeviceV1_0, Handle, HasBuilder},
+};
+
+use crate::{
+    app::AppData,
+    pipeline::{
+        descriptor_allocator::DescriptorLayoutCache,
+        reflect::{ShaderInterface, reflect_shader},
+        resources::Pipeline,
+        shader::{create_shader_module, destroy_shader_module},
+    },
+};
+
+/// how the color a pipeline outputs is combined with the color already in the
+/// attachment
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// overwrites the attachment
+    Opaque,
+    /// blends by the alpha of the output
+    #[default]
+    Alpha,
+    /// blends by the alpha of the output, which the color is already
+    /// multiplied by
+    Premultiplied,
+    /// adds the output to the attachment
+    Additive,
+    /// multiplies the attachment by the output
+    Multiply,
+}
+
+impl BlendMode {
+    /// the blend state of a color attachment using the mode
+    pub fn attachment(self) -> vk::PipelineColorBlendAttachmentState {
+        let state = vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::all())
+            .color_blend_op(vk::BlendOp::ADD)
+            .alpha_blend_op(vk::BlendOp::ADD);
+
+        let (src_color, dst_color, src_alpha, dst_alpha) = match self {
+            Self::Opaque => return state.blend_enable(false).build(),
+            Self::Alpha => (
+                vk::BlendFactor::SRC_ALPHA,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ZERO,
+            ),
+            Self::Premultiplied => (
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            ),
+            Self::Additive => (
+                vk::BlendFactor::SRC_ALPHA,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE,
+            ),
+            Self::Multiply => (
+                vk::BlendFactor::DST_COLOR,
+                vk::BlendFactor::ZERO,
+                vk::BlendFactor::DST_ALPHA,
+                vk::BlendFactor::ZERO,
+            ),
+        };
+
+        state
+            .blend_enable(true)
+            .src_color_blend_factor(src_color)
+            .dst_color_blend_factor(dst_color)
+            .src_alpha_blend_factor(src_alpha)
+            .dst_alpha_blend_factor(dst_alpha)
+            .build()
+    }
+}
+
+/// the constant and slope scaled offset added to the depth of every fragment
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DepthBias {
+    pub constant_factor: f32,
+    pub clamp: f32,
+    pub slope_factor: f32,
+}
+
+/// describes a graphics pipeline, so pipelines for different materials can be
+/// created from the same code
+///
+/// The defaults match the scene pipeline: triangle lists, back-face culling
+/// of clockwise triangles, alpha blending and a LESS depth test. The viewport
+/// and scissor are always dynamic state, set at record time with
+/// [`crate::pipeline::rendering::set_viewport`].
+#[derive(Clone, Debug)]
+pub struct PipelineBuilder {
+    shaders: Vec<(vk::ShaderStageFlags, Vec<u8>)>,
+    vertex_bindings: Vec<vk::VertexInputBindingDescription>,
+    vertex_attributes: Vec<vk::VertexInputAttributeDescription>,
+    topology: vk::PrimitiveTopology,
+    polygon_mode: vk::PolygonMode,
+    line_width: f32,
+    cull_mode: vk::CullModeFlags,
+    front_face: vk::FrontFace,
+    depth_bias: Option<DepthBias>,
+    depth_test: bool,
+    depth_write: bool,
+    depth_compare_op: vk::CompareOp,
+    stencil: Option<(vk::StencilOpState, vk::StencilOpState)>,
+    blend: BlendMode,
+    samples: vk::SampleCountFlags,
+    dynamic_states: Vec<vk::DynamicState>,
+    set_layouts: Vec<vk::DescriptorSetLayout>,
+    push_constant_ranges: Vec<vk::PushConstantRange>,
+    color_formats: Vec<vk::Format>,
+    depth_format: vk::Format,
+    render_pass: vk::RenderPass,
+    subpass: u32,
+}
+
+impl Default for PipelineBuilder {
+    fn default() -> Self {
+        Self {
+            shaders: vec![],
+            vertex_bindings: vec![],
+            vertex_attributes: vec![],
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon_mode: vk::PolygonMode::FILL,
+            line_width: 1.0,
+            cull_mode: vk::CullModeFlags::BACK,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            depth_bias: None,
+            depth_test: true,
+            depth_write: true,
+            depth_compare_op: vk::CompareOp::LESS,
+            stencil: None,
+            blend: BlendMode::Alpha,
+            samples: vk::SampleCountFlags::_1,
+            dynamic_states: vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR],
+            set_layouts: vec![],
+            push_constant_ranges: vec![],
+            color_formats: vec![],
+            depth_format: vk::Format::UNDEFINED,
+            render_pass: vk::RenderPass::null(),
+            subpass: 0,
+        }
+    }
+}
+
+impl PipelineBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds a shader stage, its entry point must be `main`
+    pub fn shader(mut self, stage: vk::ShaderStageFlags, bytecode: &[u8]) -> Self {
+        self.shaders.push((stage, bytecode.to_vec()));
+        self
+    }
+
+    /// the vertex buffers the pipeline reads and the attributes within them
+    pub fn vertex_input(
+        mut self,
+        bindings: &[vk::VertexInputBindingDescription],
+        attributes: &[vk::VertexInputAttributeDescription],
+    ) -> Self {
+        self.vertex_bindings = bindings.to_vec();
+        self.vertex_attributes = attributes.to_vec();
+        self
+    }
+
+    pub fn topology(mut self, topology: vk::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// draws filled triangles, their edges or their vertices, lines wider
+    /// than 1 need the `wideLines` feature
+    pub fn polygon_mode(mut self, polygon_mode: vk::PolygonMode, line_width: f32) -> Self {
+        self.polygon_mode = polygon_mode;
+        self.line_width = line_width;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: vk::CullModeFlags, front_face: vk::FrontFace) -> Self {
+        self.cull_mode = cull_mode;
+        self.front_face = front_face;
+        self
+    }
+
+    pub fn depth_bias(mut self, depth_bias: DepthBias) -> Self {
+        self.depth_bias = Some(depth_bias);
+        self
+    }
+
+    /// whether fragments are tested against and written to the depth
+    /// attachment, and how they are compared
+    pub fn depth(mut self, test: bool, write: bool, compare_op: vk::CompareOp) -> Self {
+        self.depth_test = test;
+        self.depth_write = write;
+        self.depth_compare_op = compare_op;
+        self
+    }
+
+    /// enables the stencil test with the operations for front and back
+    /// facing triangles
+    pub fn stencil(mut self, front: vk::StencilOpState, back: vk::StencilOpState) -> Self {
+        self.stencil = Some((front, back));
+        self
+    }
+
+    pub fn blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// makes more of the pipeline state dynamic, the extended dynamic states
+    /// like `CULL_MODE` or `DEPTH_TEST_ENABLE` need a Vulkan 1.3 device
+    pub fn dynamic_state(mut self, state: vk::DynamicState) -> Self {
+        if !self.dynamic_states.contains(&state) {
+            self.dynamic_states.push(state);
+        }
+        self
+    }
+
+    /// adds a descriptor set layout to the pipeline layout, in set order
+    pub fn set_layout(mut self, set_layout: vk::DescriptorSetLayout) -> Self {
+        self.set_layouts.push(set_layout);
+        self
+    }
+
+    /// adds a push constant range to the pipeline layout, without any the
+    /// ranges are reflected from the shaders
+    pub fn push_constant_range(
+        mut self,
+        stages: vk::ShaderStageFlags,
+        offset: u32,
+        size: u32,
+    ) -> Self {
+        let range = vk::PushConstantRange::builder()
+            .stage_flags(stages)
+            .offset(offset)
+            .size(size)
+            .build();
+
+        self.push_constant_ranges.push(range);
+        self
+    }
+
+    /// the attachment formats the pipeline renders into with dynamic
+    /// rendering, the depth format may be `UNDEFINED` when there is none
+    pub fn formats(mut self, color_formats: &[vk::Format], depth_format: vk::Format) -> Self {
+        self.color_formats = color_formats.to_vec();
+        self.depth_format = depth_format;
+        self
+    }
+
+    /// the subpass the pipeline is used in when rendering with render passes
+    pub fn render_pass(mut self, render_pass: vk::RenderPass, subpass: u32) -> Self {
+        self.render_pass = render_pass;
+        self.subpass = subpass;
+        self
+    }
+
+    /// the state that is set at record time instead of baked into the pipeline
+    pub fn dynamic_states(&self) -> &[vk::DynamicState] {
+        &self.dynamic_states
+    }
+
+    pub fn input_assembly_state(&self) -> vk::PipelineInputAssemblyStateCreateInfo {
+        vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(self.topology)
+            .primitive_restart_enable(false)
+            .build()
+    }
+
+    pub fn rasterization_state(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        let depth_bias = self.depth_bias.unwrap_or_default();
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(self.polygon_mode)
+            .line_width(self.line_width)
+            .cull_mode(self.cull_mode)
+            .front_face(self.front_face)
+            .depth_bias_enable(self.depth_bias.is_some())
+            .depth_bias_constant_factor(depth_bias.constant_factor)
+            .depth_bias_clamp(depth_bias.clamp)
+            .depth_bias_slope_factor(depth_bias.slope_factor)
+            .build()
+    }
+
+    pub fn multisample_state(&self) -> vk::PipelineMultisampleStateCreateInfo {
+        vk::PipelineMultisampleStateCreateInfo::builder()
+            .sample_shading_enable(false)
+            .rasterization_samples(self.samples)
+            .build()
+    }
+
+    pub fn depth_stencil_state(&self) -> vk::PipelineDepthStencilStateCreateInfo {
+        let (front, back) = self.stencil.unwrap_or_default();
+        vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(self.depth_test)
+            .depth_write_enable(self.depth_write)
+            .depth_compare_op(self.depth_compare_op)
+            .depth_bounds_test_enable(false)
+            .min_depth_bounds(0.0)
+            .max_depth_bounds(1.0)
+            .stencil_test_enable(self.stencil.is_some())
+            .front(front)
+            .back(back)
+            .build()
+    }
+
+    /// the blend state of every color attachment, which are all blended the
+    /// same way
+    pub fn color_blend_attachments(&self) -> Vec<vk::PipelineColorBlendAttachmentState> {
+        vec![self.blend.attachment(); self.color_formats.len().max(1)]
+    }
+
+    /// the combined interface of the shader stages, reflected from their
+    /// SPIR-V
+    pub fn reflect(&self) -> Result<ShaderInterface> {
+        let stages = self
+            .shaders
+            .iter()
+            .map(|(_, bytecode)| reflect_shader(bytecode))
+            .collect::<Result<Vec<_>>>()?;
+
+        ShaderInterface::new(&stages)
+    }
+
+    /// fails when a shader reads a binding the set layout of its set lacks,
+    /// or when a set layout was not created through the cache
+    pub fn check_set_layouts(
+        &self,
+        interface: &ShaderInterface,
+        layouts: &DescriptorLayoutCache,
+    ) -> Result<()> {
+        for (set, layout) in self.set_layouts.iter().enumerate() {
+            let bindings = layouts.bindings(*layout).ok_or_else(|| {
+                anyhow!(
+                    "The layout of set {} was not created through the descriptor layout cache",
+                    set
+                )
+            })?;
+            interface.check_set_layout(set as u32, &bindings)?;
+        }
+
+        Ok(())
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Creates a pipeline layout from the set layouts and push constant
+    /// ranges, which the caller destroys. Fails when the shaders read push
+    /// constants outside of the ranges.
+    #[track_caller]
+    pub unsafe fn create_layout(
+        &self,
+        device: &Device,
+        data: &AppData,
+    ) -> Result<vk::PipelineLayout> {
+        let interface = self.reflect()?;
+        let push_constant_ranges = if self.push_constant_ranges.is_empty() {
+            &interface.push_constant_ranges
+        } else {
+            interface.check_push_constants(&self.push_constant_ranges)?;
+            &self.push_constant_ranges
+        };
+
+        let info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(&self.set_layouts)
+            .push_constant_ranges(push_constant_ranges);
+
+        let layout = unsafe { device.create_pipeline_layout(&info, None)? };
+        data.registry.created(layout, 0);
+
+        Ok(layout)
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Creates the pipeline with the layout, for dynamic rendering when the
+    /// app renders with it and for the render pass otherwise. Fails when the
+    /// shaders read vertex attributes or bindings the pipeline lacks.
+    #[track_caller]
+    pub unsafe fn build(
+        &self,
+        device: &Device,
+        data: &AppData,
+        layout: vk::PipelineLayout,
+    ) -> Result<Pipeline> {
+        if self.shaders.is_empty() {
+            return Err(anyhow!("Pipeline has no shader stages"));
+        }
+
+        let interface = self.reflect()?;
+        interface.check_vertex_input(&self.vertex_attributes)?;
+        self.check_set_layouts(&interface, &data.descriptor_layouts)?;
+
+        // the modules are created one at a time, so the ones created before a
+        // failing one are destroyed along with the rest
+        let mut modules = Vec::with_capacity(self.shaders.len());
+        let result = self
+            .shaders
+            .iter()
+            .try_for_each(|(_, bytecode)| {
+                modules.push(unsafe { create_shader_module(device, &data.registry, bytecode)? });
+                Ok(())
+            })
+            .and_then(|_| unsafe { self.create(device, data, layout, &modules) });
+
+        modules
+            .iter()
+            .for_each(|m| unsafe { destroy_shader_module(device, &data.registry, *m) });
+
+        let pipeline = result?;
+        data.registry.created(pipeline, 0);
+
+        Ok(Pipeline::new(&data.deletions, pipeline))
+    }
+
+    unsafe fn create(
+        &self,
+        device: &Device,
+        data: &AppData,
+        layout: vk::PipelineLayout,
+        modules: &[vk::ShaderModule],
+    ) -> Result<vk::Pipeline> {
+        let stages = self
+            .shaders
+            .iter()
+            .zip(modules)
+            .map(|((stage, _), module)| {
+                vk::PipelineShaderStageCreateInfo::builder()
+                    .stage(*stage)
+                    .module(*module)
+                    .name(b"main\0")
+            })
+            .collect::<Vec<_>>();
+
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(&self.vertex_bindings)
+            .vertex_attribute_descriptions(&self.vertex_attributes);
+
+        let input_assembly_state = self.input_assembly_state();
+
+        // the viewport and scissor are set at record time, so the pipeline
+        // survives the swapchain being resized
+        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewport_count(1)
+            .scissor_count(1);
+
+        let dynamic_state =
+            vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&self.dynamic_states);
+
+        let rasterization_state = self.rasterization_state();
+        let multisample_state = self.multisample_state();
+        let depth_stencil_state = self.depth_stencil_state();
+
+        let attachments = self.color_blend_attachments();
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .logic_op_enable(false)
+            .logic_op(vk::LogicOp::COPY)
+            .attachments(&attachments)
+            .blend_constants([0.0, 0.0, 0.0, 0.0]);
+
+        // with dynamic rendering the pipeline is described by the formats it
+        // renders into instead of a render pass
+        let mut rendering_info = vk::PipelineRenderingCreateInfo::builder()
+            .color_attachment_formats(&self.color_formats)
+            .depth_attachment_format(self.depth_format);
+
+        let mut info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&stages)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend_state)
+            .dynamic_state(&dynamic_state)
+            .layout(layout)
+            .render_pass(self.render_pass)
+            .subpass(self.subpass);
+
+        if data.dynamic_rendering {
+            info = info.push_next(&mut rendering_info);
+        }
+
+        let pipeline = unsafe {
+            device
+                .create_graphics_pipelines(data.pipeline_cache.cache, &[info], None)?
+                .0[0]
+        };
+
+        Ok(pipeline)
+    }
+}
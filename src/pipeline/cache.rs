@@ -0,0 +1,180 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use vulkanalia::{
+    Device, Instance,
+    vk::{self, DeviceV1_0, HasBuilder, InstanceV1_0},
+};
+
+use crate::app::AppData;
+
+/// the environment variable setting the file pipelines are cached in, or
+/// turning the cache off with `0`
+pub const PIPELINE_CACHE_ENV: &str = "CHOAM_PIPELINE_CACHE";
+
+/// the bytes written in front of the cache data, followed by the driver
+/// version the data was created with
+const CACHE_MAGIC: &[u8; 4] = b"CHPC";
+
+/// the size of the header at the start of the data returned by the driver,
+/// which has the layout of `VkPipelineCacheHeaderVersionOne`
+const DRIVER_HEADER_SIZE: usize = 32;
+
+/// the file the pipeline cache is kept in within the user cache directory,
+/// `$XDG_CACHE_HOME` or `~/.cache` and `%LOCALAPPDATA%` on windows
+pub fn default_cache_path() -> Option<PathBuf> {
+    let dir = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".cache")))
+    };
+
+    dir.map(|d| d.join("choam").join("pipelines.bin"))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// the cache data within the contents of a cache file, when the file was
+/// written for the device and driver of the properties
+///
+/// The driver version is stored in front of the data, while the header of
+/// the data itself holds the vendor, device and pipeline cache uuid.
+pub fn validate_cache_file<'a>(
+    contents: &'a [u8],
+    properties: &vk::PhysicalDeviceProperties,
+) -> Result<&'a [u8], &'static str> {
+    let data = contents
+        .strip_prefix(CACHE_MAGIC)
+        .ok_or("not a pipeline cache file")?;
+
+    if read_u32(data, 0) != Some(properties.driver_version) {
+        return Err("written by a different driver version");
+    }
+
+    let data = &data[4..];
+    let header_size = read_u32(data, 0).ok_or("truncated")? as usize;
+    if header_size < DRIVER_HEADER_SIZE || data.len() < header_size {
+        return Err("truncated");
+    }
+
+    if read_u32(data, 4) != Some(vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32) {
+        return Err("unknown header version");
+    }
+
+    if read_u32(data, 8) != Some(properties.vendor_id)
+        || read_u32(data, 12) != Some(properties.device_id)
+    {
+        return Err("written for a different device");
+    }
+
+    if data[16..32] != properties.pipeline_cache_uuid[..] {
+        return Err("written with a different pipeline cache uuid");
+    }
+
+    Ok(data)
+}
+
+/// the contents of a cache file holding the cache data of the driver
+pub fn encode_cache_file(data: &[u8], properties: &vk::PhysicalDeviceProperties) -> Vec<u8> {
+    let mut contents = Vec::with_capacity(CACHE_MAGIC.len() + 4 + data.len());
+    contents.extend_from_slice(CACHE_MAGIC);
+    contents.extend_from_slice(&properties.driver_version.to_le_bytes());
+    contents.extend_from_slice(data);
+    contents
+}
+
+/// the pipeline cache every pipeline is created with, loaded from and saved
+/// back to a file so pipelines are not recompiled on every start
+#[derive(Clone, Debug, Default)]
+pub struct PipelineCache {
+    pub cache: vk::PipelineCache,
+    /// the file the cache is kept in, the cache only lives in memory without
+    /// one
+    path: Option<PathBuf>,
+    properties: vk::PhysicalDeviceProperties,
+}
+
+impl PipelineCache {
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Creates the cache with the contents of the file when they were written
+    /// for this device and driver, and empty otherwise.
+    pub unsafe fn create(
+        instance: &Instance,
+        device: &Device,
+        data: &AppData,
+        path: Option<&Path>,
+    ) -> Result<Self> {
+        let properties = unsafe { instance.get_physical_device_properties(data.physical_device) };
+
+        let contents = path.and_then(|p| fs::read(p).ok()).unwrap_or_default();
+        let initial_data = match validate_cache_file(&contents, &properties) {
+            Ok(initial_data) => {
+                log::info!("Loaded {} bytes of cached pipelines", initial_data.len());
+                initial_data
+            }
+            Err(reason) => {
+                if !contents.is_empty() {
+                    log::info!("Ignoring the pipeline cache, it was {}", reason);
+                }
+                &[]
+            }
+        };
+
+        let info = vk::PipelineCacheCreateInfo::builder().initial_data(initial_data);
+        let cache = unsafe { device.create_pipeline_cache(&info, None)? };
+        data.registry.created(cache, 0);
+
+        Ok(Self {
+            cache,
+            path: path.map(Path::to_path_buf),
+            properties,
+        })
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Writes the cache to its file, replacing the file in one step so an
+    /// interrupted write never leaves a broken cache behind.
+    pub unsafe fn save(&self, device: &Device) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let data = unsafe { device.get_pipeline_cache_data(self.cache)? };
+        let contents = encode_cache_file(&data, &self.properties);
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create `{}`", dir.display()))?;
+        }
+
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, contents)
+            .with_context(|| format!("Failed to write `{}`", temp.display()))?;
+        fs::rename(&temp, path).with_context(|| format!("Failed to write `{}`", path.display()))?;
+
+        log::debug!(
+            "Saved {} bytes of cached pipelines to `{}`",
+            data.len(),
+            path.display()
+        );
+
+        Ok(())
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    pub unsafe fn destroy(&self, device: &Device) {
+        unsafe { device.destroy_pipeline_cache(self.cache, None) };
+    }
+}
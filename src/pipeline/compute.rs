@@ -0,0 +1,499 @@
+use anyhow::Result;
+use vulkanalia::{
+    Device, Instance,
+    vk::{self, DeviceV1_0, Handle, HasBuilder},
+};
+
+use crate::{
+    app::AppData,
+    foundation::device::QueueFamilyIndices,
+    pipeline::{
+        block::{BlockLayout, PushConstants, push_constants},
+        shader::{create_shader_module, destroy_shader_module},
+    },
+};
+
+/// a compute shader along with the layouts it is dispatched with
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ComputePipeline {
+    pub descriptor_set_layout: vk::DescriptorSetLayout,
+    pub pipeline_layout: vk::PipelineLayout,
+    pub pipeline: vk::Pipeline,
+    pub push_constant_size: u32,
+}
+
+impl ComputePipeline {
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Creates the pipeline for the compute shader, with a single descriptor
+    /// set made of the bindings and an optional push constant block. The set
+    /// layout comes from the `descriptor_layouts`, which own it.
+    #[track_caller]
+    pub unsafe fn create(
+        device: &Device,
+        data: &mut AppData,
+        bytecode: &[u8],
+        bindings: &[vk::DescriptorSetLayoutBindingBuilder],
+        push_constant_size: u32,
+    ) -> Result<Self> {
+        let bindings = bindings.iter().map(|b| b.build()).collect::<Vec<_>>();
+        let mut pipeline = Self {
+            descriptor_set_layout: unsafe {
+                data.descriptor_layouts
+                    .get(device, &data.registry, &bindings)?
+            },
+            push_constant_size,
+            ..Default::default()
+        };
+
+        // destroying the null handles of the objects not created yet is a no-op
+        unsafe {
+            if let Err(error) = pipeline.create_objects(device, data, bytecode) {
+                pipeline.destroy(device, data);
+                return Err(error);
+            }
+        }
+
+        Ok(pipeline)
+    }
+
+    #[track_caller]
+    unsafe fn create_objects(
+        &mut self,
+        device: &Device,
+        data: &AppData,
+        bytecode: &[u8],
+    ) -> Result<()> {
+        unsafe {
+            let push_constant_range = vk::PushConstantRange::builder()
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .offset(0)
+                .size(self.push_constant_size);
+
+            let set_layouts = &[self.descriptor_set_layout];
+            let push_constant_ranges = &[push_constant_range];
+            let mut layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(set_layouts);
+            if self.push_constant_size > 0 {
+                layout_info = layout_info.push_constant_ranges(push_constant_ranges);
+            }
+
+            self.pipeline_layout = device.create_pipeline_layout(&layout_info, None)?;
+            data.registry.created(self.pipeline_layout, 0);
+
+            let shader_module = create_shader_module(device, &data.registry, bytecode)?;
+            let stage = vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::COMPUTE)
+                .module(shader_module)
+                .name(b"main\0");
+
+            let info = vk::ComputePipelineCreateInfo::builder()
+                .stage(stage)
+                .layout(self.pipeline_layout);
+
+            let pipeline =
+                device.create_compute_pipelines(data.pipeline_cache.cache, &[info], None);
+            destroy_shader_module(device, &data.registry, shader_module);
+            self.pipeline = pipeline?.0[0];
+            data.registry.created(self.pipeline, 0);
+
+            Ok(())
+        }
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    pub unsafe fn bind(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        descriptor_set: vk::DescriptorSet,
+    ) {
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline_layout,
+                0,
+                &[descriptor_set],
+                &[],
+            );
+        }
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Pushes the block with the std430 layout, see [`push_constants`].
+    pub unsafe fn push_constants<T: PushConstants>(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        value: &T,
+    ) {
+        debug_assert!(T::STAGES == vk::ShaderStageFlags::COMPUTE);
+        debug_assert!(
+            T::OFFSET as usize + T::size(BlockLayout::Std430) <= self.push_constant_size as usize
+        );
+
+        unsafe { push_constants(device, command_buffer, self.pipeline_layout, value) };
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Dispatches enough workgroups of `local_size` invocations to cover every
+    /// element of `size`, the shader has to skip the invocations past the end.
+    pub unsafe fn dispatch(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        size: [u32; 3],
+        local_size: [u32; 3],
+    ) {
+        let groups = workgroup_count(size, local_size);
+        unsafe { device.cmd_dispatch(command_buffer, groups[0], groups[1], groups[2]) };
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Destroys the pipeline and its layout, the set layout stays in the
+    /// cache.
+    pub unsafe fn destroy(&self, device: &Device, data: &AppData) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+        data.registry.destroyed(self.pipeline);
+        data.registry.destroyed(self.pipeline_layout);
+    }
+}
+
+/// the number of workgroups needed to cover every element of `size`
+pub fn workgroup_count(size: [u32; 3], local_size: [u32; 3]) -> [u32; 3] {
+    [0, 1, 2].map(|i| size[i].div_ceil(local_size[i].max(1)))
+}
+
+/// hands resources written by compute shaders over to the graphics queue,
+/// as a release and acquire barrier pair when the compute queue belongs to
+/// another family, or as a plain barrier when both are the same queue
+#[derive(Copy, Clone, Debug)]
+pub struct ComputeToGraphics {
+    compute_family: u32,
+    graphics_family: u32,
+}
+
+impl ComputeToGraphics {
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    pub unsafe fn new(instance: &Instance, data: &AppData) -> Result<Self> {
+        let indices = unsafe { QueueFamilyIndices::get(instance, data, data.physical_device)? };
+        Ok(Self {
+            compute_family: indices.compute,
+            graphics_family: indices.graphics,
+        })
+    }
+
+    /// whether the compute queue is from another family than the graphics
+    /// queue, in which case the compute work must be submitted with
+    /// [`submit_compute`] so the graphics queue waits on it
+    pub fn ownership_transfer(&self) -> bool {
+        self.compute_family != self.graphics_family
+    }
+
+    fn queue_families(&self) -> (u32, u32) {
+        if self.ownership_transfer() {
+            (self.compute_family, self.graphics_family)
+        } else {
+            (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED)
+        }
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Records the release of a storage buffer at the end of the compute
+    /// work, does nothing when no ownership transfer is needed.
+    pub unsafe fn release_buffer(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        buffer: vk::Buffer,
+    ) {
+        if !self.ownership_transfer() {
+            return;
+        }
+
+        let (src_queue_family_index, dst_queue_family_index) = self.queue_families();
+        let barrier = vk::BufferMemoryBarrier::builder()
+            .src_queue_family_index(src_queue_family_index)
+            .dst_queue_family_index(dst_queue_family_index)
+            .buffer(buffer)
+            .offset(0)
+            .size(vk::WHOLE_SIZE)
+            .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+            .dst_access_mask(vk::AccessFlags::empty());
+
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::DependencyFlags::empty(),
+                &[] as &[vk::MemoryBarrier],
+                &[barrier],
+                &[] as &[vk::ImageMemoryBarrier],
+            );
+        }
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Records the acquire of a storage buffer before the graphics work that
+    /// reads it, e.g. as a vertex buffer with `VERTEX_INPUT` and
+    /// `VERTEX_ATTRIBUTE_READ`.
+    pub unsafe fn acquire_buffer(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        buffer: vk::Buffer,
+        dst_stage_mask: vk::PipelineStageFlags,
+        dst_access_mask: vk::AccessFlags,
+    ) {
+        let (src_queue_family_index, dst_queue_family_index) = self.queue_families();
+        let (src_stage_mask, src_access_mask) = self.acquire_src();
+        let barrier = vk::BufferMemoryBarrier::builder()
+            .src_queue_family_index(src_queue_family_index)
+            .dst_queue_family_index(dst_queue_family_index)
+            .buffer(buffer)
+            .offset(0)
+            .size(vk::WHOLE_SIZE)
+            .src_access_mask(src_access_mask)
+            .dst_access_mask(dst_access_mask);
+
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage_mask,
+                dst_stage_mask,
+                vk::DependencyFlags::empty(),
+                &[] as &[vk::MemoryBarrier],
+                &[barrier],
+                &[] as &[vk::ImageMemoryBarrier],
+            );
+        }
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Records the release of a storage image at the end of the compute work,
+    /// along with its transition from `GENERAL` to `new_layout`. Does nothing
+    /// when no ownership transfer is needed.
+    pub unsafe fn release_image(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        image: vk::Image,
+        new_layout: vk::ImageLayout,
+    ) {
+        if !self.ownership_transfer() {
+            return;
+        }
+
+        let barrier = self
+            .image_barrier(image, new_layout)
+            .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+            .dst_access_mask(vk::AccessFlags::empty());
+
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::DependencyFlags::empty(),
+                &[] as &[vk::MemoryBarrier],
+                &[] as &[vk::BufferMemoryBarrier],
+                &[barrier],
+            );
+        }
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Records the acquire of a storage image before the graphics work that
+    /// reads it, transitioning it from `GENERAL` to `new_layout` such as
+    /// `SHADER_READ_ONLY_OPTIMAL`.
+    pub unsafe fn acquire_image(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        image: vk::Image,
+        new_layout: vk::ImageLayout,
+        dst_stage_mask: vk::PipelineStageFlags,
+        dst_access_mask: vk::AccessFlags,
+    ) {
+        let (src_stage_mask, src_access_mask) = self.acquire_src();
+        let barrier = self
+            .image_barrier(image, new_layout)
+            .src_access_mask(src_access_mask)
+            .dst_access_mask(dst_access_mask);
+
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage_mask,
+                dst_stage_mask,
+                vk::DependencyFlags::empty(),
+                &[] as &[vk::MemoryBarrier],
+                &[] as &[vk::BufferMemoryBarrier],
+                &[barrier],
+            );
+        }
+    }
+
+    /// the first scope of an acquire barrier, with an ownership transfer the
+    /// semaphore wait already made the compute writes available
+    fn acquire_src(&self) -> (vk::PipelineStageFlags, vk::AccessFlags) {
+        if self.ownership_transfer() {
+            (COMPUTE_WAIT_STAGE, vk::AccessFlags::empty())
+        } else {
+            (
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::SHADER_WRITE,
+            )
+        }
+    }
+
+    fn image_barrier(
+        &self,
+        image: vk::Image,
+        new_layout: vk::ImageLayout,
+    ) -> vk::ImageMemoryBarrierBuilder<'static> {
+        let (src_queue_family_index, dst_queue_family_index) = self.queue_families();
+        let subresource = vk::ImageSubresourceRange::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(vk::REMAINING_MIP_LEVELS)
+            .base_array_layer(0)
+            .layer_count(vk::REMAINING_ARRAY_LAYERS);
+
+        vk::ImageMemoryBarrier::builder()
+            .old_layout(vk::ImageLayout::GENERAL)
+            .new_layout(new_layout)
+            .src_queue_family_index(src_queue_family_index)
+            .dst_queue_family_index(dst_queue_family_index)
+            .image(image)
+            .subresource_range(subresource)
+    }
+}
+
+/// the stage the graphics queue waits on compute work at, which covers every
+/// graphics stage that could read compute output
+pub const COMPUTE_WAIT_STAGE: vk::PipelineStageFlags = vk::PipelineStageFlags::ALL_COMMANDS;
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Submits the command buffer to the compute queue, signalling the semaphore
+/// which the next frame submitted to the graphics queue waits on. The compute
+/// work starts once the graphics timeline reaches `after`, so it can read what
+/// an earlier frame wrote, 0 starts it straight away. The fence is signalled
+/// once the compute work completes and may be null.
+pub unsafe fn submit_compute(
+    device: &Device,
+    data: &mut AppData,
+    command_buffer: vk::CommandBuffer,
+    semaphore: vk::Semaphore,
+    after: u64,
+    fence: vk::Fence,
+) -> Result<()> {
+    let command_buffers = &[command_buffer];
+    let signal_semaphores = &[semaphore];
+    let (wait_semaphores, wait_values) = if after == 0 {
+        (vec![], vec![])
+    } else {
+        (vec![data.timeline.semaphore], vec![after])
+    };
+    let wait_stages = vec![vk::PipelineStageFlags::COMPUTE_SHADER; wait_semaphores.len()];
+    let signal_values = &[0];
+
+    let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::builder()
+        .wait_semaphore_values(&wait_values)
+        .signal_semaphore_values(signal_values);
+
+    let info = vk::SubmitInfo::builder()
+        .wait_semaphores(&wait_semaphores)
+        .wait_dst_stage_mask(&wait_stages)
+        .command_buffers(command_buffers)
+        .signal_semaphores(signal_semaphores)
+        .push_next(&mut timeline_info);
+
+    unsafe { device.queue_submit(data.compute_queue, &[info], fence)? };
+    data.graphics_waits.push((semaphore, COMPUTE_WAIT_STAGE));
+
+    Ok(())
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Records compute work into a one time command buffer from the compute pool,
+/// submits it to the compute queue and waits for it to complete. Meant for
+/// work outside of the frame, such as precomputing data while loading, the
+/// results are visible to the host and to any later submission.
+pub unsafe fn run_compute(
+    device: &Device,
+    data: &AppData,
+    record: impl FnOnce(vk::CommandBuffer),
+) -> Result<()> {
+    let info = vk::CommandBufferAllocateInfo::builder()
+        .level(vk::CommandBufferLevel::PRIMARY)
+        .command_pool(data.compute_command_pool)
+        .command_buffer_count(1);
+
+    unsafe {
+        let command_buffer = device.allocate_command_buffers(&info)?[0];
+        data.registry.created(command_buffer, 0);
+        let result = (|| {
+            let info = vk::CommandBufferBeginInfo::builder()
+                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+            device.begin_command_buffer(command_buffer, &info)?;
+
+            record(command_buffer);
+
+            // make the shader writes visible to the host reading them back
+            let barrier = vk::MemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                .dst_access_mask(vk::AccessFlags::HOST_READ);
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[barrier],
+                &[] as &[vk::BufferMemoryBarrier],
+                &[] as &[vk::ImageMemoryBarrier],
+            );
+            device.end_command_buffer(command_buffer)?;
+
+            let command_buffers = &[command_buffer];
+            let info = vk::SubmitInfo::builder().command_buffers(command_buffers);
+            device.queue_submit(data.compute_queue, &[info], vk::Fence::null())?;
+            device.queue_wait_idle(data.compute_queue)?;
+            Ok(())
+        })();
+
+        device.free_command_buffers(data.compute_command_pool, &[command_buffer]);
+        data.registry.destroyed(command_buffer);
+        result
+    }
+}
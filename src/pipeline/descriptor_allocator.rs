@@ -0,0 +1,496 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow};
+use vulkanalia::{
+    Device,
+    vk::{self, DeviceV1_0, HasBuilder},
+};
+
+use crate::{
+    foundation::registry::ResourceRegistry,
+    pipeline::reflect::{DescriptorBinding, ShaderInterface},
+};
+
+/// the descriptors of each type reserved for every set a pool can hold
+pub const DEFAULT_POOL_RATIOS: &[(vk::DescriptorType, u32)] = &[
+    (vk::DescriptorType::UNIFORM_BUFFER, 1),
+    (vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, 1),
+    (vk::DescriptorType::STORAGE_BUFFER, 1),
+    (vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 4),
+    (vk::DescriptorType::STORAGE_IMAGE, 1),
+];
+
+/// the sets the first pool of an allocator holds
+pub const INITIAL_SETS_PER_POOL: u32 = 16;
+
+/// the sets a pool holds at most, every new pool holds twice as many as the
+/// last up to this
+pub const MAX_SETS_PER_POOL: u32 = 4096;
+
+/// the sizes of a pool holding `max_sets` sets with the descriptors of each
+/// type per set
+pub fn pool_sizes(
+    ratios: &[(vk::DescriptorType, u32)],
+    max_sets: u32,
+) -> Vec<vk::DescriptorPoolSize> {
+    ratios
+        .iter()
+        .map(|(type_, count)| {
+            vk::DescriptorPoolSize::builder()
+                .type_(*type_)
+                .descriptor_count(count * max_sets)
+                .build()
+        })
+        .collect()
+}
+
+/// the descriptors of each type a set with the bindings needs
+pub fn set_sizes(bindings: &[vk::DescriptorSetLayoutBinding]) -> Vec<vk::DescriptorPoolSize> {
+    let mut sizes = Vec::<vk::DescriptorPoolSize>::new();
+    for binding in bindings.iter().filter(|b| b.descriptor_count > 0) {
+        match sizes
+            .iter_mut()
+            .find(|s| s.type_ == binding.descriptor_type)
+        {
+            Some(size) => size.descriptor_count += binding.descriptor_count,
+            None => sizes.push(
+                vk::DescriptorPoolSize::builder()
+                    .type_(binding.descriptor_type)
+                    .descriptor_count(binding.descriptor_count)
+                    .build(),
+            ),
+        }
+    }
+
+    sizes
+}
+
+/// allocates descriptor sets from a growing list of pools, creating a new
+/// pool whenever the current ones run out
+///
+/// Sets are never freed one by one, `reset` returns every set at once, so an
+/// allocator is either kept for the lifetime of its sets or reset each time
+/// its frame in flight comes around.
+#[derive(Clone, Debug, Default)]
+pub struct DescriptorAllocator {
+    ratios: Vec<(vk::DescriptorType, u32)>,
+    sets_per_pool: u32,
+    /// pools that may still have room
+    ready: Vec<vk::DescriptorPool>,
+    /// pools that ran out, until the next reset
+    full: Vec<vk::DescriptorPool>,
+    /// pools sized for a single set too large for the others, destroyed on
+    /// the next reset
+    dedicated: Vec<vk::DescriptorPool>,
+}
+
+impl DescriptorAllocator {
+    /// an allocator without pools, the first is created by the first
+    /// allocation
+    pub fn new(ratios: &[(vk::DescriptorType, u32)]) -> Self {
+        Self {
+            ratios: ratios.to_vec(),
+            sets_per_pool: INITIAL_SETS_PER_POOL,
+            ready: vec![],
+            full: vec![],
+            dedicated: vec![],
+        }
+    }
+
+    pub fn pools(&self) -> impl Iterator<Item = vk::DescriptorPool> + '_ {
+        self.ready
+            .iter()
+            .chain(&self.full)
+            .chain(&self.dedicated)
+            .copied()
+    }
+
+    /// whether a set with the bindings fits in the pools created from the
+    /// ratios, which hold the ratio of every type times their sets, so even
+    /// the first pool has room for it
+    pub fn fits_pools(&self, bindings: &[vk::DescriptorSetLayoutBinding]) -> bool {
+        set_sizes(bindings).iter().all(|size| {
+            self.ratios.iter().any(|(type_, ratio)| {
+                *type_ == size.type_
+                    && size.descriptor_count <= ratio.saturating_mul(INITIAL_SETS_PER_POOL)
+            })
+        })
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    #[track_caller]
+    unsafe fn next_pool(
+        &mut self,
+        device: &Device,
+        registry: &ResourceRegistry,
+    ) -> Result<vk::DescriptorPool> {
+        if let Some(pool) = self.ready.pop() {
+            return Ok(pool);
+        }
+
+        let sizes = pool_sizes(&self.ratios, self.sets_per_pool);
+        let info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&sizes)
+            .max_sets(self.sets_per_pool);
+
+        let pool = unsafe { device.create_descriptor_pool(&info, None)? };
+        registry.created(pool, 0);
+        self.sets_per_pool = (self.sets_per_pool * 2).min(MAX_SETS_PER_POOL);
+
+        Ok(pool)
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Allocates a set with the layout, moving on to a new pool when the
+    /// current one is out of memory. The layout has to come from the cache,
+    /// a set that does not fit the pools of the ratios gets a pool of its own.
+    #[track_caller]
+    pub unsafe fn allocate(
+        &mut self,
+        device: &Device,
+        registry: &ResourceRegistry,
+        layouts: &DescriptorLayoutCache,
+        layout: vk::DescriptorSetLayout,
+    ) -> Result<vk::DescriptorSet> {
+        let bindings = layouts.bindings(layout).ok_or_else(|| {
+            anyhow!("The set layout was not created through the descriptor layout cache")
+        })?;
+        let layouts = &[layout];
+        if !self.fits_pools(&bindings) {
+            let sizes = set_sizes(&bindings);
+            let info = vk::DescriptorPoolCreateInfo::builder()
+                .pool_sizes(&sizes)
+                .max_sets(1);
+
+            let pool = unsafe { device.create_descriptor_pool(&info, None)? };
+            registry.created(pool, 0);
+            self.dedicated.push(pool);
+
+            let info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(pool)
+                .set_layouts(layouts);
+            return Ok(unsafe { device.allocate_descriptor_sets(&info)? }[0]);
+        }
+
+        let mut pool = unsafe { self.next_pool(device, registry)? };
+
+        let info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(pool)
+            .set_layouts(layouts);
+        let result = match unsafe { device.allocate_descriptor_sets(&info) } {
+            Err(vk::ErrorCode::OUT_OF_POOL_MEMORY | vk::ErrorCode::FRAGMENTED_POOL) => {
+                self.full.push(pool);
+                pool = unsafe { self.next_pool(device, registry)? };
+
+                let info = vk::DescriptorSetAllocateInfo::builder()
+                    .descriptor_pool(pool)
+                    .set_layouts(layouts);
+                unsafe { device.allocate_descriptor_sets(&info) }
+            }
+            result => result,
+        };
+
+        self.ready.push(pool);
+        Ok(result?[0])
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Returns every set to its pool, none of them may be in use anymore.
+    pub unsafe fn reset(&mut self, device: &Device, registry: &ResourceRegistry) -> Result<()> {
+        for pool in self.dedicated.drain(..) {
+            unsafe { device.destroy_descriptor_pool(pool, None) };
+            registry.destroyed(pool);
+        }
+
+        self.ready.append(&mut self.full);
+        for pool in &self.ready {
+            unsafe { device.reset_descriptor_pool(*pool, vk::DescriptorPoolResetFlags::empty())? };
+        }
+
+        Ok(())
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    pub unsafe fn destroy(&mut self, device: &Device, registry: &ResourceRegistry) {
+        let pools = self.ready.drain(..).chain(self.full.drain(..));
+        for pool in pools.chain(self.dedicated.drain(..)) {
+            unsafe { device.destroy_descriptor_pool(pool, None) };
+            registry.destroyed(pool);
+        }
+    }
+}
+
+/// what identifies a set layout, the bindings sorted by their number
+pub type LayoutKey = Vec<(u32, vk::DescriptorType, u32, vk::ShaderStageFlags)>;
+
+/// the key of the layout with the bindings, which does not depend on their
+/// order
+pub fn layout_key(bindings: &[vk::DescriptorSetLayoutBinding]) -> LayoutKey {
+    let mut key = bindings
+        .iter()
+        .map(|b| {
+            (
+                b.binding,
+                b.descriptor_type,
+                b.descriptor_count,
+                b.stage_flags,
+            )
+        })
+        .collect::<LayoutKey>();
+    key.sort_by_key(|(binding, ..)| *binding);
+    key
+}
+
+/// creates every distinct descriptor set layout once, so pipelines reading the
+/// same bindings share their layouts
+#[derive(Clone, Debug, Default)]
+pub struct DescriptorLayoutCache {
+    layouts: HashMap<LayoutKey, vk::DescriptorSetLayout>,
+}
+
+impl DescriptorLayoutCache {
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// The layout with the bindings, created the first time they are asked
+    /// for. Bindings with immutable samplers are not supported.
+    #[track_caller]
+    pub unsafe fn get(
+        &mut self,
+        device: &Device,
+        registry: &ResourceRegistry,
+        bindings: &[vk::DescriptorSetLayoutBinding],
+    ) -> Result<vk::DescriptorSetLayout> {
+        let key = layout_key(bindings);
+        if let Some(layout) = self.layouts.get(&key) {
+            return Ok(*layout);
+        }
+
+        let info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(bindings);
+        let layout = unsafe { device.create_descriptor_set_layout(&info, None)? };
+        registry.created(layout, 0);
+        self.layouts.insert(key, layout);
+
+        Ok(layout)
+    }
+
+    /// the bindings of a layout the cache created
+    pub fn bindings(
+        &self,
+        layout: vk::DescriptorSetLayout,
+    ) -> Option<Vec<vk::DescriptorSetLayoutBinding>> {
+        let (key, _) = self.layouts.iter().find(|(_, l)| **l == layout)?;
+
+        Some(
+            key.iter()
+                .map(|(binding, type_, count, stages)| {
+                    vk::DescriptorSetLayoutBinding::builder()
+                        .binding(*binding)
+                        .descriptor_type(*type_)
+                        .descriptor_count(*count)
+                        .stage_flags(*stages)
+                        .build()
+                })
+                .collect(),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.layouts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layouts.is_empty()
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    pub unsafe fn destroy(&mut self, device: &Device, registry: &ResourceRegistry) {
+        for (_, layout) in self.layouts.drain() {
+            unsafe { device.destroy_descriptor_set_layout(layout, None) };
+            registry.destroyed(layout);
+        }
+    }
+}
+
+/// a binding of a set, by its number or by the name shaders give it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DescriptorSlot<'a> {
+    Binding(u32),
+    Name(&'a str),
+}
+
+impl From<u32> for DescriptorSlot<'_> {
+    fn from(binding: u32) -> Self {
+        Self::Binding(binding)
+    }
+}
+
+impl<'a> From<&'a str> for DescriptorSlot<'a> {
+    fn from(name: &'a str) -> Self {
+        Self::Name(name)
+    }
+}
+
+/// collects the buffers and images of a descriptor set, checking each against
+/// the binding it is written to, and writes them all in one update
+#[derive(Clone, Debug, Default)]
+pub struct DescriptorWriter {
+    /// the name, number and type of every binding of the set
+    bindings: Vec<(Option<String>, u32, vk::DescriptorType)>,
+    buffers: Vec<(u32, vk::DescriptorType, vk::DescriptorBufferInfo)>,
+    images: Vec<(u32, vk::DescriptorType, vk::DescriptorImageInfo)>,
+}
+
+impl DescriptorWriter {
+    /// a writer for the set of a shader interface, whose bindings can be
+    /// written by name
+    pub fn new(interface: &ShaderInterface, set: u32) -> Self {
+        Self::reflected(interface.bindings.iter().filter(|b| b.set == set))
+    }
+
+    /// a writer for the reflected bindings of a set
+    pub fn reflected<'a>(bindings: impl IntoIterator<Item = &'a DescriptorBinding>) -> Self {
+        Self {
+            bindings: bindings
+                .into_iter()
+                .map(|b| (Some(b.name.clone()), b.binding, b.descriptor_type))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// a writer for a set with the layout bindings, which can only be written
+    /// by number
+    pub fn for_layout(bindings: &[vk::DescriptorSetLayoutBinding]) -> Self {
+        Self {
+            bindings: bindings
+                .iter()
+                .map(|b| (None, b.binding, b.descriptor_type))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn binding(&self, slot: DescriptorSlot) -> Result<(u32, vk::DescriptorType)> {
+        self.bindings
+            .iter()
+            .find(|(name, binding, _)| match slot {
+                DescriptorSlot::Binding(b) => *binding == b,
+                DescriptorSlot::Name(n) => name.as_deref() == Some(n),
+            })
+            .map(|(_, binding, type_)| (*binding, *type_))
+            .ok_or_else(|| anyhow!("The set has no binding {:?}", slot))
+    }
+
+    /// writes the range of the buffer to a uniform or storage buffer binding
+    pub fn buffer<'a>(
+        &mut self,
+        slot: impl Into<DescriptorSlot<'a>>,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        range: vk::DeviceSize,
+    ) -> Result<&mut Self> {
+        let slot = slot.into();
+        let (binding, type_) = self.binding(slot)?;
+
+        match type_ {
+            vk::DescriptorType::UNIFORM_BUFFER
+            | vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
+            | vk::DescriptorType::STORAGE_BUFFER
+            | vk::DescriptorType::STORAGE_BUFFER_DYNAMIC => {}
+            _ => {
+                return Err(anyhow!("Binding {:?} is a {:?}, not a buffer", slot, type_));
+            }
+        }
+
+        let info = vk::DescriptorBufferInfo::builder()
+            .buffer(buffer)
+            .offset(offset)
+            .range(range)
+            .build();
+        self.buffers.push((binding, type_, info));
+
+        Ok(self)
+    }
+
+    /// writes the image view, with the sampler for combined image samplers, to
+    /// an image binding
+    pub fn image<'a>(
+        &mut self,
+        slot: impl Into<DescriptorSlot<'a>>,
+        image_view: vk::ImageView,
+        sampler: vk::Sampler,
+        layout: vk::ImageLayout,
+    ) -> Result<&mut Self> {
+        let slot = slot.into();
+        let (binding, type_) = self.binding(slot)?;
+
+        match type_ {
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+            | vk::DescriptorType::SAMPLED_IMAGE
+            | vk::DescriptorType::STORAGE_IMAGE
+            | vk::DescriptorType::INPUT_ATTACHMENT => {}
+            _ => {
+                return Err(anyhow!("Binding {:?} is a {:?}, not an image", slot, type_));
+            }
+        }
+
+        let info = vk::DescriptorImageInfo::builder()
+            .image_view(image_view)
+            .sampler(sampler)
+            .image_layout(layout)
+            .build();
+        self.images.push((binding, type_, info));
+
+        Ok(self)
+    }
+
+    /// the bindings written so far
+    pub fn written(&self) -> Vec<u32> {
+        self.buffers
+            .iter()
+            .map(|(b, ..)| *b)
+            .chain(self.images.iter().map(|(b, ..)| *b))
+            .collect()
+    }
+
+    /// forgets what was written, so the writer can fill another set
+    pub fn clear(&mut self) {
+        self.buffers.clear();
+        self.images.clear();
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Writes everything collected to the set.
+    pub unsafe fn update(&self, device: &Device, descriptor_set: vk::DescriptorSet) {
+        let buffer_writes = self.buffers.iter().map(|(binding, type_, info)| {
+            vk::WriteDescriptorSet::builder()
+                .dst_set(descriptor_set)
+                .dst_binding(*binding)
+                .dst_array_element(0)
+                .descriptor_type(*type_)
+                .buffer_info(std::slice::from_ref(info))
+        });
+
+        let image_writes = self.images.iter().map(|(binding, type_, info)| {
+            vk::WriteDescriptorSet::builder()
+                .dst_set(descriptor_set)
+                .dst_binding(*binding)
+                .dst_array_element(0)
+                .descriptor_type(*type_)
+                .image_info(std::slice::from_ref(info))
+        });
+
+        let writes = buffer_writes.chain(image_writes).collect::<Vec<_>>();
+        unsafe { device.update_descriptor_sets(&writes, &[] as &[vk::CopyDescriptorSet]) };
+    }
+}
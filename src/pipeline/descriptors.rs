@@ -1,135 +1,193 @@
 use anyhow::Result;
 use cgmath::{Deg, point3, vec3};
-use std::ptr::copy_nonoverlapping as memcpy;
-use std::time::Instant;
 use vulkanalia::{
-    Device, Instance,
-    vk::{self, DeviceV1_0, HasBuilder},
+    Device,
+    vk::{self, Handle, HasBuilder},
 };
 
-use crate::{app::AppData, pipeline::vertex::create_buffer};
+use crate::{
+    app::AppData,
+    pipeline::{
+        block::{BlockLayout, BlockValue, PushConstants},
+        descriptor_allocator::DescriptorWriter,
+        reflect::ShaderInterface,
+        scene_pipeline,
+    },
+};
 
 pub type Mat4 = cgmath::Matrix4<f32>;
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug)]
-pub struct UniformBufferObject {
-    view: Mat4,
-    proj: Mat4,
+crate::gpu_block! {
+    /// the scene uniforms, the `ubo` block of the vertex shader
+    pub struct UniformBufferObject {
+        pub view: Mat4,
+        pub proj: Mat4,
+    }
 }
 
-pub unsafe fn create_descriptor_set_layout(device: &Device, data: &mut AppData) -> Result<()> {
-    let ubo_binding = vk::DescriptorSetLayoutBinding::builder()
-        .binding(0)
-        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-        .descriptor_count(1)
-        .stage_flags(vk::ShaderStageFlags::VERTEX);
+crate::gpu_block! {
+    /// the vertex shader push constants of a model
+    pub struct ModelConstants {
+        pub model: Mat4,
+    }
+}
 
-    let sampler_binding = vk::DescriptorSetLayoutBinding::builder()
-        .binding(1)
-        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-        .descriptor_count(1)
-        .stage_flags(vk::ShaderStageFlags::FRAGMENT);
+impl PushConstants for ModelConstants {
+    const STAGES: vk::ShaderStageFlags = vk::ShaderStageFlags::VERTEX;
+}
 
-    let bindings = &[ubo_binding, sampler_binding];
-    let info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(bindings);
-    data.descriptor_set_layout = device.create_descriptor_set_layout(&info, None)?;
+crate::gpu_block! {
+    /// the fragment shader push constants of a model, after the
+    /// [`ModelConstants`]
+    pub struct OpacityConstants {
+        pub opacity: f32,
+    }
+}
 
-    Ok(())
+impl PushConstants for OpacityConstants {
+    const STAGES: vk::ShaderStageFlags = vk::ShaderStageFlags::FRAGMENT;
+    const OFFSET: u32 = 64;
 }
 
-pub unsafe fn create_uniform_buffers(
-    instance: &Instance,
-    device: &Device,
-    data: &mut AppData,
-) -> Result<()> {
-    data.uniform_buffers.clear();
-    data.uniform_buffers_memory.clear();
+/// the bindings the scene shaders read, as the scene descriptor set binds them
+fn scene_interface(data: &AppData) -> Result<ShaderInterface> {
+    let mut interface = scene_pipeline(data, &data.scene_shaders).reflect()?;
 
-    for _ in 0..data.swapchain_images.len() {
-        let (uniform_buffer, uniform_buffer_memory) = create_buffer(
-            instance,
-            device,
-            data,
-            size_of::<UniformBufferObject>() as u64,
-            vk::BufferUsageFlags::UNIFORM_BUFFER,
-            vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
-        )?;
+    // the scene uniforms live in the uniform ring, bound at a dynamic offset
+    interface
+        .bindings
+        .iter_mut()
+        .filter(|b| b.descriptor_type == vk::DescriptorType::UNIFORM_BUFFER)
+        .for_each(|b| b.descriptor_type = vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC);
 
-        data.uniform_buffers.push(uniform_buffer);
-        data.uniform_buffers_memory.push(uniform_buffer_memory);
-    }
+    Ok(interface)
+}
+
+/// creates the layout of the scene descriptor set from the bindings the scene
+/// shaders read
+pub unsafe fn create_descriptor_set_layout(device: &Device, data: &mut AppData) -> Result<()> {
+    let bindings = scene_interface(data)?.set_layout_bindings(0);
+    data.descriptor_set_layout = data
+        .descriptor_layouts
+        .get(device, &data.registry, &bindings)?;
 
     Ok(())
 }
 
-pub unsafe fn create_descriptor_pool(device: &Device, data: &mut AppData) -> Result<()> {
-    let ubo_size = vk::DescriptorPoolSize::builder()
-        .type_(vk::DescriptorType::UNIFORM_BUFFER)
-        .descriptor_count(data.swapchain_images.len() as u32);
+/// a storage buffer binding, for buffers shaders read and write
+pub fn storage_buffer_binding(
+    binding: u32,
+    stages: vk::ShaderStageFlags,
+) -> vk::DescriptorSetLayoutBindingBuilder<'static> {
+    vk::DescriptorSetLayoutBinding::builder()
+        .binding(binding)
+        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+        .descriptor_count(1)
+        .stage_flags(stages)
+}
 
-    let sampler_size = vk::DescriptorPoolSize::builder()
-        .type_(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-        .descriptor_count(data.swapchain_images.len() as u32);
+/// a storage image binding, for images shaders read and write in the
+/// `GENERAL` layout
+pub fn storage_image_binding(
+    binding: u32,
+    stages: vk::ShaderStageFlags,
+) -> vk::DescriptorSetLayoutBindingBuilder<'static> {
+    vk::DescriptorSetLayoutBinding::builder()
+        .binding(binding)
+        .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+        .descriptor_count(1)
+        .stage_flags(stages)
+}
 
-    let pool_sizes = &[ubo_size, sampler_size];
-    let info = vk::DescriptorPoolCreateInfo::builder()
-        .pool_sizes(pool_sizes)
-        .max_sets(data.swapchain_images.len() as u32);
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+pub unsafe fn write_storage_buffer(
+    device: &Device,
+    descriptor_set: vk::DescriptorSet,
+    binding: u32,
+    buffer: vk::Buffer,
+    range: vk::DeviceSize,
+) -> Result<()> {
+    let bindings = [storage_buffer_binding(binding, vk::ShaderStageFlags::ALL).build()];
+    let mut writer = DescriptorWriter::for_layout(&bindings);
+    writer.buffer(binding, buffer, 0, range)?;
+    unsafe { writer.update(device, descriptor_set) };
 
-    data.descriptor_pool = device.create_descriptor_pool(&info, None)?;
+    Ok(())
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+pub unsafe fn write_storage_image(
+    device: &Device,
+    descriptor_set: vk::DescriptorSet,
+    binding: u32,
+    image_view: vk::ImageView,
+) -> Result<()> {
+    let bindings = [storage_image_binding(binding, vk::ShaderStageFlags::ALL).build()];
+    let mut writer = DescriptorWriter::for_layout(&bindings);
+    writer.image(
+        binding,
+        image_view,
+        vk::Sampler::null(),
+        vk::ImageLayout::GENERAL,
+    )?;
+    unsafe { writer.update(device, descriptor_set) };
 
     Ok(())
 }
 
-pub unsafe fn create_descriptor_sets(device: &Device, data: &mut AppData) -> Result<()> {
-    let layouts = vec![data.descriptor_set_layout; data.swapchain_images.len()];
-    let info = vk::DescriptorSetAllocateInfo::builder()
-        .descriptor_pool(data.descriptor_pool)
-        .set_layouts(&layouts);
-
-    data.descriptor_sets = device.allocate_descriptor_sets(&info)?;
-    for i in 0..data.swapchain_images.len() {
-        let info = vk::DescriptorBufferInfo::builder()
-            .buffer(data.uniform_buffers[i])
-            .offset(0)
-            .range(size_of::<UniformBufferObject>() as u64);
-
-        let buffer_info = &[info];
-        let ubo_write = vk::WriteDescriptorSet::builder()
-            .dst_set(data.descriptor_sets[i])
-            .dst_binding(0)
-            .dst_array_element(0)
-            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-            .buffer_info(buffer_info);
-
-        let info = vk::DescriptorImageInfo::builder()
-            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-            .image_view(data.texture_image_view)
-            .sampler(data.texture_sampler);
-
-        let image_info = &[info];
-        let sampler_write = vk::WriteDescriptorSet::builder()
-            .dst_set(data.descriptor_sets[i])
-            .dst_binding(1)
-            .dst_array_element(0)
-            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-            .image_info(image_info);
-
-        device.update_descriptor_sets(&[ubo_write, sampler_write], &[] as &[vk::CopyDescriptorSet]);
-    }
+/// fills the writer of the scene descriptor set, the sets of every frame
+/// bind the same resources and only the dynamic offset changes
+pub fn create_scene_descriptors(data: &mut AppData) -> Result<()> {
+    let interface = scene_interface(data)?;
+    let mut writer = DescriptorWriter::new(&interface, 0);
+    writer
+        .buffer(
+            "ubo",
+            data.uniforms.buffer(),
+            0,
+            UniformBufferObject::size(BlockLayout::Std140) as u64,
+        )?
+        .image(
+            "texSampler",
+            data.texture_image_view.handle(),
+            data.texture_sampler.handle(),
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )?;
 
+    data.scene_descriptors = writer;
     Ok(())
 }
 
-pub unsafe fn update_uniform_buffer(
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Resets the descriptor allocator of the frame, whose last submission has to
+/// be finished, and allocates the scene descriptor set of the frame from it.
+pub unsafe fn allocate_frame_descriptors(
     device: &Device,
-    start: &Instant,
-    data: &AppData,
-    image_index: usize,
+    data: &mut AppData,
+    frame: usize,
 ) -> Result<()> {
-    let time = start.elapsed().as_secs_f32();
+    let allocator = &mut data.frame_descriptors[frame];
+    unsafe {
+        allocator.reset(device, &data.registry)?;
+        data.descriptor_set = allocator.allocate(
+            device,
+            &data.registry,
+            &data.descriptor_layouts,
+            data.descriptor_set_layout,
+        )?;
+        data.scene_descriptors.update(device, data.descriptor_set);
+    }
 
+    Ok(())
+}
+
+/// writes the scene uniforms of the current frame into the uniform ring,
+/// returning the dynamic offset they are bound at
+pub fn update_uniform_buffer(data: &mut AppData) -> Result<u32> {
     // create the model view projection matrices
     let view = Mat4::look_at_rh(
         point3(6.0, 0.0, 2.0),
@@ -168,16 +226,6 @@ pub unsafe fn update_uniform_buffer(
     // create the ubo object
     let ubo = UniformBufferObject { view, proj };
 
-    // copy the memory into the uniform buffer that is active
-    let memory = device.map_memory(
-        data.uniform_buffers_memory[image_index],
-        0,
-        size_of::<UniformBufferObject>() as u64,
-        vk::MemoryMapFlags::empty(),
-    )?;
-
-    memcpy(&ubo, memory.cast(), 1);
-    device.unmap_memory(data.uniform_buffers_memory[image_index]);
-
-    Ok(())
+    // copy the ubo into the region of the frame that is active
+    data.uniforms.push(&ubo)
 }
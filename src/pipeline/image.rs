@@ -1,6 +1,5 @@
 use anyhow::{Result, anyhow};
 use std::fs::File;
-use std::ptr::copy_nonoverlapping as memcpy;
 
 use vulkanalia::{
     Device, Instance,
@@ -10,14 +9,17 @@ use vulkanalia::{
 use crate::{
     app::AppData,
     pipeline::{
-        buffers::{begin_onetime_command, end_onetime_command},
-        vertex::{create_buffer, get_memory_type_index},
+        barriers::{ImageState, transition_image_layout},
+        memory::{Allocation, allocate_image},
+        resources::{Image, ImageView},
+        upload::UploadBatch,
     },
     scenes::mipmaps::generate_mipmaps,
 };
 
 /// # Safety
 /// This is a vulkan using function and thus is unsafe
+#[track_caller]
 pub unsafe fn create_image(
     instance: &Instance,
     device: &Device,
@@ -30,7 +32,7 @@ pub unsafe fn create_image(
     tiling: vk::ImageTiling,
     usage: vk::ImageUsageFlags,
     properties: vk::MemoryPropertyFlags,
-) -> Result<(vk::Image, vk::DeviceMemory)> {
+) -> Result<(vk::Image, Allocation)> {
     let info = vk::ImageCreateInfo::builder()
         .image_type(vk::ImageType::_2D)
         .extent(vk::Extent3D {
@@ -49,28 +51,22 @@ pub unsafe fn create_image(
         .flags(vk::ImageCreateFlags::empty());
 
     let image = device.create_image(&info, None)?;
-    let requirements = device.get_image_memory_requirements(image);
-    let info = vk::MemoryAllocateInfo::builder()
-        .allocation_size(requirements.size)
-        .memory_type_index(get_memory_type_index(
-            instance,
-            data,
-            properties,
-            requirements,
-        )?);
-
-    let image_memory = device.allocate_memory(&info, None)?;
-    device.bind_image_memory(image, image_memory, 0)?;
+    let allocation = allocate_image(instance, device, data, image, tiling, properties)?;
+    data.registry.created(image, allocation.size);
 
-    Ok((image, image_memory))
+    Ok((image, allocation))
 }
 
 /// # Safety
 /// This is a vulkan using function and thus is unsafe
+///
+/// Creates the texture image and records its upload and mipmap generation into
+/// the batch, the texture is ready to sample once the batch is submitted.
 pub unsafe fn create_texture_image(
     instance: &Instance,
     device: &Device,
     data: &mut AppData,
+    batch: &mut UploadBatch,
 ) -> Result<()> {
     let image = File::open("textures/viking_room.png")?;
     let decoder = png::Decoder::new(image);
@@ -79,26 +75,11 @@ pub unsafe fn create_texture_image(
     let mut pixels = vec![0; reader.info().raw_bytes()];
     reader.next_frame(&mut pixels)?;
 
-    let size = reader.info().raw_bytes() as u64;
     let (width, height) = reader.info().size();
 
     data.mip_levels = (width.max(height) as f32).log2().floor() as u32 + 1;
 
     unsafe {
-        let (staging_buffer, staging_buffer_memory) = create_buffer(
-            instance,
-            device,
-            data,
-            size,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
-        )?;
-
-        let memory =
-            device.map_memory(staging_buffer_memory, 0, size, vk::MemoryMapFlags::empty())?;
-        memcpy(pixels.as_ptr(), memory.cast(), pixels.len());
-        device.unmap_memory(staging_buffer_memory);
-
         // create the vulkan image object to store the image data
         let (texture_image, texture_image_memory) = create_image(
             instance,
@@ -116,46 +97,37 @@ pub unsafe fn create_texture_image(
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
         )?;
 
-        data.texture_image = texture_image;
-        data.texture_image_memory = texture_image_memory;
-
-        transition_image_layout(
-            device,
-            data,
-            data.texture_image,
-            vk::Format::R8G8B8A8_SRGB,
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            data.mip_levels,
-        )?;
+        data.texture_image = Image::new(&data.deletions, texture_image, texture_image_memory);
 
-        copy_buffer_to_image(
+        batch.upload_image(
+            instance,
             device,
             data,
-            staging_buffer,
-            data.texture_image,
+            &pixels,
+            texture_image,
             width,
             height,
-        )?;
-
-        transition_image_layout(
-            device,
-            data,
-            data.texture_image,
-            vk::Format::R8G8B8A8_SRGB,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
             data.mip_levels,
         )?;
 
-        device.destroy_buffer(staging_buffer, None);
-        device.free_memory(staging_buffer_memory, None);
+        // the upload leaves every level ready to be written on the graphics
+        // queue, which the tracker takes over from here
+        data.layouts.register(
+            texture_image,
+            vk::ImageAspectFlags::COLOR,
+            data.mip_levels,
+            1,
+            ImageState::TRANSFER_DST,
+        );
 
+        // blitting needs a graphics queue, so the mip chain is generated after
+        // the graphics queue acquired the uploaded image
         generate_mipmaps(
             instance,
             device,
             data,
-            data.texture_image,
+            batch.graphics_command_buffer(),
+            texture_image,
             vk::Format::R8G8B8A8_SRGB,
             width,
             height,
@@ -168,35 +140,36 @@ pub unsafe fn create_texture_image(
 
 /// # Safety
 /// This is a vulkan using function and thus is unsafe
+///
+/// Records a copy of the buffer into the first mip level of the image, which
+/// must be in `TRANSFER_DST_OPTIMAL`.
 pub unsafe fn copy_buffer_to_image(
     device: &Device,
-    data: &AppData,
+    command_buffer: vk::CommandBuffer,
     buffer: vk::Buffer,
     image: vk::Image,
     width: u32,
     height: u32,
-) -> Result<()> {
-    unsafe {
-        let command_buffer = begin_onetime_command(device, data)?;
-
-        let subresource = vk::ImageSubresourceLayers::builder()
-            .aspect_mask(vk::ImageAspectFlags::COLOR)
-            .mip_level(0)
-            .base_array_layer(0)
-            .layer_count(1);
-
-        let region = vk::BufferImageCopy::builder()
-            .buffer_offset(0)
-            .buffer_row_length(0)
-            .buffer_image_height(0)
-            .image_subresource(subresource)
-            .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
-            .image_extent(vk::Extent3D {
-                width,
-                height,
-                depth: 1,
-            });
+) {
+    let subresource = vk::ImageSubresourceLayers::builder()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .mip_level(0)
+        .base_array_layer(0)
+        .layer_count(1);
+
+    let region = vk::BufferImageCopy::builder()
+        .buffer_offset(0)
+        .buffer_row_length(0)
+        .buffer_image_height(0)
+        .image_subresource(subresource)
+        .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+        .image_extent(vk::Extent3D {
+            width,
+            height,
+            depth: 1,
+        });
 
+    unsafe {
         device.cmd_copy_buffer_to_image(
             command_buffer,
             buffer,
@@ -204,95 +177,15 @@ pub unsafe fn copy_buffer_to_image(
             vk::ImageLayout::TRANSFER_DST_OPTIMAL,
             &[region],
         );
-
-        end_onetime_command(device, data, command_buffer)?;
     }
-
-    Ok(())
-}
-
-pub unsafe fn transition_image_layout(
-    device: &Device,
-    data: &AppData,
-    image: vk::Image,
-    format: vk::Format,
-    old_layout: vk::ImageLayout,
-    new_layout: vk::ImageLayout,
-    mip_levels: u32,
-) -> Result<()> {
-    let (src_access_mask, dst_access_mask, src_stage_mask, dst_stage_mask) =
-        match (old_layout, new_layout) {
-            (vk::ImageLayout::UNDEFINED, vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL) => (
-                vk::AccessFlags::empty(),
-                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
-                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-                vk::PipelineStageFlags::TOP_OF_PIPE,
-                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-            ),
-            (vk::ImageLayout::UNDEFINED, vk::ImageLayout::TRANSFER_DST_OPTIMAL) => (
-                vk::AccessFlags::empty(),
-                vk::AccessFlags::TRANSFER_WRITE,
-                vk::PipelineStageFlags::TOP_OF_PIPE,
-                vk::PipelineStageFlags::TRANSFER,
-            ),
-            (vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL) => (
-                vk::AccessFlags::TRANSFER_WRITE,
-                vk::AccessFlags::SHADER_READ,
-                vk::PipelineStageFlags::TRANSFER,
-                vk::PipelineStageFlags::FRAGMENT_SHADER,
-            ),
-            _ => return Err(anyhow!("Unsupported Image Layout transition")),
-        };
-
-    let command_buffer = begin_onetime_command(device, data)?;
-
-    let aspect_mask = if new_layout == vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL {
-        match format {
-            vk::Format::D32_SFLOAT_S8_UINT | vk::Format::D24_UNORM_S8_UINT => {
-                vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
-            }
-            _ => vk::ImageAspectFlags::DEPTH,
-        }
-    } else {
-        vk::ImageAspectFlags::COLOR
-    };
-
-    let subresource = vk::ImageSubresourceRange::builder()
-        .aspect_mask(aspect_mask)
-        .base_mip_level(0)
-        .level_count(mip_levels)
-        .base_array_layer(0)
-        .layer_count(1);
-
-    let barrier = vk::ImageMemoryBarrier::builder()
-        .old_layout(old_layout)
-        .new_layout(new_layout)
-        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-        .image(image)
-        .subresource_range(subresource)
-        .src_access_mask(src_access_mask)
-        .dst_access_mask(dst_access_mask);
-
-    device.cmd_pipeline_barrier(
-        command_buffer,
-        src_stage_mask,
-        dst_stage_mask,
-        vk::DependencyFlags::empty(),
-        &[] as &[vk::MemoryBarrier],
-        &[] as &[vk::BufferMemoryBarrier],
-        &[barrier],
-    );
-
-    end_onetime_command(device, data, command_buffer)?;
-
-    Ok(())
 }
 
 /// # Safety
 /// This is a vulkan using function and thus is unsafe
+#[track_caller]
 pub unsafe fn create_image_view(
     device: &Device,
+    data: &AppData,
     image: vk::Image,
     format: vk::Format,
     aspects: vk::ImageAspectFlags,
@@ -311,7 +204,10 @@ pub unsafe fn create_image_view(
         .format(format)
         .subresource_range(subresource_range);
 
-    Ok(unsafe { device.create_image_view(&info, None)? })
+    let view = unsafe { device.create_image_view(&info, None)? };
+    data.registry.created(view, 0);
+
+    Ok(view)
 }
 
 /// # Safety
@@ -381,28 +277,30 @@ pub unsafe fn create_depth_objects(
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
         )?;
 
-        data.depth_image = depth_image;
-        data.depth_image_memory = depth_image_memory;
+        data.depth_image = Image::new(&data.deletions, depth_image, depth_image_memory);
 
         // image view
 
-        data.depth_image_view = create_image_view(
+        let depth_image_view = create_image_view(
             device,
-            data.depth_image,
+            data,
+            depth_image,
             format,
             vk::ImageAspectFlags::DEPTH,
             1,
         )?;
+        data.depth_image_view = ImageView::new(&data.deletions, depth_image_view);
 
-        transition_image_layout(
-            device,
-            data,
-            data.depth_image,
-            format,
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-            1,
-        )?;
+        let aspect_mask = match format {
+            vk::Format::D32_SFLOAT_S8_UINT | vk::Format::D24_UNORM_S8_UINT => {
+                vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+            }
+            _ => vk::ImageAspectFlags::DEPTH,
+        };
+
+        data.layouts
+            .register(depth_image, aspect_mask, 1, 1, ImageState::UNDEFINED);
+        transition_image_layout(device, data, depth_image, ImageState::DEPTH_ATTACHMENT)?;
 
         // aspect mask and subresource creation
 
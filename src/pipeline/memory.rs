@@ -0,0 +1,551 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Result, anyhow};
+use vulkanalia::{
+    Device, Instance,
+    vk::{self, DeviceV1_0, DeviceV1_1, HasBuilder, InstanceV1_0},
+};
+
+use crate::{app::AppData, pipeline::vertex::get_memory_type_index};
+
+/// the size of the blocks resources are carved out of, heaps of a gigabyte
+/// or less use an eighth of their size instead
+pub const BLOCK_SIZE: vk::DeviceSize = 64 * 1024 * 1024;
+
+/// resources at least this large always get their own memory object instead
+/// of taking up most of a block
+pub const DEDICATED_THRESHOLD: vk::DeviceSize = 32 * 1024 * 1024;
+
+const SMALL_HEAP_SIZE: vk::DeviceSize = 1024 * 1024 * 1024;
+
+/// a range of device memory handed out by the allocator, the resource is
+/// bound at `offset` within `memory`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Allocation {
+    pub memory: vk::DeviceMemory,
+    pub offset: vk::DeviceSize,
+    pub size: vk::DeviceSize,
+    block: usize,
+    /// the id of the block, so an allocation outliving its block is not
+    /// mistaken for one of the block that reused its slot
+    block_id: u64,
+}
+
+/// how much of a memory heap the allocator has reserved and handed out
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeapStatistics {
+    pub heap_index: u32,
+    pub heap_size: vk::DeviceSize,
+    /// memory objects allocated from the heap, including dedicated ones
+    pub blocks: usize,
+    pub dedicated_blocks: usize,
+    pub allocations: usize,
+    /// the combined size of every memory object
+    pub reserved: vk::DeviceSize,
+    /// the combined size of every allocation
+    pub used: vk::DeviceSize,
+}
+
+impl fmt::Display for HeapStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const MIB: f64 = 1024.0 * 1024.0;
+
+        write!(
+            f,
+            "heap {}: {} allocations using {:.1} of {:.1} MiB in {} blocks ({} dedicated), heap size {:.0} MiB",
+            self.heap_index,
+            self.allocations,
+            self.used as f64 / MIB,
+            self.reserved as f64 / MIB,
+            self.blocks,
+            self.dedicated_blocks,
+            self.heap_size as f64 / MIB
+        )
+    }
+}
+
+/// carves buffers and images out of large memory blocks per memory type, so
+/// the number of memory objects stays far below `maxMemoryAllocationCount`
+///
+/// Clones share the same blocks, so the allocator can be used through a
+/// shared reference to the app data. Host visible blocks stay mapped for as
+/// long as they live.
+#[derive(Clone, Debug, Default)]
+pub struct Allocator {
+    blocks: Arc<Mutex<Vec<Option<MemoryBlock>>>>,
+    next_block_id: Arc<AtomicU64>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct UsedRange {
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+    /// buffers and linear images, which may not share a
+    /// `bufferImageGranularity` page with optimal images
+    linear: bool,
+}
+
+#[derive(Debug)]
+struct MemoryBlock {
+    id: u64,
+    memory: vk::DeviceMemory,
+    memory_type: u32,
+    heap_index: u32,
+    dedicated: bool,
+    mapped: *mut u8,
+    ranges: BlockRanges,
+}
+
+// the mapped pointer is only handed out through the allocator, which guards
+// the blocks with a mutex
+unsafe impl Send for MemoryBlock {}
+
+/// `value` rounded up to a multiple of `alignment`, an alignment of 0 is
+/// treated as 1
+pub fn align_up(value: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+    value.div_ceil(alignment.max(1)) * alignment.max(1)
+}
+
+/// whether both offsets lie on the same `bufferImageGranularity` page
+pub fn same_page(a: vk::DeviceSize, b: vk::DeviceSize, granularity: vk::DeviceSize) -> bool {
+    a / granularity.max(1) == b / granularity.max(1)
+}
+
+/// the ranges handed out of a memory block of `size` bytes
+#[derive(Clone, Debug, Default)]
+pub struct BlockRanges {
+    size: vk::DeviceSize,
+    /// the allocated ranges sorted by offset
+    ranges: Vec<UsedRange>,
+}
+
+impl BlockRanges {
+    pub fn new(size: vk::DeviceSize) -> Self {
+        Self {
+            size,
+            ranges: vec![],
+        }
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    /// the number of ranges handed out
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// the combined size of every range handed out
+    pub fn used(&self) -> vk::DeviceSize {
+        self.ranges.iter().map(|r| r.size).sum()
+    }
+
+    /// the index the range would be inserted at and its offset, for the
+    /// first gap the requirements fit into
+    ///
+    /// `linear` ranges are buffers and linear images, which may not share a
+    /// `bufferImageGranularity` page with optimal images.
+    pub fn find(
+        &self,
+        requirements: vk::MemoryRequirements,
+        linear: bool,
+        granularity: vk::DeviceSize,
+    ) -> Option<(usize, vk::DeviceSize)> {
+        for index in 0..=self.ranges.len() {
+            let previous = index.checked_sub(1).map(|i| self.ranges[i]);
+            let next = self.ranges.get(index).copied();
+
+            let start = previous.map_or(0, |p| p.offset + p.size);
+            let mut offset = align_up(start, requirements.alignment);
+            if let Some(previous) = previous
+                && previous.linear != linear
+                && same_page(previous.offset + previous.size - 1, offset, granularity)
+            {
+                offset = align_up(offset, granularity);
+            }
+
+            let end = offset + requirements.size;
+            if end > next.map_or(self.size, |n| n.offset) {
+                continue;
+            }
+
+            if let Some(next) = next
+                && next.linear != linear
+                && same_page(end - 1, next.offset, granularity)
+            {
+                continue;
+            }
+
+            return Some((index, offset));
+        }
+
+        None
+    }
+
+    /// hands out a range in the first gap the requirements fit into,
+    /// returning its offset
+    pub fn allocate(
+        &mut self,
+        requirements: vk::MemoryRequirements,
+        linear: bool,
+        granularity: vk::DeviceSize,
+    ) -> Option<vk::DeviceSize> {
+        let (index, offset) = self.find(requirements, linear, granularity)?;
+        self.ranges.insert(
+            index,
+            UsedRange {
+                offset,
+                size: requirements.size,
+                linear,
+            },
+        );
+
+        Some(offset)
+    }
+
+    /// returns the range at `offset` of `size` bytes, false when no such
+    /// range was handed out
+    pub fn free(&mut self, offset: vk::DeviceSize, size: vk::DeviceSize) -> bool {
+        match self
+            .ranges
+            .iter()
+            .position(|r| r.offset == offset && r.size == size)
+        {
+            Some(index) => {
+                self.ranges.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Allocator {
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// A pointer to the start of the allocation, which must be host visible.
+    pub unsafe fn mapped(&self, allocation: Allocation) -> Result<*mut u8> {
+        let blocks = self.blocks.lock().unwrap();
+        let block = blocks
+            .get(allocation.block)
+            .and_then(|b| b.as_ref())
+            .filter(|b| b.id == allocation.block_id)
+            .ok_or_else(|| anyhow!("Allocation was already freed"))?;
+
+        if block.mapped.is_null() {
+            return Err(anyhow!("Allocation is not host visible"));
+        }
+
+        Ok(unsafe { block.mapped.add(allocation.offset as usize) })
+    }
+
+    /// every memory object with a readable name, for debug tooling
+    pub fn memory_names(&self) -> Vec<(vk::DeviceMemory, String)> {
+        self.blocks
+            .lock()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .filter_map(|(i, b)| b.as_ref().map(|b| (i, b)))
+            .map(|(i, b)| {
+                let kind = if b.dedicated { "dedicated" } else { "block" };
+                (
+                    b.memory,
+                    format!("memory[{}].{}[{}]", b.memory_type, kind, i),
+                )
+            })
+            .collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[track_caller]
+    unsafe fn allocate(
+        &self,
+        instance: &Instance,
+        device: &Device,
+        data: &AppData,
+        requirements: vk::MemoryRequirements,
+        properties: vk::MemoryPropertyFlags,
+        linear: bool,
+        dedicated_image: Option<vk::Image>,
+    ) -> Result<Allocation> {
+        unsafe {
+            let memory_properties =
+                instance.get_physical_device_memory_properties(data.physical_device);
+            let granularity = instance
+                .get_physical_device_properties(data.physical_device)
+                .limits
+                .buffer_image_granularity;
+
+            let memory_type = get_memory_type_index(instance, data, properties, requirements)?;
+            let heap_index = memory_properties.memory_types[memory_type as usize].heap_index;
+            let heap_size = memory_properties.memory_heaps[heap_index as usize].size;
+            let block_size = if heap_size <= SMALL_HEAP_SIZE {
+                heap_size / 8
+            } else {
+                BLOCK_SIZE
+            };
+
+            let mut blocks = self.blocks.lock().unwrap();
+            let dedicated = dedicated_image.is_some() || requirements.size > block_size / 2;
+
+            if !dedicated {
+                for (index, block) in blocks.iter_mut().enumerate() {
+                    let Some(block) = block else { continue };
+                    if block.dedicated || block.memory_type != memory_type {
+                        continue;
+                    }
+
+                    if let Some(offset) = block.ranges.allocate(requirements, linear, granularity) {
+                        return Ok(Allocation {
+                            memory: block.memory,
+                            offset,
+                            size: requirements.size,
+                            block: index,
+                            block_id: block.id,
+                        });
+                    }
+                }
+            }
+
+            let size = if dedicated {
+                requirements.size
+            } else {
+                block_size
+            };
+
+            let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::builder();
+            let mut info = vk::MemoryAllocateInfo::builder()
+                .allocation_size(size)
+                .memory_type_index(memory_type);
+
+            if let Some(image) = dedicated_image {
+                dedicated_info = dedicated_info.image(image);
+                info = info.push_next(&mut dedicated_info);
+            }
+
+            let memory = device.allocate_memory(&info, None)?;
+            data.registry.created(memory, size);
+            let host_visible = memory_properties.memory_types[memory_type as usize]
+                .property_flags
+                .contains(vk::MemoryPropertyFlags::HOST_VISIBLE);
+            let mapped = if host_visible {
+                device
+                    .map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())?
+                    .cast()
+            } else {
+                std::ptr::null_mut()
+            };
+
+            let mut ranges = BlockRanges::new(size);
+            ranges.allocate(requirements, linear, granularity);
+
+            let block = MemoryBlock {
+                id: self.next_block_id.fetch_add(1, Ordering::Relaxed),
+                memory,
+                memory_type,
+                heap_index,
+                dedicated,
+                mapped,
+                ranges,
+            };
+            let block_id = block.id;
+
+            let index = match blocks.iter().position(|b| b.is_none()) {
+                Some(index) => {
+                    blocks[index] = Some(block);
+                    index
+                }
+                None => {
+                    blocks.push(Some(block));
+                    blocks.len() - 1
+                }
+            };
+
+            Ok(Allocation {
+                memory,
+                offset: 0,
+                size: requirements.size,
+                block: index,
+                block_id,
+            })
+        }
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Allocates memory for the buffer and binds it.
+#[track_caller]
+pub unsafe fn allocate_buffer(
+    instance: &Instance,
+    device: &Device,
+    data: &AppData,
+    buffer: vk::Buffer,
+    properties: vk::MemoryPropertyFlags,
+) -> Result<Allocation> {
+    unsafe {
+        let requirements = device.get_buffer_memory_requirements(buffer);
+        let allocation = data.allocator.allocate(
+            instance,
+            device,
+            data,
+            requirements,
+            properties,
+            true,
+            None,
+        )?;
+
+        device.bind_buffer_memory(buffer, allocation.memory, allocation.offset)?;
+        Ok(allocation)
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Allocates memory for the image and binds it, images the driver prefers to
+/// have their own memory object and large ones get a dedicated allocation.
+#[track_caller]
+pub unsafe fn allocate_image(
+    instance: &Instance,
+    device: &Device,
+    data: &AppData,
+    image: vk::Image,
+    tiling: vk::ImageTiling,
+    properties: vk::MemoryPropertyFlags,
+) -> Result<Allocation> {
+    unsafe {
+        let info = vk::ImageMemoryRequirementsInfo2::builder().image(image);
+        let mut dedicated_requirements = vk::MemoryDedicatedRequirements::builder();
+        let mut requirements =
+            vk::MemoryRequirements2::builder().push_next(&mut dedicated_requirements);
+        device.get_image_memory_requirements2(&info, &mut requirements);
+
+        let requirements = requirements.memory_requirements;
+        let dedicated = dedicated_requirements.prefers_dedicated_allocation == vk::TRUE
+            || dedicated_requirements.requires_dedicated_allocation == vk::TRUE
+            || requirements.size >= DEDICATED_THRESHOLD;
+
+        let allocation = data.allocator.allocate(
+            instance,
+            device,
+            data,
+            requirements,
+            properties,
+            tiling == vk::ImageTiling::LINEAR,
+            Some(image).filter(|_| dedicated),
+        )?;
+
+        device.bind_image_memory(image, allocation.memory, allocation.offset)?;
+        Ok(allocation)
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Returns the allocation to its block, the resource bound to it must have
+/// been destroyed. Empty dedicated blocks are freed straight away, and empty
+/// shared ones once there is another empty block of the same memory type.
+///
+/// Panics when the allocation is not live, as freeing it twice or after its
+/// block was freed would otherwise release the memory of another resource.
+pub unsafe fn free_allocation(device: &Device, data: &AppData, allocation: Allocation) {
+    if allocation.memory == vk::DeviceMemory::default() {
+        return;
+    }
+
+    let mut blocks = data.allocator.blocks.lock().unwrap();
+    let block = blocks
+        .get_mut(allocation.block)
+        .and_then(|b| b.as_mut())
+        .filter(|b| b.id == allocation.block_id);
+
+    if !block.is_some_and(|b| b.ranges.free(allocation.offset, allocation.size)) {
+        panic!(
+            "Freed an allocation that is not live, of {} bytes at {} in {:?}",
+            allocation.size, allocation.offset, allocation.memory
+        );
+    }
+
+    let block = blocks[allocation.block].as_ref().unwrap();
+    if !block.ranges.is_empty() {
+        return;
+    }
+
+    let (memory_type, dedicated) = (block.memory_type, block.dedicated);
+    let spare = blocks.iter().enumerate().any(|(i, b)| {
+        b.as_ref().is_some_and(|b| {
+            i != allocation.block
+                && !b.dedicated
+                && b.memory_type == memory_type
+                && b.ranges.is_empty()
+        })
+    });
+
+    if dedicated || spare {
+        unsafe { device.free_memory(allocation.memory, None) };
+        data.registry.destroyed(allocation.memory);
+        blocks[allocation.block] = None;
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Frees every block, the resources bound to them must have been destroyed.
+pub unsafe fn destroy_allocator(device: &Device, data: &AppData) {
+    let mut blocks = data.allocator.blocks.lock().unwrap();
+
+    for block in blocks.drain(..).flatten() {
+        if !block.ranges.is_empty() {
+            log::warn!(
+                "Freeing memory block with {} allocations still in use",
+                block.ranges.len()
+            );
+        }
+
+        unsafe { device.free_memory(block.memory, None) };
+        data.registry.destroyed(block.memory);
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// The usage of every memory heap of the device.
+pub unsafe fn memory_statistics(instance: &Instance, data: &AppData) -> Vec<HeapStatistics> {
+    let memory_properties =
+        unsafe { instance.get_physical_device_memory_properties(data.physical_device) };
+    let mut statistics = memory_properties.memory_heaps
+        [..memory_properties.memory_heap_count as usize]
+        .iter()
+        .enumerate()
+        .map(|(i, h)| HeapStatistics {
+            heap_index: i as u32,
+            heap_size: h.size,
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+
+    for block in data.allocator.blocks.lock().unwrap().iter().flatten() {
+        let heap = &mut statistics[block.heap_index as usize];
+        heap.blocks += 1;
+        heap.dedicated_blocks += block.dedicated as usize;
+        heap.allocations += block.ranges.len();
+        heap.reserved += block.ranges.size();
+        heap.used += block.ranges.used();
+    }
+
+    statistics
+}
@@ -1,21 +1,39 @@
-use anyhow::Result;
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
 
 use vulkanalia::{
     Device, Instance,
-    vk::{self, DeviceV1_0, Handle, HasBuilder},
+    vk::{self, DeviceV1_0, HasBuilder},
 };
 
 use crate::{
     app::AppData,
-    pipeline::{image::get_depth_format, shader::create_shader_module, vertex::Vertex},
+    pipeline::{
+        builder::PipelineBuilder, image::get_depth_format, rendering::scene_final_state,
+        shader::ShaderSource, vertex::Vertex,
+    },
 };
 
+pub mod barriers;
+pub mod block;
 pub mod buffers;
+pub mod builder;
+pub mod cache;
+pub mod compute;
+pub mod descriptor_allocator;
 pub mod descriptors;
 pub mod image;
+pub mod memory;
+pub mod reflect;
+pub mod reload;
 pub mod render;
+pub mod rendering;
+pub mod resources;
 pub mod shader;
 pub mod texture;
+pub mod uniforms;
+pub mod upload;
 pub mod vertex;
 
 pub unsafe fn create_render_pass(
@@ -65,7 +83,7 @@ pub unsafe fn create_render_pass(
         .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
         .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
         .initial_layout(vk::ImageLayout::UNDEFINED)
-        .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+        .final_layout(scene_final_state(data).layout);
 
     let color_resolve_attachment_ref = vk::AttachmentReference::builder()
         .attachment(2)
@@ -109,144 +127,80 @@ pub unsafe fn create_render_pass(
         .dependencies(dependencies);
 
     data.render_pass = device.create_render_pass(&info, None)?;
+    data.registry.created(data.render_pass, 0);
 
     Ok(())
 }
 
+/// the SPIR-V of the scene shaders, replaced when they are hot reloaded
+#[derive(Clone, Debug)]
+pub struct SceneShaders {
+    pub vert: Vec<u8>,
+    pub frag: Vec<u8>,
+}
+
+impl Default for SceneShaders {
+    /// the shaders compiled from `shaders/` by the build script
+    fn default() -> Self {
+        Self {
+            vert: include_bytes!(concat!(env!("OUT_DIR"), "/shader.vert.spv")).to_vec(),
+            frag: include_bytes!(concat!(env!("OUT_DIR"), "/shader.frag.spv")).to_vec(),
+        }
+    }
+}
+
+/// the description of the scene pipeline drawn with the shaders
+pub fn scene_pipeline(data: &AppData, shaders: &SceneShaders) -> PipelineBuilder {
+    PipelineBuilder::new()
+        .shader(vk::ShaderStageFlags::VERTEX, &shaders.vert)
+        .shader(vk::ShaderStageFlags::FRAGMENT, &shaders.frag)
+        .vertex_input(
+            &[Vertex::binding_description()],
+            &Vertex::attribute_descriptions(),
+        )
+        .samples(data.msaa_samples)
+        .set_layout(data.descriptor_set_layout)
+        .formats(&[data.swapchain_format], data.depth_format)
+        .render_pass(data.render_pass, 0)
+}
+
 pub unsafe fn create_pipeline(device: &Device, data: &mut AppData) -> Result<()> {
-    let vert = include_bytes!("../../shaders/vert.spv");
-    let frag = include_bytes!("../../shaders/frag.spv");
-
-    let vert_shader_module = create_shader_module(device, &vert[..])?;
-    let frag_shader_module = create_shader_module(device, &frag[..])?;
-
-    // create the stages for the shaders
-
-    let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
-        .stage(vk::ShaderStageFlags::VERTEX)
-        .module(vert_shader_module)
-        .name(b"main\0");
-
-    let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
-        .stage(vk::ShaderStageFlags::FRAGMENT)
-        .module(frag_shader_module)
-        .name(b"main\0");
-
-    // setup shader data input
-
-    let binding_descriptions = &[Vertex::binding_description()];
-    let attribute_descriptions = Vertex::attribute_descriptions();
-    let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
-        .vertex_binding_descriptions(binding_descriptions)
-        .vertex_attribute_descriptions(&attribute_descriptions);
-
-    let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
-        .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
-        .primitive_restart_enable(false);
-
-    // setup viewport state
-
-    let viewport = vk::Viewport::builder()
-        .x(0.0)
-        .y(0.0)
-        .width(data.swapchain_extent.width as f32)
-        .height(data.swapchain_extent.height as f32)
-        .min_depth(0.0)
-        .max_depth(1.0);
-
-    let scissor = vk::Rect2D::builder()
-        .offset(vk::Offset2D { x: 0, y: 0 })
-        .extent(data.swapchain_extent);
-
-    let viewports = &[viewport];
-    let scissors = &[scissor];
-    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
-        .viewports(viewports)
-        .scissors(scissors);
-
-    // setup rasterizer
-
-    let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
-        .depth_clamp_enable(false)
-        .rasterizer_discard_enable(false)
-        .polygon_mode(vk::PolygonMode::FILL)
-        .line_width(1.0)
-        .cull_mode(vk::CullModeFlags::BACK)
-        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
-        .depth_bias_enable(false);
-
-    let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
-        .sample_shading_enable(false)
-        .rasterization_samples(data.msaa_samples);
-
-    let attachment = vk::PipelineColorBlendAttachmentState::builder()
-        .color_write_mask(vk::ColorComponentFlags::all())
-        .blend_enable(true)
-        .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
-        .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
-        .color_blend_op(vk::BlendOp::ADD)
-        .src_alpha_blend_factor(vk::BlendFactor::ONE)
-        .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
-        .alpha_blend_op(vk::BlendOp::ADD);
-
-    let attachments = &[attachment];
-    let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
-        .logic_op_enable(false)
-        .logic_op(vk::LogicOp::COPY)
-        .attachments(attachments)
-        .blend_constants([0.0, 0.0, 0.0, 0.0]);
-
-    let vert_push_constant_range = vk::PushConstantRange::builder()
-        .stage_flags(vk::ShaderStageFlags::VERTEX)
-        .offset(0)
-        .size(64 /* 16 x 4 byte floats */);
-
-    let frag_push_constant_range = vk::PushConstantRange::builder()
-        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
-        .offset(64)
-        .size(4);
-
-    let set_layouts = &[data.descriptor_set_layout];
-    let push_constant_ranges = &[vert_push_constant_range, frag_push_constant_range];
-    let layout_info = vk::PipelineLayoutCreateInfo::builder()
-        .set_layouts(set_layouts)
-        .push_constant_ranges(push_constant_ranges);
-
-    data.pipeline_layout = device.create_pipeline_layout(&layout_info, None)?;
-
-    // depth stencil and stencils state state
-
-    let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
-        .depth_test_enable(true)
-        .depth_write_enable(true)
-        .depth_compare_op(vk::CompareOp::LESS)
-        .depth_bounds_test_enable(false)
-        .min_depth_bounds(0.0)
-        .max_depth_bounds(1.0)
-        .stencil_test_enable(false);
-
-    // create graphics pipeline
-
-    let stages = &[vert_stage, frag_stage];
-    let info = vk::GraphicsPipelineCreateInfo::builder()
-        .stages(stages)
-        .vertex_input_state(&vertex_input_state)
-        .input_assembly_state(&input_assembly_state)
-        .viewport_state(&viewport_state)
-        .rasterization_state(&rasterization_state)
-        .multisample_state(&multisample_state)
-        .depth_stencil_state(&depth_stencil_state)
-        .color_blend_state(&color_blend_state)
-        .layout(data.pipeline_layout)
-        .render_pass(data.render_pass)
-        .subpass(0);
-
-    data.pipeline = device
-        .create_graphics_pipelines(vk::PipelineCache::null(), &[info], None)?
-        .0[0];
-
-    // cleanup shaders before function end
-    device.destroy_shader_module(vert_shader_module, None);
-    device.destroy_shader_module(frag_shader_module, None);
+    let builder = scene_pipeline(data, &data.scene_shaders);
+
+    data.pipeline_layout = builder.create_layout(device, data)?;
+    data.pipeline = builder.build(device, data, data.pipeline_layout)?;
+
+    Ok(())
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Recompiles the scene shaders in `dir` and replaces the scene pipeline with
+/// one using them. The old pipeline is retired once the frames using it have
+/// finished. It is kept when a shader fails to compile.
+pub unsafe fn reload_pipeline(device: &Device, data: &mut AppData, dir: &Path) -> Result<()> {
+    let shaders = SceneShaders {
+        vert: ShaderSource::new(dir.join("shader.vert")).compile()?,
+        frag: ShaderSource::new(dir.join("shader.frag")).compile()?,
+    };
+
+    // the layouts stay, so the shaders have to keep reading the same
+    // descriptors and push constants
+    let interface = scene_pipeline(data, &shaders).reflect()?;
+    let current = scene_pipeline(data, &data.scene_shaders).reflect()?;
+    if (&interface.bindings, &interface.push_constant_ranges)
+        != (&current.bindings, &current.push_constant_ranges)
+    {
+        return Err(anyhow!(
+            "The shaders changed the descriptors or push constants they read, restart to apply"
+        ));
+    }
+
+    let pipeline =
+        unsafe { scene_pipeline(data, &shaders).build(device, data, data.pipeline_layout)? };
+    data.pipeline = pipeline;
+    data.scene_shaders = shaders;
+
     Ok(())
 }
@@ -0,0 +1,810 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Result, anyhow};
+use vulkanalia::vk::{self, HasBuilder};
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+// the opcodes, decorations, storage classes and execution models reflection
+// looks at, from the SPIR-V specification
+const OP_NAME: u32 = 5;
+const OP_MEMBER_NAME: u32 = 6;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_SPEC_CONSTANT_TRUE: u32 = 48;
+const OP_SPEC_CONSTANT_OP: u32 = 52;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ROW_MAJOR: u32 = 4;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+/// a member of a uniform, storage or push constant block
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockMember {
+    pub name: String,
+    pub offset: u32,
+    pub size: u32,
+}
+
+/// a descriptor a shader reads, along with every stage reading it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    /// the length of the array of descriptors, 0 for unsized arrays
+    pub count: u32,
+    pub stages: vk::ShaderStageFlags,
+    pub name: String,
+    /// the members of uniform and storage buffer blocks
+    pub members: Vec<BlockMember>,
+}
+
+/// what a vertex attribute format is read as in a shader
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NumericType {
+    /// normalized, scaled, srgb and 32 bit or smaller float formats
+    Float,
+    SInt,
+    UInt,
+    /// the 64 bit formats, which only 64 bit inputs can read
+    Float64,
+    SInt64,
+    UInt64,
+}
+
+/// the numeric type of a vertex attribute format, none for formats that are
+/// not color formats
+pub fn numeric_type(format: vk::Format) -> Option<NumericType> {
+    // the names of the formats spell out their component widths and numeric
+    // format, e.g. `R16G16_SNORM` or `A2B10G10R10_UINT_PACK32`
+    let name = format!("{:?}", format);
+    let mut parts = name.split('_');
+    let components = parts.next()?;
+    let wide = components.starts_with("R64");
+
+    let numeric = match parts.next()? {
+        "UNORM" | "SNORM" | "USCALED" | "SSCALED" | "UFLOAT" | "SRGB" => NumericType::Float,
+        "SFLOAT" if wide => NumericType::Float64,
+        "SFLOAT" => NumericType::Float,
+        "SINT" if wide => NumericType::SInt64,
+        "SINT" => NumericType::SInt,
+        "UINT" if wide => NumericType::UInt64,
+        "UINT" => NumericType::UInt,
+        _ => return None,
+    };
+
+    // depth, stencil and compressed formats are not read as vertex attributes
+    let color = components
+        .chars()
+        .all(|c| c.is_ascii_digit() || "RGBA".contains(c));
+    color.then_some(numeric)
+}
+
+/// an attribute a vertex shader reads
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VertexInput {
+    pub location: u32,
+    pub format: vk::Format,
+    pub name: String,
+}
+
+/// the interface of a single shader stage
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderReflection {
+    pub stage: vk::ShaderStageFlags,
+    pub bindings: Vec<DescriptorBinding>,
+    /// the bytes of the push constant block the stage reads, from the offset
+    /// of its first member
+    pub push_constants: Option<vk::PushConstantRange>,
+    pub push_constant_members: Vec<BlockMember>,
+    /// empty for every stage but the vertex stage
+    pub inputs: Vec<VertexInput>,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Type {
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Struct,
+    Pointer { pointee: u32 },
+}
+
+/// how a matrix member of a block is laid out, from its decorations
+#[derive(Copy, Clone, Debug, Default)]
+struct MatrixLayout {
+    stride: Option<u32>,
+    row_major: bool,
+}
+
+#[derive(Debug, Default)]
+struct Module {
+    stage: vk::ShaderStageFlags,
+    names: HashMap<u32, String>,
+    member_names: HashMap<(u32, u32), String>,
+    types: HashMap<u32, Type>,
+    struct_members: HashMap<u32, Vec<u32>>,
+    constants: HashMap<u32, u32>,
+    /// specialization constants, whose value is only known at pipeline
+    /// creation
+    spec_constants: HashSet<u32>,
+    /// the id, pointer type and storage class of every global variable
+    variables: Vec<(u32, u32, u32)>,
+    decorations: HashMap<(u32, u32), u32>,
+    /// decorations without an operand like `Block`
+    flags: HashMap<u32, Vec<u32>>,
+    member_decorations: HashMap<(u32, u32, u32), u32>,
+}
+
+fn literal_string(words: &[u32]) -> String {
+    let bytes = words
+        .iter()
+        .flat_map(|w| w.to_le_bytes())
+        .take_while(|b| *b != 0)
+        .collect::<Vec<_>>();
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn execution_model_stage(model: u32) -> vk::ShaderStageFlags {
+    match model {
+        0 => vk::ShaderStageFlags::VERTEX,
+        1 => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+        2 => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+        3 => vk::ShaderStageFlags::GEOMETRY,
+        4 => vk::ShaderStageFlags::FRAGMENT,
+        5 => vk::ShaderStageFlags::COMPUTE,
+        _ => vk::ShaderStageFlags::empty(),
+    }
+}
+
+impl Module {
+    fn parse(bytecode: &[u8]) -> Result<Self> {
+        if !bytecode.len().is_multiple_of(4) || bytecode.len() < 20 {
+            return Err(anyhow!("SPIR-V is {} bytes long", bytecode.len()));
+        }
+
+        let mut words = bytecode
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        if words[0] == SPIRV_MAGIC.swap_bytes() {
+            words.iter_mut().for_each(|w| *w = w.swap_bytes());
+        } else if words[0] != SPIRV_MAGIC {
+            return Err(anyhow!("Not SPIR-V, the magic number is {:#x}", words[0]));
+        }
+
+        let mut module = Self::default();
+        let mut rest = &words[5..];
+        while let Some(&first) = rest.first() {
+            let (count, opcode) = ((first >> 16) as usize, first & 0xffff);
+            if count == 0 || count > rest.len() {
+                return Err(anyhow!("Truncated SPIR-V instruction {}", opcode));
+            }
+
+            module.instruction(opcode, &rest[1..count]);
+            rest = &rest[count..];
+        }
+
+        Ok(module)
+    }
+
+    fn instruction(&mut self, opcode: u32, operands: &[u32]) {
+        let operand = |i: usize| operands.get(i).copied().unwrap_or(0);
+        let result = operand(0);
+
+        let ty = match opcode {
+            OP_NAME => {
+                self.names
+                    .insert(result, literal_string(operands.get(1..).unwrap_or(&[])));
+                return;
+            }
+            OP_MEMBER_NAME => {
+                let name = literal_string(operands.get(2..).unwrap_or(&[]));
+                self.member_names.insert((result, operand(1)), name);
+                return;
+            }
+            OP_ENTRY_POINT => {
+                self.stage |= execution_model_stage(result);
+                return;
+            }
+            OP_DECORATE => {
+                match operands.get(2) {
+                    Some(value) => self.decorations.insert((result, operand(1)), *value),
+                    None => {
+                        self.flags.entry(result).or_default().push(operand(1));
+                        None
+                    }
+                };
+                return;
+            }
+            OP_MEMBER_DECORATE => {
+                let key = (result, operand(1), operand(2));
+                self.member_decorations.insert(key, operand(3));
+                return;
+            }
+            OP_CONSTANT => {
+                self.constants.insert(operand(1), operand(2));
+                return;
+            }
+            OP_SPEC_CONSTANT_TRUE..=OP_SPEC_CONSTANT_OP => {
+                self.spec_constants.insert(operand(1));
+                return;
+            }
+            OP_VARIABLE => {
+                self.variables.push((operand(1), result, operand(2)));
+                return;
+            }
+            OP_TYPE_STRUCT => {
+                self.struct_members
+                    .insert(result, operands.get(1..).unwrap_or(&[]).to_vec());
+                Type::Struct
+            }
+            OP_TYPE_BOOL => Type::Bool,
+            OP_TYPE_INT => Type::Int {
+                width: operand(1),
+                signed: operand(2) == 1,
+            },
+            OP_TYPE_FLOAT => Type::Float { width: operand(1) },
+            OP_TYPE_VECTOR => Type::Vector {
+                component: operand(1),
+                count: operand(2),
+            },
+            OP_TYPE_MATRIX => Type::Matrix {
+                column: operand(1),
+                count: operand(2),
+            },
+            OP_TYPE_IMAGE => Type::Image {
+                dim: operand(2),
+                sampled: operand(6),
+            },
+            OP_TYPE_SAMPLER => Type::Sampler,
+            OP_TYPE_SAMPLED_IMAGE => Type::SampledImage,
+            OP_TYPE_ARRAY => Type::Array {
+                element: operand(1),
+                length: operand(2),
+            },
+            OP_TYPE_RUNTIME_ARRAY => Type::RuntimeArray {
+                element: operand(1),
+            },
+            OP_TYPE_POINTER => Type::Pointer {
+                pointee: operand(2),
+            },
+            _ => return,
+        };
+
+        self.types.insert(result, ty);
+    }
+
+    fn name(&self, id: u32) -> String {
+        self.names.get(&id).cloned().unwrap_or_default()
+    }
+
+    fn has_flag(&self, id: u32, decoration: u32) -> bool {
+        self.flags.get(&id).is_some_and(|f| f.contains(&decoration))
+    }
+
+    /// the value of the constant an array length refers to
+    fn array_length(&self, length: u32) -> Result<u32> {
+        if let Some(value) = self.constants.get(&length) {
+            return Ok(*value);
+        }
+
+        if self.spec_constants.contains(&length) {
+            Err(anyhow!(
+                "Array lengths set by specialization constants are not supported"
+            ))
+        } else {
+            Err(anyhow!("Array length {} is not a constant", length))
+        }
+    }
+
+    /// the size of a type in a buffer block, laid out by its offset and
+    /// stride decorations
+    fn size_of(&self, id: u32, matrix: MatrixLayout) -> Result<u32> {
+        let size = match self.types.get(&id) {
+            Some(Type::Bool) => Some(4),
+            Some(Type::Int { width, .. } | Type::Float { width }) => Some(width / 8),
+            Some(Type::Vector { component, count }) => self
+                .size_of(*component, MatrixLayout::default())?
+                .checked_mul(*count),
+            Some(Type::Matrix { column, count }) => {
+                // a row major matrix is stored as one vector per row, each
+                // as long as the matrix has columns
+                let (stride, vectors) = match self.types.get(column) {
+                    Some(Type::Vector {
+                        component,
+                        count: rows,
+                    }) if matrix.row_major => {
+                        let row = self
+                            .size_of(*component, MatrixLayout::default())?
+                            .checked_mul(*count);
+                        (matrix.stride.or(row), *rows)
+                    }
+                    _ => (
+                        matrix
+                            .stride
+                            .or(Some(self.size_of(*column, MatrixLayout::default())?)),
+                        *count,
+                    ),
+                };
+                stride.and_then(|s| s.checked_mul(vectors))
+            }
+            Some(Type::Array { element, length }) => {
+                let stride = match self.decorations.get(&(id, DECORATION_ARRAY_STRIDE)) {
+                    Some(stride) => *stride,
+                    None => self.size_of(*element, matrix)?,
+                };
+                stride.checked_mul(self.array_length(*length)?)
+            }
+            Some(Type::Struct) => Some(self.struct_range(id)?.1),
+            _ => Some(0),
+        };
+
+        size.ok_or_else(|| anyhow!("Type {} is too large", id))
+    }
+
+    /// the members of a struct laid out as a block
+    fn members(&self, id: u32) -> Result<Vec<BlockMember>> {
+        let members = self
+            .struct_members
+            .get(&id)
+            .map(Vec::as_slice)
+            .unwrap_or(&[]);
+
+        members
+            .iter()
+            .enumerate()
+            .map(|(i, member)| {
+                let i = i as u32;
+                let matrix = MatrixLayout {
+                    stride: self
+                        .member_decorations
+                        .get(&(id, i, DECORATION_MATRIX_STRIDE))
+                        .copied(),
+                    row_major: self
+                        .member_decorations
+                        .contains_key(&(id, i, DECORATION_ROW_MAJOR)),
+                };
+
+                Ok(BlockMember {
+                    name: self.member_names.get(&(id, i)).cloned().unwrap_or_default(),
+                    offset: self
+                        .member_decorations
+                        .get(&(id, i, DECORATION_OFFSET))
+                        .copied()
+                        .unwrap_or(0),
+                    size: self.size_of(*member, matrix)?,
+                })
+            })
+            .collect()
+    }
+
+    /// the offset of the first member of a struct and the end of its last
+    fn struct_range(&self, id: u32) -> Result<(u32, u32)> {
+        Ok(self
+            .members(id)?
+            .iter()
+            .fold((u32::MAX, 0), |(start, end), m| {
+                (
+                    start.min(m.offset),
+                    end.max(m.offset.saturating_add(m.size)),
+                )
+            }))
+    }
+
+    fn descriptor_type(&self, storage: u32, id: u32) -> Option<vk::DescriptorType> {
+        let ty = self.types.get(&id)?;
+        let descriptor_type = match (storage, ty) {
+            (STORAGE_UNIFORM, Type::Struct) if self.has_flag(id, DECORATION_BUFFER_BLOCK) => {
+                vk::DescriptorType::STORAGE_BUFFER
+            }
+            (STORAGE_UNIFORM, Type::Struct) if self.has_flag(id, DECORATION_BLOCK) => {
+                vk::DescriptorType::UNIFORM_BUFFER
+            }
+            (STORAGE_STORAGE_BUFFER, _) => vk::DescriptorType::STORAGE_BUFFER,
+            (STORAGE_UNIFORM_CONSTANT, Type::SampledImage) => {
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+            }
+            (STORAGE_UNIFORM_CONSTANT, Type::Sampler) => vk::DescriptorType::SAMPLER,
+            (STORAGE_UNIFORM_CONSTANT, Type::Image { dim, sampled }) => match (*dim, *sampled) {
+                (DIM_SUBPASS_DATA, _) => vk::DescriptorType::INPUT_ATTACHMENT,
+                (DIM_BUFFER, 2) => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+                (DIM_BUFFER, _) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+                (_, 2) => vk::DescriptorType::STORAGE_IMAGE,
+                _ => vk::DescriptorType::SAMPLED_IMAGE,
+            },
+            _ => return None,
+        };
+
+        Some(descriptor_type)
+    }
+
+    /// the vertex attribute format a shader input of the type is read as
+    fn format(&self, id: u32) -> Result<vk::Format> {
+        let (component, count) = match self.types.get(&id) {
+            Some(Type::Vector { component, count }) => (*component, *count),
+            _ => (id, 1),
+        };
+
+        let formats = match self.types.get(&component) {
+            Some(Type::Float { width: 32 }) => [
+                vk::Format::R32_SFLOAT,
+                vk::Format::R32G32_SFLOAT,
+                vk::Format::R32G32B32_SFLOAT,
+                vk::Format::R32G32B32A32_SFLOAT,
+            ],
+            Some(Type::Float { width: 64 }) => [
+                vk::Format::R64_SFLOAT,
+                vk::Format::R64G64_SFLOAT,
+                vk::Format::R64G64B64_SFLOAT,
+                vk::Format::R64G64B64A64_SFLOAT,
+            ],
+            Some(Type::Int {
+                width: 32,
+                signed: true,
+            }) => [
+                vk::Format::R32_SINT,
+                vk::Format::R32G32_SINT,
+                vk::Format::R32G32B32_SINT,
+                vk::Format::R32G32B32A32_SINT,
+            ],
+            Some(Type::Int {
+                width: 32,
+                signed: false,
+            }) => [
+                vk::Format::R32_UINT,
+                vk::Format::R32G32_UINT,
+                vk::Format::R32G32B32_UINT,
+                vk::Format::R32G32B32A32_UINT,
+            ],
+            Some(Type::Matrix { .. }) => {
+                return Err(anyhow!("Matrices are not supported as vertex inputs"));
+            }
+            Some(Type::Float { width }) => {
+                return Err(anyhow!(
+                    "{} bit floats are not supported as vertex inputs",
+                    width
+                ));
+            }
+            Some(Type::Int { width, .. }) => {
+                return Err(anyhow!(
+                    "{} bit integers are not supported as vertex inputs",
+                    width
+                ));
+            }
+            _ => {
+                return Err(anyhow!(
+                    "Type {} is not supported as a vertex input",
+                    component
+                ));
+            }
+        };
+
+        (count as usize)
+            .checked_sub(1)
+            .and_then(|i| formats.get(i))
+            .copied()
+            .ok_or_else(|| anyhow!("Vectors of {} components are not valid SPIR-V", count))
+    }
+}
+
+/// reflects the descriptor bindings, push constant block and vertex inputs of
+/// a shader from its SPIR-V
+pub fn reflect_shader(bytecode: &[u8]) -> Result<ShaderReflection> {
+    let module = Module::parse(bytecode)?;
+    let mut reflection = ShaderReflection {
+        stage: module.stage,
+        bindings: vec![],
+        push_constants: None,
+        push_constant_members: vec![],
+        inputs: vec![],
+    };
+
+    for &(id, pointer, storage) in &module.variables {
+        let Some(Type::Pointer { pointee }) = module.types.get(&pointer) else {
+            continue;
+        };
+
+        match storage {
+            STORAGE_PUSH_CONSTANT => {
+                let (offset, end) = module.struct_range(*pointee)?;
+                let range = vk::PushConstantRange::builder()
+                    .stage_flags(module.stage)
+                    .offset(offset.min(end))
+                    .size(end.saturating_sub(offset))
+                    .build();
+                reflection.push_constants = Some(range);
+                reflection.push_constant_members = module.members(*pointee)?;
+            }
+            STORAGE_INPUT if module.stage == vk::ShaderStageFlags::VERTEX => {
+                if module.has_flag(id, DECORATION_BUILT_IN)
+                    || module.decorations.contains_key(&(id, DECORATION_BUILT_IN))
+                {
+                    continue;
+                }
+
+                let Some(location) = module.decorations.get(&(id, DECORATION_LOCATION)) else {
+                    continue;
+                };
+
+                let format = module
+                    .format(*pointee)
+                    .map_err(|e| anyhow!("Vertex input `{}`: {}", module.name(id), e))?;
+                reflection.inputs.push(VertexInput {
+                    location: *location,
+                    format,
+                    name: module.name(id),
+                });
+            }
+            STORAGE_UNIFORM | STORAGE_UNIFORM_CONSTANT | STORAGE_STORAGE_BUFFER => {
+                let (element, count) = match module.types.get(pointee) {
+                    Some(Type::Array { element, length }) => {
+                        (*element, module.array_length(*length)?)
+                    }
+                    Some(Type::RuntimeArray { element }) => (*element, 0),
+                    _ => (*pointee, 1),
+                };
+
+                let Some(descriptor_type) = module.descriptor_type(storage, element) else {
+                    continue;
+                };
+
+                let name = Some(module.name(id))
+                    .filter(|n| !n.is_empty())
+                    .unwrap_or_else(|| module.name(element));
+
+                reflection.bindings.push(DescriptorBinding {
+                    set: module
+                        .decorations
+                        .get(&(id, DECORATION_DESCRIPTOR_SET))
+                        .copied()
+                        .unwrap_or(0),
+                    binding: module
+                        .decorations
+                        .get(&(id, DECORATION_BINDING))
+                        .copied()
+                        .unwrap_or(0),
+                    descriptor_type,
+                    count,
+                    stages: module.stage,
+                    name,
+                    members: module.members(element)?,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    reflection.bindings.sort_by_key(|b| (b.set, b.binding));
+    reflection.inputs.sort_by_key(|i| i.location);
+
+    Ok(reflection)
+}
+
+/// the combined interface of the stages of a pipeline, which its layouts are
+/// built from
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShaderInterface {
+    pub bindings: Vec<DescriptorBinding>,
+    pub push_constant_ranges: Vec<vk::PushConstantRange>,
+    pub inputs: Vec<VertexInput>,
+}
+
+/// uniform and storage buffers bound at a dynamic offset are declared the
+/// same way in a shader
+fn compatible(shader: vk::DescriptorType, layout: vk::DescriptorType) -> bool {
+    let normalize = |t| match t {
+        vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC => vk::DescriptorType::UNIFORM_BUFFER,
+        vk::DescriptorType::STORAGE_BUFFER_DYNAMIC => vk::DescriptorType::STORAGE_BUFFER,
+        t => t,
+    };
+
+    normalize(shader) == normalize(layout)
+}
+
+impl ShaderInterface {
+    /// merges the interfaces of the stages, failing when two stages declare
+    /// the same binding differently
+    pub fn new(stages: &[ShaderReflection]) -> Result<Self> {
+        let mut interface = Self::default();
+
+        for stage in stages {
+            for binding in &stage.bindings {
+                let existing = interface
+                    .bindings
+                    .iter_mut()
+                    .find(|b| (b.set, b.binding) == (binding.set, binding.binding));
+
+                match existing {
+                    None => interface.bindings.push(binding.clone()),
+                    Some(existing)
+                        if (existing.descriptor_type, existing.count)
+                            == (binding.descriptor_type, binding.count) =>
+                    {
+                        existing.stages |= binding.stages;
+                    }
+                    Some(existing) => {
+                        return Err(anyhow!(
+                            "Set {} binding {} is `{}` {:?} in the {:?} stage but `{}` {:?} in the {:?} stage",
+                            binding.set,
+                            binding.binding,
+                            existing.name,
+                            existing.descriptor_type,
+                            existing.stages,
+                            binding.name,
+                            binding.descriptor_type,
+                            binding.stages
+                        ));
+                    }
+                }
+            }
+
+            interface.push_constant_ranges.extend(stage.push_constants);
+            interface.inputs.extend(stage.inputs.iter().cloned());
+        }
+
+        interface.bindings.sort_by_key(|b| (b.set, b.binding));
+
+        Ok(interface)
+    }
+
+    /// the layout bindings of a descriptor set
+    pub fn set_layout_bindings(&self, set: u32) -> Vec<vk::DescriptorSetLayoutBinding> {
+        self.bindings
+            .iter()
+            .filter(|b| b.set == set)
+            .map(|b| {
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(b.binding)
+                    .descriptor_type(b.descriptor_type)
+                    .descriptor_count(b.count)
+                    .stage_flags(b.stages)
+                    .build()
+            })
+            .collect()
+    }
+
+    /// fails when a shader reads a vertex attribute the vertex format does not
+    /// provide, or provides as another numeric type. The component counts may
+    /// differ, missing components are filled in and extra ones ignored.
+    pub fn check_vertex_input(
+        &self,
+        attributes: &[vk::VertexInputAttributeDescription],
+    ) -> Result<()> {
+        for input in &self.inputs {
+            match attributes.iter().find(|a| a.location == input.location) {
+                None => {
+                    return Err(anyhow!(
+                        "The vertex shader reads `{}` at location {} as {:?}, which the vertex format does not provide",
+                        input.name,
+                        input.location,
+                        input.format
+                    ));
+                }
+                Some(attribute)
+                    if numeric_type(attribute.format).is_none()
+                        || numeric_type(attribute.format) != numeric_type(input.format) =>
+                {
+                    return Err(anyhow!(
+                        "The vertex shader reads `{}` at location {} as {:?}, but the vertex format provides {:?}",
+                        input.name,
+                        input.location,
+                        input.format,
+                        attribute.format
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// fails when a shader reads a binding of the set the layout does not
+    /// have, or has with a different type or for other stages
+    pub fn check_set_layout(
+        &self,
+        set: u32,
+        bindings: &[vk::DescriptorSetLayoutBinding],
+    ) -> Result<()> {
+        for binding in self.bindings.iter().filter(|b| b.set == set) {
+            let layout = bindings
+                .iter()
+                .find(|b| b.binding == binding.binding)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "The shaders read `{}` at set {} binding {}, which is not bound",
+                        binding.name,
+                        set,
+                        binding.binding
+                    )
+                })?;
+
+            if !compatible(binding.descriptor_type, layout.descriptor_type) {
+                return Err(anyhow!(
+                    "The shaders read `{}` at set {} binding {} as {:?}, but {:?} is bound",
+                    binding.name,
+                    set,
+                    binding.binding,
+                    binding.descriptor_type,
+                    layout.descriptor_type
+                ));
+            }
+
+            if !layout.stage_flags.contains(binding.stages) {
+                return Err(anyhow!(
+                    "The {:?} stages read `{}` at set {} binding {}, but it is only visible to {:?}",
+                    binding.stages,
+                    binding.name,
+                    set,
+                    binding.binding,
+                    layout.stage_flags
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// fails when a stage reads push constants outside the ranges of its stage
+    pub fn check_push_constants(&self, ranges: &[vk::PushConstantRange]) -> Result<()> {
+        for used in &self.push_constant_ranges {
+            let covered = ranges.iter().any(|r| {
+                r.stage_flags.contains(used.stage_flags)
+                    && r.offset <= used.offset
+                    && used.offset + used.size <= r.offset + r.size
+            });
+
+            if !covered {
+                return Err(anyhow!(
+                    "The {:?} stage reads push constants {}..{}, which no push constant range of the stage covers",
+                    used.stage_flags,
+                    used.offset,
+                    used.offset + used.size
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// the environment variable turning shader hot reloading on with `1`, or off
+/// with `0`
+pub const HOT_RELOAD_ENV: &str = "CHOAM_HOT_RELOAD";
+
+/// how often the shader directory is checked for changes
+pub const HOT_RELOAD_INTERVAL: Duration = Duration::from_millis(250);
+
+/// the extensions of the files the scene shaders are compiled from, `.glsl`
+/// being the files they include
+pub const SOURCE_EXTENSIONS: &[&str] = &["vert", "frag", "glsl"];
+
+/// watches a shader directory for changed sources by polling their
+/// modification times, so shaders can be recompiled while the app runs
+///
+/// Other files, such as the SPIR-V written next to the sources, are ignored.
+#[derive(Clone, Debug)]
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    interval: Duration,
+    last_poll: Option<Instant>,
+    modified: HashMap<PathBuf, SystemTime>,
+}
+
+fn modified_times(dir: &Path) -> HashMap<PathBuf, SystemTime> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return HashMap::new();
+    };
+
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.path()
+                .extension()
+                .is_some_and(|x| SOURCE_EXTENSIONS.iter().any(|s| x == *s))
+        })
+        .filter_map(|e| {
+            let metadata = e.metadata().ok().filter(|m| m.is_file())?;
+            Some((e.path(), metadata.modified().ok()?))
+        })
+        .collect()
+}
+
+impl ShaderWatcher {
+    /// watches the sources currently in the directory, checking it at most
+    /// once per interval
+    pub fn new(dir: impl Into<PathBuf>, interval: Duration) -> Self {
+        let dir = dir.into();
+
+        Self {
+            modified: modified_times(&dir),
+            dir,
+            interval,
+            last_poll: None,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// the sources that were added or modified since the last call, empty
+    /// when the interval has not passed yet
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        if self
+            .last_poll
+            .is_some_and(|last| now.duration_since(last) < self.interval)
+        {
+            return vec![];
+        }
+        self.last_poll = Some(now);
+
+        let modified = modified_times(&self.dir);
+        let mut changed = modified
+            .iter()
+            .filter(|(path, time)| self.modified.get(*path) != Some(*time))
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        changed.sort();
+
+        self.modified = modified;
+        changed
+    }
+}
@@ -1,30 +1,152 @@
 use anyhow::Result;
 use vulkanalia::{
     Device,
-    vk::{self, DeviceV1_0, Handle, HasBuilder},
+    vk::{self, DeviceV1_0, DeviceV1_2, Handle, HasBuilder},
 };
 
 use crate::app::{AppData, MAX_FRAMES_IN_FLIGHT};
 
+/// counts the submissions made to the graphics queue with a timeline
+/// semaphore, every submission signals the next value so reaching a value
+/// means that submission and every one before it has finished
+#[derive(Copy, Clone, Debug, Default)]
+pub struct GraphicsTimeline {
+    pub semaphore: vk::Semaphore,
+    submitted: u64,
+}
+
+impl GraphicsTimeline {
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    pub unsafe fn create(device: &Device) -> Result<Self> {
+        let mut type_info = vk::SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(0);
+        let info = vk::SemaphoreCreateInfo::builder().push_next(&mut type_info);
+
+        Ok(Self {
+            semaphore: unsafe { device.create_semaphore(&info, None)? },
+            submitted: 0,
+        })
+    }
+
+    /// the value signalled by the last submission
+    pub fn submitted(&self) -> u64 {
+        self.submitted
+    }
+
+    /// the value the next submission signals, only counted as submitted
+    /// once the submission succeeded, as nothing would ever signal it
+    /// otherwise
+    fn next(&self) -> u64 {
+        self.submitted + 1
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// The value of the last finished submission.
+    pub unsafe fn completed(&self, device: &Device) -> Result<u64> {
+        Ok(unsafe { device.get_semaphore_counter_value(self.semaphore)? })
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    pub unsafe fn is_reached(&self, device: &Device, value: u64) -> Result<bool> {
+        Ok(unsafe { self.completed(device)? } >= value)
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Blocks until the submission that signals the value has finished,
+    /// returning straight away for 0 which no submission signals.
+    pub unsafe fn wait(&self, device: &Device, value: u64) -> Result<()> {
+        if value == 0 {
+            return Ok(());
+        }
+
+        let semaphores = &[self.semaphore];
+        let values = &[value];
+        let info = vk::SemaphoreWaitInfo::builder()
+            .semaphores(semaphores)
+            .values(values);
+
+        unsafe { device.wait_semaphores(&info, u64::MAX)? };
+        Ok(())
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    pub unsafe fn destroy(&self, device: &Device) {
+        unsafe { device.destroy_semaphore(self.semaphore, None) };
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Submits the command buffers to the graphics queue, waiting on the binary
+/// semaphores and signalling the given ones along with the next timeline
+/// value, which is returned so the cpu or other queues can wait on it.
+pub unsafe fn submit_graphics(
+    device: &Device,
+    data: &mut AppData,
+    command_buffers: &[vk::CommandBuffer],
+    wait_semaphores: &[vk::Semaphore],
+    wait_stages: &[vk::PipelineStageFlags],
+    signal_semaphores: &[vk::Semaphore],
+) -> Result<u64> {
+    let value = data.timeline.next();
+
+    // binary semaphores ignore their value, but every semaphore needs one
+    let wait_values = vec![0; wait_semaphores.len()];
+    let signal_values = signal_semaphores
+        .iter()
+        .map(|_| 0)
+        .chain([value])
+        .collect::<Vec<_>>();
+    let signal_semaphores = signal_semaphores
+        .iter()
+        .copied()
+        .chain([data.timeline.semaphore])
+        .collect::<Vec<_>>();
+
+    let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::builder()
+        .wait_semaphore_values(&wait_values)
+        .signal_semaphore_values(&signal_values);
+
+    let info = vk::SubmitInfo::builder()
+        .wait_semaphores(wait_semaphores)
+        .wait_dst_stage_mask(wait_stages)
+        .command_buffers(command_buffers)
+        .signal_semaphores(&signal_semaphores)
+        .push_next(&mut timeline_info);
+
+    unsafe { device.queue_submit(data.graphics_queue, &[info], vk::Fence::null())? };
+    data.timeline.submitted = value;
+    data.deletions.submitted(value);
+
+    Ok(value)
+}
+
 pub unsafe fn create_sync_objects(device: &Device, data: &mut AppData) -> Result<()> {
     let semaphore_info = vk::SemaphoreCreateInfo::builder();
-    let fence_info = vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED);
 
     for _ in 0..MAX_FRAMES_IN_FLIGHT {
-        data.image_available_semaphore
-            .push(device.create_semaphore(&semaphore_info, None)?);
-        data.render_finished_semaphore
-            .push(device.create_semaphore(&semaphore_info, None)?);
+        let image_available = device.create_semaphore(&semaphore_info, None)?;
+        data.registry.created(image_available, 0);
+        data.image_available_semaphore.push(image_available);
 
-        data.in_flight_fences
-            .push(device.create_fence(&fence_info, None)?);
+        let render_finished = device.create_semaphore(&semaphore_info, None)?;
+        data.registry.created(render_finished, 0);
+        data.render_finished_semaphore.push(render_finished);
     }
 
-    data.images_in_flight = data
-        .swapchain_images
-        .iter()
-        .map(|_| vk::Fence::null())
-        .collect();
+    data.timeline = unsafe { GraphicsTimeline::create(device)? };
+    data.registry.created(data.timeline.semaphore, 0);
+    data.frame_values = vec![0; MAX_FRAMES_IN_FLIGHT];
+    data.images_in_flight = vec![0; data.swapchain_images.len()];
 
     Ok(())
 }
@@ -0,0 +1,313 @@
+use anyhow::Result;
+use vulkanalia::{
+    Device,
+    vk::{
+        self, DeviceV1_0, DeviceV1_3, Handle, HasBuilder,
+        KhrDynamicRenderingExtensionDeviceCommands,
+    },
+};
+
+use crate::{
+    app::AppData,
+    foundation::requirements::{DYNAMIC_RENDERING_EXTENSION, Feature},
+    pipeline::barriers::{ImageState, Subresources, record_image_barriers, transition_image},
+};
+
+/// a color attachment of a pass, optionally resolved into a single sampled
+/// image when the pass ends
+#[derive(Copy, Clone, Debug)]
+pub struct ColorAttachment {
+    pub view: vk::ImageView,
+    pub resolve_view: Option<vk::ImageView>,
+    pub load_op: vk::AttachmentLoadOp,
+    pub store_op: vk::AttachmentStoreOp,
+    pub clear: [f32; 4],
+}
+
+/// the depth attachment of a pass
+#[derive(Copy, Clone, Debug)]
+pub struct DepthAttachment {
+    pub view: vk::ImageView,
+    pub load_op: vk::AttachmentLoadOp,
+    pub store_op: vk::AttachmentStoreOp,
+    pub clear: f32,
+}
+
+/// a pass described at record time, with dynamic rendering this is all that
+/// is needed to begin it, otherwise it is mapped onto the scene render pass
+/// and the framebuffer of the current image
+#[derive(Clone, Debug)]
+pub struct PassDescription {
+    pub area: vk::Rect2D,
+    pub color_attachments: Vec<ColorAttachment>,
+    pub depth_attachment: Option<DepthAttachment>,
+    pub contents: vk::SubpassContents,
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Sets the viewport and scissor of the bound pipeline to the area, which
+/// can be a part of the attachments to render several viewports in a pass.
+pub unsafe fn set_viewport(device: &Device, command_buffer: vk::CommandBuffer, area: vk::Rect2D) {
+    let viewport = vk::Viewport::builder()
+        .x(area.offset.x as f32)
+        .y(area.offset.y as f32)
+        .width(area.extent.width as f32)
+        .height(area.extent.height as f32)
+        .min_depth(0.0)
+        .max_depth(1.0);
+
+    unsafe {
+        device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+        device.cmd_set_scissor(command_buffer, 0, &[area]);
+    }
+}
+
+/// the state the resolved scene image is left in, offscreen targets are left
+/// ready to be copied out of instead of presented
+pub fn scene_final_state(data: &AppData) -> ImageState {
+    if data.surface.is_null() {
+        ImageState::TRANSFER_SRC
+    } else {
+        ImageState::PRESENT
+    }
+}
+
+/// the pass drawing the scene into the image at `image_index`, the
+/// multisampled color image is cleared and resolved into the swapchain image
+pub fn scene_pass(data: &AppData, image_index: usize) -> PassDescription {
+    let area = vk::Rect2D::builder()
+        .offset(vk::Offset2D::default())
+        .extent(data.swapchain_extent)
+        .build();
+
+    PassDescription {
+        area,
+        color_attachments: vec![ColorAttachment {
+            view: data.color_image_view.handle(),
+            resolve_view: Some(data.swapchain_image_views[image_index].handle()),
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::STORE,
+            clear: [0.0, 0.0, 0.0, 1.0],
+        }],
+        depth_attachment: Some(DepthAttachment {
+            view: data.depth_image_view.handle(),
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::DONT_CARE,
+            clear: 1.0,
+        }),
+        contents: vk::SubpassContents::SECONDARY_COMMAND_BUFFERS,
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Begins the pass, with dynamic rendering the attachments are used as
+/// described, otherwise only their clear values are used and the scene render
+/// pass decides how they are loaded and stored.
+pub unsafe fn begin_pass(
+    device: &Device,
+    data: &AppData,
+    command_buffer: vk::CommandBuffer,
+    image_index: usize,
+    pass: &PassDescription,
+) {
+    if data.dynamic_rendering {
+        let color_attachments = pass
+            .color_attachments
+            .iter()
+            .map(|a| {
+                let mut info = vk::RenderingAttachmentInfo::builder()
+                    .image_view(a.view)
+                    .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                    .load_op(a.load_op)
+                    .store_op(a.store_op)
+                    .clear_value(vk::ClearValue {
+                        color: vk::ClearColorValue { float32: a.clear },
+                    });
+
+                if let Some(resolve_view) = a.resolve_view {
+                    info = info
+                        .resolve_mode(vk::ResolveModeFlags::AVERAGE)
+                        .resolve_image_view(resolve_view)
+                        .resolve_image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+                }
+
+                info
+            })
+            .collect::<Vec<_>>();
+
+        let depth_attachment = pass.depth_attachment.map(|a| {
+            vk::RenderingAttachmentInfo::builder()
+                .image_view(a.view)
+                .image_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+                .load_op(a.load_op)
+                .store_op(a.store_op)
+                .clear_value(vk::ClearValue {
+                    depth_stencil: vk::ClearDepthStencilValue {
+                        depth: a.clear,
+                        stencil: 0,
+                    },
+                })
+        });
+
+        let flags = if pass.contents == vk::SubpassContents::SECONDARY_COMMAND_BUFFERS {
+            vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS
+        } else {
+            vk::RenderingFlags::empty()
+        };
+
+        let mut info = vk::RenderingInfo::builder()
+            .flags(flags)
+            .render_area(pass.area)
+            .layer_count(1)
+            .color_attachments(&color_attachments);
+
+        if let Some(depth_attachment) = &depth_attachment {
+            info = info.depth_attachment(depth_attachment);
+        }
+
+        // devices older than 1.3 only have the commands of the extension
+        unsafe {
+            if data
+                .enabled_features
+                .has_extension(DYNAMIC_RENDERING_EXTENSION)
+            {
+                device.cmd_begin_rendering_khr(command_buffer, &info);
+            } else {
+                device.cmd_begin_rendering(command_buffer, &info);
+            }
+        }
+    } else {
+        let clear_values = pass
+            .color_attachments
+            .iter()
+            .map(|a| vk::ClearValue {
+                color: vk::ClearColorValue { float32: a.clear },
+            })
+            .chain(pass.depth_attachment.iter().map(|a| vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: a.clear,
+                    stencil: 0,
+                },
+            }))
+            .collect::<Vec<_>>();
+
+        let info = vk::RenderPassBeginInfo::builder()
+            .render_pass(data.render_pass)
+            .framebuffer(data.framebuffers[image_index])
+            .render_area(pass.area)
+            .clear_values(&clear_values);
+
+        unsafe { device.cmd_begin_render_pass(command_buffer, &info, pass.contents) };
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+pub unsafe fn end_pass(device: &Device, data: &AppData, command_buffer: vk::CommandBuffer) {
+    unsafe {
+        if data.dynamic_rendering {
+            if data
+                .enabled_features
+                .has_extension(DYNAMIC_RENDERING_EXTENSION)
+            {
+                device.cmd_end_rendering_khr(command_buffer);
+            } else {
+                device.cmd_end_rendering(command_buffer);
+            }
+        } else {
+            device.cmd_end_render_pass(command_buffer);
+        }
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Moves the scene attachments into the layouts they are rendered in, the
+/// render pass does this itself so this only records barriers with dynamic
+/// rendering. The previous contents of every attachment are discarded.
+pub unsafe fn prepare_scene_targets(
+    device: &Device,
+    data: &mut AppData,
+    command_buffer: vk::CommandBuffer,
+    image_index: usize,
+) -> Result<()> {
+    if !data.dynamic_rendering {
+        return Ok(());
+    }
+
+    let targets = [
+        (
+            data.swapchain_images[image_index],
+            ImageState::COLOR_ATTACHMENT,
+        ),
+        (data.color_image.handle(), ImageState::COLOR_ATTACHMENT),
+        (data.depth_image.handle(), ImageState::DEPTH_ATTACHMENT),
+    ];
+
+    let mut barriers = vec![];
+    for (image, state) in targets {
+        data.layouts.discard(image);
+        barriers.extend(data.layouts.transition(image, Subresources::ALL, state)?);
+    }
+
+    let synchronization2 = data.enabled_features.has(Feature::Synchronization2);
+    unsafe { record_image_barriers(device, synchronization2, command_buffer, &barriers) };
+
+    Ok(())
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Moves the resolved scene image into [`scene_final_state`] once the pass
+/// has ended. With a render pass the layouts it left the attachments in are
+/// only recorded in the tracker.
+pub unsafe fn finish_scene_targets(
+    device: &Device,
+    data: &mut AppData,
+    command_buffer: vk::CommandBuffer,
+    image_index: usize,
+) -> Result<()> {
+    let image = data.swapchain_images[image_index];
+    let final_state = scene_final_state(data);
+
+    if data.dynamic_rendering {
+        return unsafe {
+            transition_image(
+                device,
+                data,
+                command_buffer,
+                image,
+                Subresources::ALL,
+                final_state,
+            )
+        };
+    }
+
+    // the render pass wrote the attachments and then changed the layout, so
+    // the next use still has to wait on those writes
+    let resolved = ImageState::new(
+        final_state.layout,
+        vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+    );
+
+    data.layouts.assume(image, Subresources::ALL, resolved);
+    data.layouts.assume(
+        data.color_image.handle(),
+        Subresources::ALL,
+        ImageState::COLOR_ATTACHMENT,
+    );
+    data.layouts.assume(
+        data.depth_image.handle(),
+        Subresources::ALL,
+        ImageState::DEPTH_ATTACHMENT,
+    );
+
+    Ok(())
+}
@@ -0,0 +1,296 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use vulkanalia::{
+    Device,
+    vk::{self, DeviceV1_0, Handle},
+};
+
+use crate::{
+    app::AppData,
+    pipeline::memory::{Allocation, free_allocation},
+};
+
+/// a resource dropped by its owner, waiting for the gpu to stop using it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Retired {
+    Buffer(vk::Buffer, Allocation),
+    Image(vk::Image, Allocation),
+    ImageView(vk::ImageView),
+    Sampler(vk::Sampler),
+    Pipeline(vk::Pipeline),
+}
+
+#[derive(Debug, Default)]
+struct Deletions {
+    /// the timeline value of the last graphics submission
+    submitted: u64,
+    /// retired resources along with the timeline value they are destroyed at
+    retired: Vec<(u64, Retired)>,
+}
+
+/// collects the resources dropped by their owners, which are destroyed once
+/// every graphics submission that could have used them has finished
+///
+/// Clones share the same queue, every owned resource keeps one so it can
+/// retire itself when dropped.
+#[derive(Clone, Debug, Default)]
+pub struct DeletionQueue {
+    deletions: Arc<Mutex<Deletions>>,
+}
+
+impl DeletionQueue {
+    /// records the timeline value of a graphics submission, called by
+    /// [`crate::pipeline::render::submit_graphics`]
+    pub fn submitted(&self, value: u64) {
+        let mut deletions = self.deletions.lock().unwrap();
+        deletions.submitted = deletions.submitted.max(value);
+    }
+
+    /// the number of resources waiting to be destroyed
+    pub fn len(&self) -> usize {
+        self.deletions.lock().unwrap().retired.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn retire(&self, resource: Retired) {
+        let mut deletions = self.deletions.lock().unwrap();
+
+        // a resource may still be recorded into a command buffer that has not
+        // been submitted yet, so it also waits on the next submission
+        let value = deletions.submitted + 1;
+        deletions.retired.push((value, resource));
+    }
+
+    /// takes every retired resource whose last possible use has finished by
+    /// the time the timeline reached `completed`
+    pub fn take_completed(&self, completed: u64) -> Vec<Retired> {
+        self.take(|v| v <= completed)
+    }
+
+    /// takes every retired resource the predicate accepts the timeline value of
+    fn take(&self, destroy: impl Fn(u64) -> bool) -> Vec<Retired> {
+        let mut deletions = self.deletions.lock().unwrap();
+        let (ready, waiting) = std::mem::take(&mut deletions.retired)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(v, _)| destroy(*v));
+
+        deletions.retired = waiting;
+        ready.into_iter().map(|(_, r)| r).collect()
+    }
+}
+
+/// a buffer along with its memory, destroyed through the deletion queue when
+/// dropped
+#[derive(Debug, Default)]
+pub struct Buffer {
+    handle: vk::Buffer,
+    allocation: Allocation,
+    queue: DeletionQueue,
+}
+
+impl Buffer {
+    pub fn new(queue: &DeletionQueue, handle: vk::Buffer, allocation: Allocation) -> Self {
+        Self {
+            handle,
+            allocation,
+            queue: queue.clone(),
+        }
+    }
+
+    pub fn handle(&self) -> vk::Buffer {
+        self.handle
+    }
+
+    pub fn allocation(&self) -> Allocation {
+        self.allocation
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            self.queue
+                .retire(Retired::Buffer(self.handle, self.allocation));
+        }
+    }
+}
+
+/// an image along with its memory, destroyed through the deletion queue when
+/// dropped, which also stops the layout tracker from tracking it
+#[derive(Debug, Default)]
+pub struct Image {
+    handle: vk::Image,
+    allocation: Allocation,
+    queue: DeletionQueue,
+}
+
+impl Image {
+    pub fn new(queue: &DeletionQueue, handle: vk::Image, allocation: Allocation) -> Self {
+        Self {
+            handle,
+            allocation,
+            queue: queue.clone(),
+        }
+    }
+
+    pub fn handle(&self) -> vk::Image {
+        self.handle
+    }
+
+    pub fn allocation(&self) -> Allocation {
+        self.allocation
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            self.queue
+                .retire(Retired::Image(self.handle, self.allocation));
+        }
+    }
+}
+
+/// an image view destroyed through the deletion queue when dropped
+#[derive(Debug, Default)]
+pub struct ImageView {
+    handle: vk::ImageView,
+    queue: DeletionQueue,
+}
+
+impl ImageView {
+    pub fn new(queue: &DeletionQueue, handle: vk::ImageView) -> Self {
+        Self {
+            handle,
+            queue: queue.clone(),
+        }
+    }
+
+    pub fn handle(&self) -> vk::ImageView {
+        self.handle
+    }
+}
+
+impl Drop for ImageView {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            self.queue.retire(Retired::ImageView(self.handle));
+        }
+    }
+}
+
+/// a sampler destroyed through the deletion queue when dropped
+#[derive(Debug, Default)]
+pub struct Sampler {
+    handle: vk::Sampler,
+    queue: DeletionQueue,
+}
+
+impl Sampler {
+    pub fn new(queue: &DeletionQueue, handle: vk::Sampler) -> Self {
+        Self {
+            handle,
+            queue: queue.clone(),
+        }
+    }
+
+    pub fn handle(&self) -> vk::Sampler {
+        self.handle
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            self.queue.retire(Retired::Sampler(self.handle));
+        }
+    }
+}
+
+/// a pipeline destroyed through the deletion queue when dropped, its layout
+/// is owned separately
+#[derive(Debug, Default)]
+pub struct Pipeline {
+    handle: vk::Pipeline,
+    queue: DeletionQueue,
+}
+
+impl Pipeline {
+    pub fn new(queue: &DeletionQueue, handle: vk::Pipeline) -> Self {
+        Self {
+            handle,
+            queue: queue.clone(),
+        }
+    }
+
+    pub fn handle(&self) -> vk::Pipeline {
+        self.handle
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            self.queue.retire(Retired::Pipeline(self.handle));
+        }
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+unsafe fn destroy_retired(device: &Device, data: &mut AppData, resources: Vec<Retired>) {
+    for resource in resources {
+        unsafe {
+            match resource {
+                Retired::Buffer(buffer, allocation) => {
+                    device.destroy_buffer(buffer, None);
+                    data.registry.destroyed(buffer);
+                    free_allocation(device, data, allocation);
+                }
+                Retired::Image(image, allocation) => {
+                    device.destroy_image(image, None);
+                    data.registry.destroyed(image);
+                    free_allocation(device, data, allocation);
+                    data.layouts.forget(image);
+                }
+                Retired::ImageView(view) => {
+                    device.destroy_image_view(view, None);
+                    data.registry.destroyed(view);
+                }
+                Retired::Sampler(sampler) => {
+                    device.destroy_sampler(sampler, None);
+                    data.registry.destroyed(sampler);
+                }
+                Retired::Pipeline(pipeline) => {
+                    device.destroy_pipeline(pipeline, None);
+                    data.registry.destroyed(pipeline);
+                }
+            }
+        }
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Destroys every retired resource whose last possible use has finished.
+pub unsafe fn flush_deletions(device: &Device, data: &mut AppData) -> Result<()> {
+    let completed = unsafe { data.timeline.completed(device)? };
+    let resources = data.deletions.take_completed(completed);
+    unsafe { destroy_retired(device, data, resources) };
+
+    Ok(())
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Destroys every retired resource, the device must be idle.
+pub unsafe fn destroy_deletions(device: &Device, data: &mut AppData) {
+    let resources = data.deletions.take(|_| true);
+    unsafe { destroy_retired(device, data, resources) };
+}
@@ -1,15 +1,212 @@
-use anyhow::Result;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Result, anyhow};
+use thiserror::Error;
 use vulkanalia::{
     Device,
     bytecode::Bytecode,
     vk::{self, DeviceV1_0, HasBuilder},
 };
 
-pub unsafe fn create_shader_module(device: &Device, bytecode: &[u8]) -> Result<vk::ShaderModule> {
+use crate::foundation::registry::ResourceRegistry;
+
+/// the environment variable pointing at the glslc binary shaders are compiled
+/// with, shared with the build script
+pub const GLSLC_ENV: &str = "GLSLC";
+
+/// the environment variable pointing at the directory the glsl sources of the
+/// shaders are hot reloaded from
+pub const SHADER_DIR_ENV: &str = "CHOAM_SHADER_DIR";
+
+/// the directory the glsl sources are hot reloaded from when `CHOAM_SHADER_DIR`
+/// is not set, relative to the working directory
+pub const DEFAULT_SHADER_DIR: &str = "shaders";
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Creates the shader module and records it in the registry, it is destroyed
+/// with [`destroy_shader_module`].
+#[track_caller]
+pub unsafe fn create_shader_module(
+    device: &Device,
+    registry: &ResourceRegistry,
+    bytecode: &[u8],
+) -> Result<vk::ShaderModule> {
     let bytecode = Bytecode::new(bytecode).unwrap();
     let info = vk::ShaderModuleCreateInfo::builder()
         .code(bytecode.code())
         .code_size(bytecode.code_size());
 
-    Ok(device.create_shader_module(&info, None)?)
+    let module = unsafe { device.create_shader_module(&info, None)? };
+    registry.created(module, 0);
+
+    Ok(module)
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+pub unsafe fn destroy_shader_module(
+    device: &Device,
+    registry: &ResourceRegistry,
+    module: vk::ShaderModule,
+) {
+    unsafe { device.destroy_shader_module(module, None) };
+    registry.destroyed(module);
+}
+
+/// an error or warning glslc reported while compiling a shader
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    pub file: Option<PathBuf>,
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+impl fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{}: {}", file.display(), line, self.message),
+            (Some(file), None) => write!(f, "{}: {}", file.display(), self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// the diagnostics in the output of glslc, which reports them as
+/// `file:line: error: message`
+pub fn parse_diagnostics(output: &str) -> Vec<ShaderDiagnostic> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (location, message) = [": error: ", ": warning: "].iter().find_map(|s| {
+                line.split_once(s)
+                    .map(|(l, m)| (l, format!("{}{}", &s[2..], m)))
+            })?;
+
+            let (file, line) = match location.rsplit_once(':') {
+                Some((file, line)) if line.parse::<u32>().is_ok() => (file, line.parse().ok()),
+                _ => (location, None),
+            };
+
+            Some(ShaderDiagnostic {
+                file: Some(file).filter(|f| *f != "glslc").map(PathBuf::from),
+                line,
+                message,
+            })
+        })
+        .collect()
+}
+
+/// the diagnostics of a shader glslc failed to compile
+#[derive(Debug, Error)]
+pub struct ShaderCompileError {
+    pub path: PathBuf,
+    pub diagnostics: Vec<ShaderDiagnostic>,
+}
+
+impl fmt::Display for ShaderCompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to compile `{}`", self.path.display())?;
+        for diagnostic in &self.diagnostics {
+            write!(f, "\n{}", diagnostic)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// a glsl shader along with the preprocessor defines of the permutation it is
+/// compiled for, `#include`s are resolved relative to its directory
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderSource {
+    pub path: PathBuf,
+    pub defines: Vec<(String, String)>,
+}
+
+impl ShaderSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            defines: vec![],
+        }
+    }
+
+    /// defines a preprocessor macro for the permutation
+    pub fn define(mut self, name: &str, value: &str) -> Self {
+        self.defines.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// the stage of the shader, from its extension
+    pub fn stage(&self) -> Option<vk::ShaderStageFlags> {
+        match self.path.extension()?.to_str()? {
+            "vert" => Some(vk::ShaderStageFlags::VERTEX),
+            "frag" => Some(vk::ShaderStageFlags::FRAGMENT),
+            "comp" => Some(vk::ShaderStageFlags::COMPUTE),
+            _ => None,
+        }
+    }
+
+    /// compiles the shader to SPIR-V with glslc, which is found on the path
+    /// unless `GLSLC` points at it
+    ///
+    /// glslc runs as a subprocess instead of compiling in-process. The glsl
+    /// frontend of naga has no combined image samplers, which the scene
+    /// shaders use, and shaderc builds glslang from source with cmake and a
+    /// C++ toolchain. glslc ships with the Vulkan SDK the build script
+    /// already compiles the shaders with.
+    pub fn compile(&self) -> Result<Vec<u8>> {
+        let glslc = std::env::var_os(GLSLC_ENV).unwrap_or_else(|| "glslc".into());
+        self.compile_with(glslc)
+    }
+
+    /// compiles the shader to SPIR-V with the glslc binary at `glslc`
+    pub fn compile_with(&self, glslc: impl AsRef<OsStr>) -> Result<Vec<u8>> {
+        let include_dir = self.path.parent().unwrap_or(Path::new("."));
+
+        let mut command = Command::new(glslc.as_ref());
+        command
+            .arg("--target-env=vulkan1.2")
+            .arg("-I")
+            .arg(include_dir)
+            .args(self.defines.iter().map(|(n, v)| format!("-D{}={}", n, v)))
+            .arg("-o")
+            .arg("-")
+            .arg(&self.path);
+
+        let output = match command.output() {
+            Ok(output) => output,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                return Err(anyhow!(
+                    "glslc was not found, install the Vulkan SDK or set {}",
+                    GLSLC_ENV
+                ));
+            }
+            Err(error) => return Err(anyhow!("Failed to run glslc: {}", error)),
+        };
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let mut diagnostics = parse_diagnostics(&stderr);
+            if diagnostics.is_empty() {
+                diagnostics.push(ShaderDiagnostic {
+                    file: None,
+                    line: None,
+                    message: stderr.trim().to_string(),
+                });
+            }
+
+            return Err(anyhow!(ShaderCompileError {
+                path: self.path.clone(),
+                diagnostics,
+            }));
+        }
+
+        Ok(output.stdout)
+    }
 }
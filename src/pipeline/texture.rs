@@ -4,19 +4,30 @@ use vulkanalia::{
     vk::{self, DeviceV1_0, HasBuilder},
 };
 
-use crate::{app::AppData, pipeline::image::create_image_view};
+use crate::{
+    app::AppData,
+    foundation::requirements::Feature,
+    pipeline::{
+        image::create_image_view,
+        resources::{ImageView, Sampler},
+    },
+};
 
 /// # Safety
 /// This is a vulkan using function and thus is unsafe
 pub unsafe fn create_texture_sampler(device: &Device, data: &mut AppData) -> Result<()> {
+    // anisotropic filtering is optional, fall back to plain linear filtering
+    // on devices that do not support it
+    let anisotropy = data.enabled_features.has(Feature::SamplerAnisotropy);
+
     let info = vk::SamplerCreateInfo::builder()
         .mag_filter(vk::Filter::LINEAR)
         .min_filter(vk::Filter::LINEAR)
         .address_mode_u(vk::SamplerAddressMode::REPEAT)
         .address_mode_v(vk::SamplerAddressMode::REPEAT)
         .address_mode_w(vk::SamplerAddressMode::REPEAT)
-        .anisotropy_enable(true)
-        .max_anisotropy(16.0)
+        .anisotropy_enable(anisotropy)
+        .max_anisotropy(if anisotropy { 16.0 } else { 1.0 })
         .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
         .unnormalized_coordinates(false)
         .compare_enable(false)
@@ -26,22 +37,26 @@ pub unsafe fn create_texture_sampler(device: &Device, data: &mut AppData) -> Res
         .min_lod(0.0)
         .max_lod(data.mip_levels as f32);
 
-    data.texture_sampler = device.create_sampler(&info, None)?;
+    let sampler = device.create_sampler(&info, None)?;
+    data.registry.created(sampler, 0);
+    data.texture_sampler = Sampler::new(&data.deletions, sampler);
     Ok(())
 }
 
 /// # Safety
 /// This is a vulkan using function and thus is unsafe
 pub unsafe fn create_texture_image_view(device: &Device, data: &mut AppData) -> Result<()> {
-    data.texture_image_view = unsafe {
+    let view = unsafe {
         create_image_view(
             device,
-            data.texture_image,
+            data,
+            data.texture_image.handle(),
             vk::Format::R8G8B8A8_SRGB,
             vk::ImageAspectFlags::COLOR,
             data.mip_levels,
         )?
     };
+    data.texture_image_view = ImageView::new(&data.deletions, view);
 
     Ok(())
 }
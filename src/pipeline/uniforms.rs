@@ -0,0 +1,156 @@
+use std::ptr::NonNull;
+use std::ptr::copy_nonoverlapping as memcpy;
+
+use anyhow::{Result, anyhow};
+use vulkanalia::{
+    Device, Instance,
+    vk::{self, InstanceV1_0},
+};
+
+use crate::{
+    app::{AppData, MAX_FRAMES_IN_FLIGHT},
+    pipeline::{
+        block::{Block, BlockLayout},
+        memory::align_up,
+        resources::Buffer,
+        vertex::create_buffer,
+    },
+};
+
+/// the bytes of uniform data every frame in flight can hand out
+pub const UNIFORM_FRAME_SIZE: vk::DeviceSize = 64 * 1024;
+
+/// a uniform buffer split into one region per frame in flight, which stays
+/// mapped and hands out aligned sub-allocations bound through dynamic offsets
+///
+/// A region is only written after the frame that last used it has finished,
+/// so every object can have its own data each frame without extra buffers.
+#[derive(Debug, Default)]
+pub struct UniformRing {
+    buffer: Buffer,
+    mapped: Option<NonNull<u8>>,
+    frames: usize,
+    frame_size: vk::DeviceSize,
+    alignment: vk::DeviceSize,
+    /// the start of the region of the current frame
+    frame_offset: vk::DeviceSize,
+    /// the bytes of the current region handed out so far
+    cursor: vk::DeviceSize,
+}
+
+impl UniformRing {
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Creates the ring with `frame_size` bytes for each frame in flight.
+    pub unsafe fn create(
+        instance: &Instance,
+        device: &Device,
+        data: &AppData,
+        frame_size: vk::DeviceSize,
+    ) -> Result<Self> {
+        unsafe {
+            let alignment = instance
+                .get_physical_device_properties(data.physical_device)
+                .limits
+                .min_uniform_buffer_offset_alignment
+                .max(1);
+            let frame_size = align_up(frame_size, alignment);
+
+            let (buffer, allocation) = create_buffer(
+                instance,
+                device,
+                data,
+                frame_size * MAX_FRAMES_IN_FLIGHT as vk::DeviceSize,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+            )?;
+            let buffer = Buffer::new(&data.deletions, buffer, allocation);
+            let mapped = NonNull::new(data.allocator.mapped(allocation)?)
+                .ok_or_else(|| anyhow!("Uniform ring memory is not mapped"))?;
+
+            Ok(Self {
+                buffer,
+                ..Self::from_mapped(mapped, frame_size, alignment, MAX_FRAMES_IN_FLIGHT)
+            })
+        }
+    }
+
+    /// # Safety
+    /// `mapped` must point to at least `frame_size * frames` bytes that stay
+    /// valid for writes for as long as the ring is used, and that nothing else
+    /// writes to in the meantime.
+    ///
+    /// A ring without a buffer of its own, handing out `frames` regions of
+    /// `frame_size` bytes of the memory at `mapped`.
+    pub unsafe fn from_mapped(
+        mapped: NonNull<u8>,
+        frame_size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+        frames: usize,
+    ) -> Self {
+        Self {
+            buffer: Buffer::default(),
+            mapped: Some(mapped),
+            frames,
+            frame_size,
+            alignment: alignment.max(1),
+            frame_offset: 0,
+            cursor: 0,
+        }
+    }
+
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer.handle()
+    }
+
+    /// starts handing out the region of the frame in flight, the frame that
+    /// last used it must have finished
+    pub fn begin_frame(&mut self, frame: usize) {
+        assert!(
+            frame < self.frames,
+            "Uniform ring has no region for frame {}",
+            frame
+        );
+        self.frame_offset = frame as vk::DeviceSize * self.frame_size;
+        self.cursor = 0;
+    }
+
+    /// writes the block with the std140 layout into the region of the current
+    /// frame, returning the dynamic offset it is bound at, which is a multiple
+    /// of the alignment the ring was created with
+    pub fn push<T: Block>(&mut self, value: &T) -> Result<u32> {
+        let mapped = self
+            .mapped
+            .ok_or_else(|| anyhow!("Uniform ring was not created"))?;
+
+        let bytes = value.to_bytes(BlockLayout::Std140);
+        let frame_end = self.frame_offset + self.frame_size;
+        let offset = align_up(self.frame_offset + self.cursor, self.alignment);
+        let end = offset
+            .checked_add(bytes.len() as vk::DeviceSize)
+            .filter(|e| *e <= frame_end)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Uniform ring is out of space, {} of {} bytes used this frame",
+                    self.cursor,
+                    self.frame_size
+                )
+            })?;
+        let dynamic_offset = u32::try_from(offset)
+            .map_err(|_| anyhow!("Uniform ring offset {} does not fit a u32", offset))?;
+
+        // the range lies within the region of the current frame, which the
+        // constructors guarantee to be valid for writes
+        unsafe {
+            memcpy(
+                bytes.as_ptr(),
+                mapped.as_ptr().add(offset as usize),
+                bytes.len(),
+            )
+        };
+        self.cursor = end - self.frame_offset;
+
+        Ok(dynamic_offset)
+    }
+}
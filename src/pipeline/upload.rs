@@ -0,0 +1,419 @@
+use anyhow::Result;
+use std::ptr::copy_nonoverlapping as memcpy;
+
+use vulkanalia::{
+    Device, Instance,
+    vk::{self, DeviceV1_0, Handle, HasBuilder},
+};
+
+use crate::{
+    app::AppData,
+    foundation::device::QueueFamilyIndices,
+    pipeline::{
+        image::copy_buffer_to_image,
+        memory::{Allocation, free_allocation},
+        render::{GraphicsTimeline, submit_graphics},
+        vertex::create_buffer,
+    },
+};
+
+/// uploads recorded into a single submission to the transfer queue, followed
+/// by a submission to the graphics queue that takes ownership of the uploaded
+/// resources, so asset loading never blocks the cpu on a copy
+#[derive(Clone, Debug)]
+pub struct UploadBatch {
+    transfer_command_buffer: vk::CommandBuffer,
+    graphics_command_buffer: vk::CommandBuffer,
+    transfer_family: u32,
+    graphics_family: u32,
+    staging_buffers: Vec<(vk::Buffer, Allocation)>,
+}
+
+/// a submitted upload batch, the staging buffers and command buffers are kept
+/// alive until the graphics timeline reaches its value
+#[derive(Clone, Debug, Default)]
+pub struct PendingUpload {
+    transfer_command_buffer: vk::CommandBuffer,
+    graphics_command_buffer: vk::CommandBuffer,
+    semaphore: vk::Semaphore,
+    value: u64,
+    staging_buffers: Vec<(vk::Buffer, Allocation)>,
+}
+
+impl UploadBatch {
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    pub unsafe fn begin(instance: &Instance, device: &Device, data: &AppData) -> Result<Self> {
+        unsafe {
+            let indices = QueueFamilyIndices::get(instance, data, data.physical_device)?;
+
+            let allocate = |command_pool| {
+                let info = vk::CommandBufferAllocateInfo::builder()
+                    .level(vk::CommandBufferLevel::PRIMARY)
+                    .command_pool(command_pool)
+                    .command_buffer_count(1);
+
+                let command_buffer = device.allocate_command_buffers(&info)?[0];
+                data.registry.created(command_buffer, 0);
+                let info = vk::CommandBufferBeginInfo::builder()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+                device.begin_command_buffer(command_buffer, &info)?;
+
+                Ok::<_, anyhow::Error>(command_buffer)
+            };
+
+            Ok(Self {
+                transfer_command_buffer: allocate(data.transfer_command_pool)?,
+                graphics_command_buffer: allocate(data.command_pool)?,
+                transfer_family: indices.transfer,
+                graphics_family: indices.graphics,
+                staging_buffers: vec![],
+            })
+        }
+    }
+
+    /// the command buffer run on the graphics queue after the uploaded
+    /// resources were acquired, for work the transfer queue can't do such as
+    /// blitting mip levels
+    pub fn graphics_command_buffer(&self) -> vk::CommandBuffer {
+        self.graphics_command_buffer
+    }
+
+    /// whether the uploaded resources have to change queue family ownership
+    fn ownership_transfer(&self) -> bool {
+        self.transfer_family != self.graphics_family
+    }
+
+    /// the queue families of a release and acquire barrier pair, ignored when
+    /// both queues are from the same family
+    fn queue_families(&self) -> (u32, u32) {
+        if self.ownership_transfer() {
+            (self.transfer_family, self.graphics_family)
+        } else {
+            (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED)
+        }
+    }
+
+    /// the access the acquire barrier waits on, the semaphore already makes
+    /// the transfer writes available when ownership is transferred
+    fn acquire_src_access(&self) -> vk::AccessFlags {
+        if self.ownership_transfer() {
+            vk::AccessFlags::empty()
+        } else {
+            vk::AccessFlags::TRANSFER_WRITE
+        }
+    }
+
+    unsafe fn stage<T: Copy>(
+        &mut self,
+        instance: &Instance,
+        device: &Device,
+        data: &AppData,
+        values: &[T],
+    ) -> Result<vk::Buffer> {
+        unsafe {
+            let size = size_of_val(values) as u64;
+            let (staging_buffer, staging_buffer_memory) = create_buffer(
+                instance,
+                device,
+                data,
+                size,
+                vk::BufferUsageFlags::TRANSFER_SRC,
+                vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+            )?;
+            self.staging_buffers
+                .push((staging_buffer, staging_buffer_memory));
+
+            let memory = data.allocator.mapped(staging_buffer_memory)?;
+            memcpy(values.as_ptr(), memory.cast(), values.len());
+
+            Ok(staging_buffer)
+        }
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Copies the values into the start of the buffer, which is made available
+    /// to the given stages and accesses on the graphics queue.
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn upload_buffer<T: Copy>(
+        &mut self,
+        instance: &Instance,
+        device: &Device,
+        data: &AppData,
+        values: &[T],
+        buffer: vk::Buffer,
+        dst_stage_mask: vk::PipelineStageFlags,
+        dst_access_mask: vk::AccessFlags,
+    ) -> Result<()> {
+        unsafe {
+            let staging_buffer = self.stage(instance, device, data, values)?;
+
+            let region = vk::BufferCopy::builder().size(size_of_val(values) as u64);
+            device.cmd_copy_buffer(
+                self.transfer_command_buffer,
+                staging_buffer,
+                buffer,
+                &[region],
+            );
+
+            let (src_queue_family_index, dst_queue_family_index) = self.queue_families();
+            let barrier = vk::BufferMemoryBarrier::builder()
+                .src_queue_family_index(src_queue_family_index)
+                .dst_queue_family_index(dst_queue_family_index)
+                .buffer(buffer)
+                .offset(0)
+                .size(vk::WHOLE_SIZE);
+
+            if self.ownership_transfer() {
+                let release = barrier
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::empty());
+
+                device.cmd_pipeline_barrier(
+                    self.transfer_command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    vk::DependencyFlags::empty(),
+                    &[] as &[vk::MemoryBarrier],
+                    &[release],
+                    &[] as &[vk::ImageMemoryBarrier],
+                );
+            }
+
+            let acquire = barrier
+                .src_access_mask(self.acquire_src_access())
+                .dst_access_mask(dst_access_mask);
+
+            device.cmd_pipeline_barrier(
+                self.graphics_command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                dst_stage_mask,
+                vk::DependencyFlags::empty(),
+                &[] as &[vk::MemoryBarrier],
+                &[acquire],
+                &[] as &[vk::ImageMemoryBarrier],
+            );
+        }
+
+        Ok(())
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Copies the pixels into the first mip level of the color image. Every
+    /// mip level is left in `TRANSFER_DST_OPTIMAL` on the graphics queue, for
+    /// the caller to generate mipmaps or transition it to where it is used.
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn upload_image(
+        &mut self,
+        instance: &Instance,
+        device: &Device,
+        data: &AppData,
+        pixels: &[u8],
+        image: vk::Image,
+        width: u32,
+        height: u32,
+        mip_levels: u32,
+    ) -> Result<()> {
+        unsafe {
+            let staging_buffer = self.stage(instance, device, data, pixels)?;
+
+            let subresource = vk::ImageSubresourceRange::builder()
+                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                .base_mip_level(0)
+                .level_count(mip_levels)
+                .base_array_layer(0)
+                .layer_count(1);
+
+            let barrier = vk::ImageMemoryBarrier::builder()
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(subresource);
+
+            let to_transfer_dst = barrier
+                .old_layout(vk::ImageLayout::UNDEFINED)
+                .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .src_access_mask(vk::AccessFlags::empty())
+                .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE);
+
+            device.cmd_pipeline_barrier(
+                self.transfer_command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[] as &[vk::MemoryBarrier],
+                &[] as &[vk::BufferMemoryBarrier],
+                &[to_transfer_dst],
+            );
+
+            copy_buffer_to_image(
+                device,
+                self.transfer_command_buffer,
+                staging_buffer,
+                image,
+                width,
+                height,
+            );
+
+            // the layout stays the same, only the ownership changes
+
+            let (src_queue_family_index, dst_queue_family_index) = self.queue_families();
+            let barrier = barrier
+                .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .src_queue_family_index(src_queue_family_index)
+                .dst_queue_family_index(dst_queue_family_index);
+
+            if self.ownership_transfer() {
+                let release = barrier
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::empty());
+
+                device.cmd_pipeline_barrier(
+                    self.transfer_command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    vk::DependencyFlags::empty(),
+                    &[] as &[vk::MemoryBarrier],
+                    &[] as &[vk::BufferMemoryBarrier],
+                    &[release],
+                );
+            }
+
+            let acquire = barrier
+                .src_access_mask(self.acquire_src_access())
+                .dst_access_mask(vk::AccessFlags::TRANSFER_READ | vk::AccessFlags::TRANSFER_WRITE);
+
+            device.cmd_pipeline_barrier(
+                self.graphics_command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[] as &[vk::MemoryBarrier],
+                &[] as &[vk::BufferMemoryBarrier],
+                &[acquire],
+            );
+        }
+
+        Ok(())
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// Submits the copies to the transfer queue, and the acquiring command
+    /// buffer to the graphics queue waiting on them through a semaphore. Work
+    /// submitted to the graphics queue afterwards sees the uploaded resources
+    /// without the cpu having to wait on the upload.
+    pub unsafe fn submit(self, device: &Device, data: &mut AppData) -> Result<PendingUpload> {
+        unsafe {
+            device.end_command_buffer(self.transfer_command_buffer)?;
+            device.end_command_buffer(self.graphics_command_buffer)?;
+
+            let semaphore = device.create_semaphore(&vk::SemaphoreCreateInfo::builder(), None)?;
+            data.registry.created(semaphore, 0);
+
+            let command_buffers = &[self.transfer_command_buffer];
+            let signal_semaphores = &[semaphore];
+            let transfer_info = vk::SubmitInfo::builder()
+                .command_buffers(command_buffers)
+                .signal_semaphores(signal_semaphores);
+
+            device.queue_submit(data.transfer_queue, &[transfer_info], vk::Fence::null())?;
+
+            // the graphics submission only starts once the transfer one is
+            // done, so its timeline value covers both submissions
+            let value = submit_graphics(
+                device,
+                data,
+                &[self.graphics_command_buffer],
+                signal_semaphores,
+                &[vk::PipelineStageFlags::TRANSFER],
+                &[],
+            )?;
+
+            Ok(PendingUpload {
+                transfer_command_buffer: self.transfer_command_buffer,
+                graphics_command_buffer: self.graphics_command_buffer,
+                semaphore,
+                value,
+                staging_buffers: self.staging_buffers,
+            })
+        }
+    }
+}
+
+impl PendingUpload {
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    pub unsafe fn is_complete(&self, device: &Device, timeline: &GraphicsTimeline) -> Result<bool> {
+        unsafe { timeline.is_reached(device, self.value) }
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    pub unsafe fn wait(&self, device: &Device, timeline: &GraphicsTimeline) -> Result<()> {
+        unsafe { timeline.wait(device, self.value) }
+    }
+
+    /// # Safety
+    /// This is a vulkan using function and thus is unsafe
+    ///
+    /// The upload must have completed.
+    pub unsafe fn destroy(&self, device: &Device, data: &AppData) {
+        unsafe {
+            self.staging_buffers.iter().for_each(|(b, m)| {
+                device.destroy_buffer(*b, None);
+                data.registry.destroyed(*b);
+                free_allocation(device, data, *m);
+            });
+
+            device
+                .free_command_buffers(data.transfer_command_pool, &[self.transfer_command_buffer]);
+            device.free_command_buffers(data.command_pool, &[self.graphics_command_buffer]);
+            device.destroy_semaphore(self.semaphore, None);
+
+            data.registry.destroyed(self.transfer_command_buffer);
+            data.registry.destroyed(self.graphics_command_buffer);
+            data.registry.destroyed(self.semaphore);
+        }
+    }
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Frees the staging buffers of every upload that has completed.
+pub unsafe fn collect_finished_uploads(device: &Device, data: &mut AppData) -> Result<()> {
+    let mut pending = vec![];
+
+    for upload in std::mem::take(&mut data.pending_uploads) {
+        if unsafe { upload.is_complete(device, &data.timeline)? } {
+            unsafe { upload.destroy(device, data) };
+        } else {
+            pending.push(upload);
+        }
+    }
+
+    data.pending_uploads = pending;
+    Ok(())
+}
+
+/// # Safety
+/// This is a vulkan using function and thus is unsafe
+///
+/// Waits on and frees every upload that is still pending.
+pub unsafe fn destroy_uploads(device: &Device, data: &mut AppData) -> Result<()> {
+    for upload in std::mem::take(&mut data.pending_uploads) {
+        unsafe {
+            upload.wait(device, &data.timeline)?;
+            upload.destroy(device, data);
+        }
+    }
+
+    Ok(())
+}
@@ -1,6 +1,5 @@
 use anyhow::{Result, anyhow};
 use std::hash::Hasher;
-use std::ptr::copy_nonoverlapping as memcpy;
 use std::{hash::Hash, mem::size_of};
 
 use cgmath::{vec2, vec3};
@@ -11,7 +10,11 @@ use vulkanalia::{
 
 use crate::{
     app::AppData,
-    pipeline::buffers::{begin_onetime_command, end_onetime_command},
+    pipeline::{
+        memory::{Allocation, allocate_buffer},
+        resources::Buffer,
+        upload::UploadBatch,
+    },
 };
 
 pub type Vec2 = cgmath::Vector2<f32>;
@@ -105,6 +108,7 @@ pub unsafe fn get_memory_type_index(
         .ok_or_else(|| anyhow!("Failed to find suitable memory type"))
 }
 
+#[track_caller]
 pub unsafe fn create_buffer(
     instance: &Instance,
     device: &Device,
@@ -112,88 +116,52 @@ pub unsafe fn create_buffer(
     size: vk::DeviceSize,
     usage: vk::BufferUsageFlags,
     properties: vk::MemoryPropertyFlags,
-) -> Result<(vk::Buffer, vk::DeviceMemory)> {
+) -> Result<(vk::Buffer, Allocation)> {
     let buffer_info = vk::BufferCreateInfo::builder()
         .size(size)
         .usage(usage)
         .sharing_mode(vk::SharingMode::EXCLUSIVE);
 
     let buffer = device.create_buffer(&buffer_info, None)?;
+    let allocation = allocate_buffer(instance, device, data, buffer, properties)?;
+    data.registry.created(buffer, allocation.size);
 
-    let requirements = device.get_buffer_memory_requirements(buffer);
-    let memory_info = vk::MemoryAllocateInfo::builder()
-        .allocation_size(requirements.size)
-        .memory_type_index(get_memory_type_index(
-            instance,
-            data,
-            properties,
-            requirements,
-        )?);
-
-    let buffer_memory = device.allocate_memory(&memory_info, None)?;
-    device.bind_buffer_memory(buffer, buffer_memory, 0)?;
-    Ok((buffer, buffer_memory))
-}
-
-/// # Safety
-/// This is a vulkan using function and thus is unsafe
-pub unsafe fn copy_buffer(
-    device: &Device,
-    data: &AppData,
-    source: vk::Buffer,
-    destination: vk::Buffer,
-    size: vk::DeviceSize,
-) -> Result<()> {
-    let command_buffer = begin_onetime_command(device, data)?;
-
-    let regions = vk::BufferCopy::builder().size(size);
-    device.cmd_copy_buffer(command_buffer, source, destination, &[regions]);
-
-    end_onetime_command(device, data, command_buffer)?;
-
-    Ok(())
+    Ok((buffer, allocation))
 }
 
 /// # Safety
 /// This is a vulkan using function and thus is unsafe
+///
+/// Creates the index buffer and records its upload into the batch.
 pub unsafe fn create_index_buffer(
     instance: &Instance,
     device: &Device,
     data: &mut AppData,
+    batch: &mut UploadBatch,
 ) -> Result<()> {
     unsafe {
         let size = (size_of::<u32>() * data.indices.len()) as u64;
 
-        let (staging_buffer, staging_buffer_memory) = create_buffer(
+        let (index_buffer, index_buffer_memory) = create_buffer(
             instance,
             device,
             data,
             size,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+            vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
         )?;
 
-        let memory =
-            device.map_memory(staging_buffer_memory, 0, size, vk::MemoryMapFlags::empty())?;
-        memcpy(data.indices.as_ptr(), memory.cast(), data.indices.len());
-        device.unmap_memory(staging_buffer_memory);
+        data.index_buffer = Buffer::new(&data.deletions, index_buffer, index_buffer_memory);
 
-        let (index_buffer, index_buffer_memory) = create_buffer(
+        batch.upload_buffer(
             instance,
             device,
             data,
-            size,
-            vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            &data.indices,
+            index_buffer,
+            vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::AccessFlags::INDEX_READ,
         )?;
-
-        data.index_buffer = index_buffer;
-        data.index_buffer_memory = index_buffer_memory;
-
-        copy_buffer(device, data, staging_buffer, index_buffer, size)?;
-
-        device.destroy_buffer(staging_buffer, None);
-        device.free_memory(staging_buffer_memory, None);
     }
 
     Ok(())
@@ -201,26 +169,16 @@ pub unsafe fn create_index_buffer(
 
 /// # Safety
 /// This is a vulkan using function and thus is unsafe
+///
+/// Creates the vertex buffer and records its upload into the batch.
 pub unsafe fn create_vertex_buffer(
     instance: &Instance,
     device: &Device,
     data: &mut AppData,
+    batch: &mut UploadBatch,
 ) -> Result<()> {
     unsafe {
         let size = (size_of::<Vertex>() * data.vertices.len()) as u64;
-        let (staging_buffer, staging_buffer_memory) = create_buffer(
-            instance,
-            device,
-            data,
-            size,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
-        )?;
-
-        let memory =
-            device.map_memory(staging_buffer_memory, 0, size, vk::MemoryMapFlags::empty())?;
-        memcpy(data.vertices.as_ptr(), memory.cast(), data.vertices.len());
-        device.unmap_memory(staging_buffer_memory);
 
         let (vertex_buffer, vertex_buffer_memory) = create_buffer(
             instance,
@@ -231,12 +189,17 @@ pub unsafe fn create_vertex_buffer(
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
         )?;
 
-        data.vertex_buffer = vertex_buffer;
-        data.vertex_buffer_memory = vertex_buffer_memory;
+        data.vertex_buffer = Buffer::new(&data.deletions, vertex_buffer, vertex_buffer_memory);
 
-        copy_buffer(device, data, staging_buffer, vertex_buffer, size)?;
-        device.destroy_buffer(staging_buffer, None);
-        device.free_memory(staging_buffer_memory, None);
+        batch.upload_buffer(
+            instance,
+            device,
+            data,
+            &data.vertices,
+            vertex_buffer,
+            vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+        )?;
     }
 
     Ok(())
@@ -6,15 +6,21 @@ use vulkanalia::{
 
 use crate::{
     app::AppData,
-    pipeline::buffers::{begin_onetime_command, end_onetime_command},
+    pipeline::barriers::{ImageState, Subresources, transition_image},
 };
 
 /// # Safety
 /// This is a vulkan using function and thus is unsafe
+///
+/// Records the blits generating every mip level from the first one into the
+/// command buffer, the whole image must be tracked in `TRANSFER_DST_OPTIMAL`
+/// and ends up in `SHADER_READ_ONLY_OPTIMAL`.
+#[allow(clippy::too_many_arguments)]
 pub unsafe fn generate_mipmaps(
     instance: &Instance,
     device: &Device,
-    data: &AppData,
+    data: &mut AppData,
+    command_buffer: vk::CommandBuffer,
     image: vk::Image,
     format: vk::Format,
     width: u32,
@@ -32,39 +38,18 @@ pub unsafe fn generate_mipmaps(
             ));
         }
 
-        let command_buffer = begin_onetime_command(device, data)?;
-
-        let subresource = vk::ImageSubresourceRange::builder()
-            .aspect_mask(vk::ImageAspectFlags::COLOR)
-            .base_array_layer(0)
-            .layer_count(1)
-            .level_count(1);
-
-        let mut barrier = vk::ImageMemoryBarrier::builder()
-            .image(image)
-            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-            .subresource_range(subresource);
-
         let mut mip_width = width;
         let mut mip_height = height;
 
         for i in 1..mip_levels {
-            barrier.subresource_range.base_mip_level = i - 1;
-            barrier.old_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
-            barrier.new_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
-            barrier.src_access_mask = vk::AccessFlags::TRANSFER_WRITE;
-            barrier.dst_access_mask = vk::AccessFlags::TRANSFER_READ;
-
-            device.cmd_pipeline_barrier(
+            transition_image(
+                device,
+                data,
                 command_buffer,
-                vk::PipelineStageFlags::TRANSFER,
-                vk::PipelineStageFlags::TRANSFER,
-                vk::DependencyFlags::empty(),
-                &[] as &[vk::MemoryBarrier],
-                &[] as &[vk::BufferMemoryBarrier],
-                &[barrier],
-            );
+                image,
+                Subresources::mip(i - 1),
+                ImageState::TRANSFER_SRC,
+            )?;
 
             let src_subresource = vk::ImageSubresourceLayers::builder()
                 .aspect_mask(vk::ImageAspectFlags::COLOR)
@@ -108,6 +93,15 @@ pub unsafe fn generate_mipmaps(
                 vk::Filter::LINEAR,
             );
 
+            transition_image(
+                device,
+                data,
+                command_buffer,
+                image,
+                Subresources::mip(i - 1),
+                ImageState::FRAGMENT_SHADER_READ,
+            )?;
+
             if mip_width > 1 {
                 mip_width /= 2;
             }
@@ -117,23 +111,14 @@ pub unsafe fn generate_mipmaps(
             }
         }
 
-        barrier.subresource_range.base_mip_level = mip_levels - 1;
-        barrier.old_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
-        barrier.new_layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
-        barrier.src_access_mask = vk::AccessFlags::TRANSFER_WRITE;
-        barrier.dst_access_mask = vk::AccessFlags::SHADER_READ;
-
-        device.cmd_pipeline_barrier(
+        transition_image(
+            device,
+            data,
             command_buffer,
-            vk::PipelineStageFlags::TRANSFER,
-            vk::PipelineStageFlags::FRAGMENT_SHADER,
-            vk::DependencyFlags::empty(),
-            &[] as &[vk::MemoryBarrier],
-            &[] as &[vk::BufferMemoryBarrier],
-            &[barrier],
-        );
-
-        end_onetime_command(device, data, command_buffer)?;
+            image,
+            Subresources::mip(mip_levels - 1),
+            ImageState::FRAGMENT_SHADER_READ,
+        )?;
 
         Ok(())
     }
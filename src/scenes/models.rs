@@ -7,6 +7,9 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
 
+/// the name of the loaded model, used to name the objects created for it
+pub const MODEL_NAME: &str = "viking_room";
+
 pub fn load_model(data: &mut AppData) -> Result<()> {
     let mut reader = BufReader::new(File::open("models/viking_room.obj")?);
     let (models, _) = tobj::load_obj_buf(
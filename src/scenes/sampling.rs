@@ -6,13 +6,20 @@ use vulkanalia::{
 
 use crate::{
     app::AppData,
-    pipeline::image::{create_image, create_image_view},
+    pipeline::{
+        barriers::ImageState,
+        image::{create_image, create_image_view},
+        resources::{Image, ImageView},
+    },
 };
 
 /// # Safety
 /// This is a vulkan using function and thus is unsafe
-pub unsafe fn get_max_msaa_samples(instance: &Instance, data: &AppData) -> vk::SampleCountFlags {
-    let properties = unsafe { instance.get_physical_device_properties(data.physical_device) };
+pub unsafe fn get_max_msaa_samples(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> vk::SampleCountFlags {
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
     let counts = properties.limits.framebuffer_color_sample_counts
         & properties.limits.framebuffer_depth_sample_counts;
 
@@ -50,16 +57,24 @@ pub unsafe fn create_color_objects(
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
         )?;
 
-        data.color_image = color_image;
-        data.color_image_memory = color_image_memory;
+        data.color_image = Image::new(&data.deletions, color_image, color_image_memory);
+        data.layouts.register(
+            color_image,
+            vk::ImageAspectFlags::COLOR,
+            1,
+            1,
+            ImageState::UNDEFINED,
+        );
 
-        data.color_image_view = create_image_view(
+        let color_image_view = create_image_view(
             device,
-            data.color_image,
+            data,
+            color_image,
             data.swapchain_format,
             vk::ImageAspectFlags::COLOR,
             1,
         )?;
+        data.color_image_view = ImageView::new(&data.deletions, color_image_view);
 
         Ok(())
     }
@@ -0,0 +1,111 @@
+use cgmath::{Matrix3, Vector3, Vector4, vec3};
+use choam::gpu_block;
+use choam::pipeline::block::{Block, BlockLayout, BlockValue, check_block};
+use choam::pipeline::descriptors::{ModelConstants, OpacityConstants, UniformBufferObject};
+use choam::pipeline::reflect::reflect_shader;
+
+// the SPIR-V the build script compiled and the app embeds
+const VERT: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/shader.vert.spv"));
+const FRAG: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/shader.frag.spv"));
+
+gpu_block! {
+    struct Light {
+        position: Vector3<f32>,
+        intensity: f32,
+        weights: [f32; 3],
+        color: Vector4<f32>,
+    }
+}
+
+gpu_block! {
+    struct Lit {
+        rotation: Matrix3<f32>,
+        light: Light,
+        exposure: f32,
+    }
+}
+
+fn offsets<T: Block>(layout: BlockLayout) -> Vec<usize> {
+    T::members(layout).into_iter().map(|(_, o)| o).collect()
+}
+
+#[test]
+fn scalars_pack_after_a_vec3() {
+    // a float fits in the padding of a vec3 under both layouts
+    assert_eq!(offsets::<Light>(BlockLayout::Std140)[..2], [0, 12]);
+    assert_eq!(offsets::<Light>(BlockLayout::Std430)[..2], [0, 12]);
+}
+
+#[test]
+fn std140_pads_array_elements_to_a_vec4() {
+    assert_eq!(<[f32; 3]>::size(BlockLayout::Std140), 48);
+    assert_eq!(<[f32; 3]>::size(BlockLayout::Std430), 12);
+
+    assert_eq!(offsets::<Light>(BlockLayout::Std140), vec![0, 12, 16, 64]);
+    assert_eq!(offsets::<Light>(BlockLayout::Std430), vec![0, 12, 16, 32]);
+    assert_eq!(Light::size(BlockLayout::Std140), 80);
+    assert_eq!(Light::size(BlockLayout::Std430), 48);
+}
+
+#[test]
+fn matrices_and_structs_are_aligned() {
+    assert_eq!(Matrix3::<f32>::size(BlockLayout::Std140), 48);
+    assert_eq!(Matrix3::<f32>::size(BlockLayout::Std430), 48);
+
+    // the struct after the matrix is aligned to a vec4, and the float after
+    // the struct starts at its padded end
+    assert_eq!(offsets::<Lit>(BlockLayout::Std140), vec![0, 48, 128]);
+    assert_eq!(Lit::size(BlockLayout::Std140), 144);
+}
+
+#[test]
+fn values_are_written_at_their_offsets() {
+    let light = Light {
+        position: vec3(1.0, 2.0, 3.0),
+        intensity: 4.0,
+        weights: [5.0, 6.0, 7.0],
+        color: Vector4::new(8.0, 9.0, 10.0, 11.0),
+    };
+
+    let bytes = light.to_bytes(BlockLayout::Std140);
+    let floats = bytes
+        .chunks(4)
+        .map(|c| f32::from_ne_bytes(c.try_into().unwrap()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(floats[..4], [1.0, 2.0, 3.0, 4.0]);
+    // every array element starts a vec4
+    assert_eq!([floats[4], floats[8], floats[12]], [5.0, 6.0, 7.0]);
+    assert_eq!(floats[16..20], [8.0, 9.0, 10.0, 11.0]);
+}
+
+#[test]
+fn scene_blocks_match_the_shaders() {
+    let vert = reflect_shader(VERT).unwrap();
+    let frag = reflect_shader(FRAG).unwrap();
+
+    let ubo = &vert.bindings[0].members;
+    assert_eq!(ubo.len(), 2);
+    check_block::<UniformBufferObject>(BlockLayout::Std140, ubo, 0).unwrap();
+
+    check_block::<ModelConstants>(BlockLayout::Std430, &vert.push_constant_members, 0).unwrap();
+    check_block::<OpacityConstants>(BlockLayout::Std430, &frag.push_constant_members, 64).unwrap();
+}
+
+#[test]
+fn misplaced_members_are_reported() {
+    let frag = reflect_shader(FRAG).unwrap();
+
+    // the opacity is read at 64, not at the start of the push constants
+    let error =
+        check_block::<OpacityConstants>(BlockLayout::Std430, &frag.push_constant_members, 0)
+            .unwrap_err();
+    assert!(error.to_string().contains("opacity"), "{}", error);
+
+    // the vertex shader reads `model`, which the opacity block lacks
+    let vert = reflect_shader(VERT).unwrap();
+    assert!(
+        check_block::<OpacityConstants>(BlockLayout::Std430, &vert.push_constant_members, 0)
+            .is_err()
+    );
+}
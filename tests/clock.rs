@@ -0,0 +1,85 @@
+use std::thread;
+use std::time::Duration;
+
+use choam::clock::Clock;
+
+#[test]
+fn fixed_step_advances_once_per_frame() {
+    let mut clock = Clock::fixed_step(Duration::from_millis(500));
+    assert_eq!(clock.time(), Duration::ZERO);
+
+    clock.tick();
+    clock.tick();
+    clock.tick();
+    assert_eq!(clock.time(), Duration::from_millis(1500));
+    assert_eq!(clock.seconds(), 1.5);
+}
+
+#[test]
+fn fixed_step_does_not_truncate_large_frame_counts() {
+    let step = Duration::from_nanos(1);
+    let frames = u32::MAX as u64 + 10;
+    let clock = Clock::FixedStep { step, frames };
+
+    assert_eq!(clock.time().as_nanos(), frames as u128);
+}
+
+#[test]
+fn fixed_step_jumps_to_the_closest_frame() {
+    let mut clock = Clock::fixed_step(Duration::from_millis(100));
+
+    clock.set(Duration::from_millis(1540));
+    assert_eq!(clock.time(), Duration::from_millis(1500));
+
+    clock.advance(Duration::from_millis(260));
+    assert_eq!(clock.time(), Duration::from_millis(1800));
+
+    // a zero step never leaves the first frame
+    let mut clock = Clock::fixed_step(Duration::ZERO);
+    clock.set(Duration::from_secs(3));
+    clock.tick();
+    assert_eq!(clock.time(), Duration::ZERO);
+}
+
+#[test]
+fn manual_clock_only_moves_when_told() {
+    let mut clock = Clock::manual(Duration::from_millis(1500));
+    clock.tick();
+    assert_eq!(clock.time(), Duration::from_millis(1500));
+
+    clock.advance(Duration::from_millis(250));
+    assert_eq!(clock.time(), Duration::from_millis(1750));
+
+    clock.set(Duration::ZERO);
+    assert_eq!(clock.time(), Duration::ZERO);
+}
+
+#[test]
+fn real_time_follows_the_wall_clock() {
+    let mut clock = Clock::real_time();
+    let before = clock.time();
+
+    thread::sleep(Duration::from_millis(20));
+    assert!(clock.time() >= before + Duration::from_millis(20));
+
+    // setting or ticking a real time clock has no effect
+    clock.set(Duration::ZERO);
+    clock.tick();
+    assert!(clock.time() >= Duration::from_millis(20));
+}
+
+#[test]
+fn clocks_saturate_instead_of_overflowing() {
+    let mut clock = Clock::manual(Duration::MAX - Duration::from_secs(1));
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(clock.time(), Duration::MAX);
+
+    // a huge fixed step stops at the largest time on its second frame
+    let mut clock = Clock::fixed_step(Duration::MAX);
+    clock.tick();
+    clock.tick();
+    assert_eq!(clock.time(), Duration::MAX);
+
+    clock.advance(Duration::MAX);
+    assert_eq!(clock.time(), Duration::MAX);
+}
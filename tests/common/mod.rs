@@ -0,0 +1,292 @@
+// every test crate including this module only uses some of its helpers
+#![allow(dead_code)]
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use choam::app::App;
+use choam::clock::Clock;
+use choam::config::AppConfig;
+use choam::foundation::offscreen::save_png;
+use choam::foundation::validation::ValidationSink;
+use vulkanalia::loader::{LIBRARY, LibloadingLoader};
+use vulkanalia::vk::DeviceV1_0;
+
+/// the size every golden scene is rendered at
+pub const GOLDEN_WIDTH: u32 = 256;
+pub const GOLDEN_HEIGHT: u32 = 192;
+
+/// the default per channel tolerance, overridable with `CHOAM_GOLDEN_TOLERANCE`
+pub const DEFAULT_TOLERANCE: u8 = 2;
+
+/// a fixed scene that is rendered and compared against a reference image
+#[derive(Copy, Clone, Debug)]
+pub struct GoldenScene {
+    pub name: &'static str,
+    pub models: usize,
+    pub time: Duration,
+    pub tolerance: u8,
+    /// draws with the render pass fallback even when dynamic rendering is
+    /// available, both paths are compared against the same reference
+    pub render_pass: bool,
+}
+
+impl GoldenScene {
+    pub const fn new(name: &'static str, models: usize) -> Self {
+        Self {
+            name,
+            models,
+            time: Duration::ZERO,
+            tolerance: DEFAULT_TOLERANCE,
+            render_pass: false,
+        }
+    }
+
+    pub const fn at(mut self, time: Duration) -> Self {
+        self.time = time;
+        self
+    }
+
+    pub const fn tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub const fn render_pass(mut self) -> Self {
+        self.render_pass = true;
+        self
+    }
+
+    /// the name the rendered and diff images are written under on a mismatch
+    fn output_name(&self) -> String {
+        if self.render_pass {
+            format!("{}.render_pass", self.name)
+        } else {
+            self.name.to_string()
+        }
+    }
+}
+
+/// whether there is a vulkan loader to render with, tests needing a device
+/// are skipped on machines without one instead of failing
+///
+/// Setting `CHOAM_REQUIRE_VULKAN=1` fails them instead, so a CI machine that
+/// lost its loader does not pass every device test without running it.
+pub fn vulkan_available() -> bool {
+    match unsafe { LibloadingLoader::new(LIBRARY) } {
+        Ok(_) => true,
+        Err(error) if std::env::var_os("CHOAM_REQUIRE_VULKAN").is_some_and(|v| v != "0") => {
+            panic!(
+                "CHOAM_REQUIRE_VULKAN is set, but there is no vulkan loader: {}",
+                error
+            )
+        }
+        Err(error) => {
+            eprintln!("Skipping test, no vulkan loader: {}", error);
+            false
+        }
+    }
+}
+
+/// renders the scene through the headless app and returns its rgba pixels,
+/// failing when the validation layers reported any errors while rendering or
+/// tearing down, or are not installed at all
+pub fn render_scene(scene: &GoldenScene) -> Result<Vec<u8>> {
+    let sink = ValidationSink::new();
+    let mut config = AppConfig::from_env()?;
+    // a scene is only clean when validation actually ran, so a missing
+    // validation layer fails the test instead of passing it silently
+    config.validation.enabled = true;
+    config.validation.sink = Some(sink.clone());
+    config.track_resources = true;
+    // the scenes are rendered from scratch, without the user's pipeline cache
+    config.pipeline_cache = None;
+    if scene.render_pass {
+        config.dynamic_rendering = false;
+    }
+
+    let pixels = unsafe {
+        let mut app = App::create_headless(GOLDEN_WIDTH, GOLDEN_HEIGHT, &config)
+            .context("Golden scenes need the vulkan validation layers installed")?;
+        app.models = scene.models;
+        app.clock = Clock::manual(scene.time);
+
+        let result = app.render_offscreen();
+        app.device.device_wait_idle()?;
+        app.destroy();
+        // teardown errors such as destroying objects still in use are only
+        // reported while destroying
+        let errors = sink.errors();
+
+        let leaked = app.resources().live();
+        if !leaked.is_empty() {
+            let leaked = leaked.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+            return Err(anyhow!(
+                "Scene `{}` leaked {} resources:\n{}",
+                scene.name,
+                leaked.len(),
+                leaked.join("\n")
+            ));
+        }
+
+        if !errors.is_empty() {
+            let messages = errors
+                .iter()
+                .map(|e| e.message.as_str())
+                .collect::<Vec<_>>();
+            return Err(anyhow!(
+                "Scene `{}` reported {} validation errors:\n{}",
+                scene.name,
+                errors.len(),
+                messages.join("\n")
+            ));
+        }
+
+        result?
+    };
+
+    Ok(pixels)
+}
+
+/// renders the scene and checks it against `tests/golden/<name>.png`
+///
+/// Setting `CHOAM_BLESS=1` writes the rendered image as the new reference
+/// instead of comparing against it. On a mismatch the rendered image and a
+/// diff image (mismatched pixels in red) are written next to the test binary.
+pub fn check_scene(scene: GoldenScene) -> Result<()> {
+    if !vulkan_available() {
+        return Ok(());
+    }
+
+    let pixels = render_scene(&scene)?;
+    let reference_path = reference_path(scene.name);
+
+    if std::env::var_os("CHOAM_BLESS").is_some() {
+        // the render pass fallback shares the dynamic rendering reference
+        if scene.render_pass {
+            return Ok(());
+        }
+
+        fs::create_dir_all(reference_path.parent().unwrap())?;
+        save_png(&reference_path, GOLDEN_WIDTH, GOLDEN_HEIGHT, &pixels)?;
+        return Ok(());
+    }
+
+    if !reference_path.exists() {
+        return Err(anyhow!(
+            "Missing reference image `{}`, run with CHOAM_BLESS=1 to create it",
+            reference_path.display()
+        ));
+    }
+
+    let (width, height, reference) = load_png(&reference_path)?;
+    if (width, height) != (GOLDEN_WIDTH, GOLDEN_HEIGHT) {
+        return Err(anyhow!(
+            "Reference image `{}` is {}x{}, expected {}x{}",
+            reference_path.display(),
+            width,
+            height,
+            GOLDEN_WIDTH,
+            GOLDEN_HEIGHT
+        ));
+    }
+
+    let tolerance = tolerance_override().unwrap_or(scene.tolerance);
+    let comparison = compare_pixels(&reference, &pixels, tolerance);
+    if comparison.mismatched == 0 {
+        return Ok(());
+    }
+
+    let output_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    fs::create_dir_all(&output_dir)?;
+
+    let actual_path = output_dir.join(format!("{}.actual.png", scene.output_name()));
+    let diff_path = output_dir.join(format!("{}.diff.png", scene.output_name()));
+    save_png(&actual_path, GOLDEN_WIDTH, GOLDEN_HEIGHT, &pixels)?;
+    save_png(&diff_path, GOLDEN_WIDTH, GOLDEN_HEIGHT, &comparison.diff)?;
+
+    Err(anyhow!(
+        "Scene `{}` differs from its reference in {} pixels (max channel difference {}, tolerance {}), see `{}` and `{}`",
+        scene.name,
+        comparison.mismatched,
+        comparison.max_difference,
+        tolerance,
+        actual_path.display(),
+        diff_path.display()
+    ))
+}
+
+fn reference_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name))
+}
+
+fn tolerance_override() -> Option<u8> {
+    std::env::var("CHOAM_GOLDEN_TOLERANCE")
+        .ok()
+        .and_then(|t| t.parse().ok())
+}
+
+fn load_png(path: &Path) -> Result<(u32, u32, Vec<u8>)> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::ALPHA);
+    let mut reader = decoder.read_info()?;
+
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels)?;
+    pixels.truncate(info.buffer_size());
+
+    if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+        return Err(anyhow!(
+            "Reference image `{}` is not 8 bit rgba",
+            path.display()
+        ));
+    }
+
+    Ok((info.width, info.height, pixels))
+}
+
+/// the result of comparing two images pixel by pixel
+pub struct Comparison {
+    pub mismatched: usize,
+    pub max_difference: u8,
+    pub diff: Vec<u8>,
+}
+
+/// compares two rgba images, a pixel mismatches when any of its channels
+/// differs by more than the tolerance. The diff image shows mismatched pixels
+/// in red over a faded copy of the reference.
+pub fn compare_pixels(reference: &[u8], actual: &[u8], tolerance: u8) -> Comparison {
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+    let mut diff = Vec::with_capacity(reference.len());
+
+    for (expected, actual) in reference.chunks_exact(4).zip(actual.chunks_exact(4)) {
+        let difference = expected
+            .iter()
+            .zip(actual)
+            .map(|(e, a)| e.abs_diff(*a))
+            .max()
+            .unwrap_or(0);
+
+        max_difference = max_difference.max(difference);
+
+        if difference > tolerance {
+            mismatched += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let luma = (expected[0] as u32 + expected[1] as u32 + expected[2] as u32) / 3;
+            let faded = (luma / 4) as u8;
+            diff.extend_from_slice(&[faded, faded, faded, 255]);
+        }
+    }
+
+    Comparison {
+        mismatched,
+        max_difference,
+        diff,
+    }
+}
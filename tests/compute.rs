@@ -0,0 +1,126 @@
+mod common;
+
+use anyhow::Result;
+use choam::app::App;
+use choam::config::AppConfig;
+use choam::gpu_block;
+use choam::pipeline::block::{BlockLayout, BlockValue, PushConstants};
+use choam::pipeline::compute::{ComputePipeline, run_compute, workgroup_count};
+use choam::pipeline::descriptor_allocator::{DEFAULT_POOL_RATIOS, DescriptorAllocator};
+use choam::pipeline::descriptors::{storage_buffer_binding, write_storage_buffer};
+use choam::pipeline::memory::free_allocation;
+use choam::pipeline::vertex::create_buffer;
+use vulkanalia::vk::{self, DeviceV1_0};
+
+const SCALE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/scale.comp.spv"));
+
+/// the local size `scale.comp` is compiled with
+const LOCAL_SIZE: [u32; 3] = [64, 1, 1];
+
+gpu_block! {
+    /// the push constants of `scale.comp`
+    struct ScaleConstants {
+        count: u32,
+        factor: f32,
+    }
+}
+
+impl PushConstants for ScaleConstants {
+    const STAGES: vk::ShaderStageFlags = vk::ShaderStageFlags::COMPUTE;
+}
+
+#[test]
+fn workgroups_cover_every_element() {
+    assert_eq!(workgroup_count([64, 1, 1], LOCAL_SIZE), [1, 1, 1]);
+    assert_eq!(workgroup_count([65, 1, 1], LOCAL_SIZE), [2, 1, 1]);
+    assert_eq!(workgroup_count([100, 30, 2], [8, 8, 1]), [13, 4, 2]);
+}
+
+#[test]
+fn empty_and_unsized_dimensions() {
+    // nothing to cover dispatches no workgroups
+    assert_eq!(workgroup_count([0, 1, 1], LOCAL_SIZE), [0, 1, 1]);
+    // a local size of 0 is treated as 1 instead of dividing by zero
+    assert_eq!(workgroup_count([5, 3, 1], [0, 0, 1]), [5, 3, 1]);
+}
+
+#[test]
+fn scale_shader_doubles_a_buffer() -> Result<()> {
+    if !common::vulkan_available() {
+        return Ok(());
+    }
+
+    let mut config = AppConfig::from_env()?;
+    config.pipeline_cache = None;
+    config.track_resources = true;
+
+    // more values than one workgroup, with a partial last one
+    let input = (0..100).map(|i| i as f32).collect::<Vec<_>>();
+    let size = std::mem::size_of_val(input.as_slice()) as u64;
+
+    unsafe {
+        let mut app = App::create_headless(16, 16, &config)?;
+        let device = app.device.clone();
+        let pipeline = ComputePipeline::create(
+            &device,
+            app.data_mut(),
+            SCALE,
+            &[storage_buffer_binding(0, vk::ShaderStageFlags::COMPUTE)],
+            ScaleConstants::size(BlockLayout::Std430) as u32,
+        )?;
+
+        let (device, data) = (&app.device, app.data());
+        let mut descriptors = DescriptorAllocator::new(DEFAULT_POOL_RATIOS);
+        let (buffer, allocation) = create_buffer(
+            app.instance(),
+            device,
+            data,
+            size,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+        let memory = data.allocator.mapped(allocation)?.cast::<f32>();
+        memory.copy_from_nonoverlapping(input.as_ptr(), input.len());
+
+        let descriptor_set = descriptors.allocate(
+            device,
+            &data.registry,
+            &data.descriptor_layouts,
+            pipeline.descriptor_set_layout,
+        )?;
+        write_storage_buffer(device, descriptor_set, 0, buffer, size)?;
+
+        let constants = ScaleConstants {
+            count: input.len() as u32,
+            factor: 2.0,
+        };
+        run_compute(device, data, |command_buffer| {
+            pipeline.bind(device, command_buffer, descriptor_set);
+            pipeline.push_constants(device, command_buffer, &constants);
+            pipeline.dispatch(
+                device,
+                command_buffer,
+                [input.len() as u32, 1, 1],
+                LOCAL_SIZE,
+            );
+        })?;
+
+        let mut output = vec![0.0f32; input.len()];
+        output
+            .as_mut_ptr()
+            .copy_from_nonoverlapping(memory, output.len());
+
+        device.destroy_buffer(buffer, None);
+        data.registry.destroyed(buffer);
+        free_allocation(device, data, allocation);
+        descriptors.destroy(device, &data.registry);
+        pipeline.destroy(device, data);
+        app.destroy();
+        assert!(app.resources().live().is_empty());
+
+        let expected = input.iter().map(|v| v * 2.0).collect::<Vec<_>>();
+        assert_eq!(output, expected);
+    }
+
+    Ok(())
+}
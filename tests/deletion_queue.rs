@@ -0,0 +1,85 @@
+use choam::pipeline::memory::Allocation;
+use choam::pipeline::resources::{
+    Buffer, DeletionQueue, Image, ImageView, Pipeline, Retired, Sampler,
+};
+use vulkanalia::vk::{self, Handle};
+
+#[test]
+fn dropped_resources_are_retired() {
+    let queue = DeletionQueue::default();
+
+    drop(Buffer::new(
+        &queue,
+        vk::Buffer::from_raw(1),
+        Allocation::default(),
+    ));
+    drop(Image::new(
+        &queue,
+        vk::Image::from_raw(2),
+        Allocation::default(),
+    ));
+    drop(ImageView::new(&queue, vk::ImageView::from_raw(3)));
+    drop(Sampler::new(&queue, vk::Sampler::from_raw(4)));
+    drop(Pipeline::new(&queue, vk::Pipeline::from_raw(5)));
+
+    assert_eq!(queue.len(), 5);
+}
+
+#[test]
+fn null_resources_are_not_retired() {
+    let queue = DeletionQueue::default();
+
+    drop(Buffer::default());
+    drop(ImageView::new(&queue, vk::ImageView::null()));
+
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn clones_share_the_queue() {
+    let queue = DeletionQueue::default();
+    let view = ImageView::new(&queue.clone(), vk::ImageView::from_raw(1));
+
+    assert!(queue.is_empty());
+    drop(view);
+    assert_eq!(queue.len(), 1);
+}
+
+#[test]
+fn resources_wait_for_the_next_submission() {
+    let queue = DeletionQueue::default();
+    queue.submitted(4);
+
+    // the view may still be recorded into the command buffer of submission 5
+    drop(ImageView::new(&queue, vk::ImageView::from_raw(1)));
+
+    assert!(queue.take_completed(4).is_empty());
+    assert_eq!(queue.len(), 1);
+
+    assert_eq!(
+        queue.take_completed(5),
+        vec![Retired::ImageView(vk::ImageView::from_raw(1))]
+    );
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn only_completed_values_are_taken() {
+    let queue = DeletionQueue::default();
+
+    queue.submitted(1);
+    drop(Sampler::new(&queue, vk::Sampler::from_raw(1)));
+    queue.submitted(2);
+    drop(Sampler::new(&queue, vk::Sampler::from_raw(2)));
+    // an older value never moves the timeline back
+    queue.submitted(1);
+    drop(Sampler::new(&queue, vk::Sampler::from_raw(3)));
+
+    assert_eq!(
+        queue.take_completed(2),
+        vec![Retired::Sampler(vk::Sampler::from_raw(1))]
+    );
+    assert_eq!(queue.len(), 2);
+    assert_eq!(queue.take_completed(3).len(), 2);
+    assert!(queue.is_empty());
+}
@@ -0,0 +1,299 @@
+mod common;
+
+use anyhow::Result;
+use choam::app::App;
+use choam::config::AppConfig;
+use choam::pipeline::descriptor_allocator::{
+    DEFAULT_POOL_RATIOS, DescriptorAllocator, DescriptorLayoutCache, DescriptorWriter,
+    INITIAL_SETS_PER_POOL, layout_key, pool_sizes, set_sizes,
+};
+use choam::pipeline::reflect::{ShaderInterface, reflect_shader};
+use vulkanalia::vk::{self, Handle, HasBuilder};
+
+// the SPIR-V the build script compiled and the app embeds
+const VERT: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/shader.vert.spv"));
+const FRAG: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/shader.frag.spv"));
+
+fn scene_interface() -> ShaderInterface {
+    let stages = [reflect_shader(VERT).unwrap(), reflect_shader(FRAG).unwrap()];
+    ShaderInterface::new(&stages).unwrap()
+}
+
+fn binding(
+    binding: u32,
+    type_: vk::DescriptorType,
+    stages: vk::ShaderStageFlags,
+) -> vk::DescriptorSetLayoutBinding {
+    vk::DescriptorSetLayoutBinding::builder()
+        .binding(binding)
+        .descriptor_type(type_)
+        .descriptor_count(1)
+        .stage_flags(stages)
+        .build()
+}
+
+#[test]
+fn pools_hold_the_ratio_of_descriptors_per_set() {
+    let sizes = pool_sizes(DEFAULT_POOL_RATIOS, 16);
+
+    assert_eq!(sizes.len(), DEFAULT_POOL_RATIOS.len());
+    for (size, (type_, count)) in sizes.iter().zip(DEFAULT_POOL_RATIOS) {
+        assert_eq!(size.type_, *type_);
+        assert_eq!(size.descriptor_count, count * 16);
+    }
+}
+
+#[test]
+fn layout_keys_ignore_binding_order() {
+    let ubo = binding(
+        0,
+        vk::DescriptorType::UNIFORM_BUFFER,
+        vk::ShaderStageFlags::VERTEX,
+    );
+    let sampler = binding(
+        1,
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        vk::ShaderStageFlags::FRAGMENT,
+    );
+
+    assert_eq!(layout_key(&[ubo, sampler]), layout_key(&[sampler, ubo]));
+
+    // the same binding read by another stage is a different layout
+    let shared = binding(
+        0,
+        vk::DescriptorType::UNIFORM_BUFFER,
+        vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+    );
+    assert_ne!(layout_key(&[ubo, sampler]), layout_key(&[shared, sampler]));
+}
+
+#[test]
+fn writer_resolves_bindings_by_name_and_number() {
+    let interface = scene_interface();
+    let mut writer = DescriptorWriter::new(&interface, 0);
+
+    writer
+        .buffer("ubo", vk::Buffer::null(), 0, 128)
+        .unwrap()
+        .image(
+            1,
+            vk::ImageView::null(),
+            vk::Sampler::null(),
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )
+        .unwrap();
+    assert_eq!(writer.written(), vec![0, 1]);
+
+    writer.clear();
+    assert!(writer.written().is_empty());
+}
+
+#[test]
+fn writer_rejects_unknown_and_mismatched_bindings() {
+    let interface = scene_interface();
+    let mut writer = DescriptorWriter::new(&interface, 0);
+
+    assert!(writer.buffer("lights", vk::Buffer::null(), 0, 16).is_err());
+    assert!(writer.buffer(7, vk::Buffer::null(), 0, 16).is_err());
+
+    // the sampler binding takes an image, not a buffer
+    let error = writer
+        .buffer("texSampler", vk::Buffer::null(), 0, 16)
+        .unwrap_err();
+    assert!(error.to_string().contains("not a buffer"), "{}", error);
+
+    // the bindings of other sets are not part of the writer
+    let mut writer = DescriptorWriter::new(&interface, 1);
+    assert!(writer.buffer("ubo", vk::Buffer::null(), 0, 16).is_err());
+    assert!(writer.written().is_empty());
+}
+
+#[test]
+fn layout_writers_only_resolve_numbers() {
+    let bindings = [binding(
+        0,
+        vk::DescriptorType::STORAGE_BUFFER,
+        vk::ShaderStageFlags::COMPUTE,
+    )];
+    let mut writer = DescriptorWriter::for_layout(&bindings);
+
+    assert!(writer.buffer(0, vk::Buffer::null(), 0, 16).is_ok());
+    assert!(
+        writer
+            .buffer("particles", vk::Buffer::null(), 0, 16)
+            .is_err()
+    );
+    assert!(
+        writer
+            .image(
+                0,
+                vk::ImageView::null(),
+                vk::Sampler::null(),
+                vk::ImageLayout::GENERAL
+            )
+            .is_err()
+    );
+}
+
+#[test]
+fn sets_fit_the_pools_up_to_their_capacity() {
+    let allocator = DescriptorAllocator::new(DEFAULT_POOL_RATIOS);
+
+    let scene = scene_interface().set_layout_bindings(0);
+    assert!(allocator.fits_pools(&scene));
+
+    // a type the pools reserve nothing for
+    let sampled = binding(
+        0,
+        vk::DescriptorType::SAMPLED_IMAGE,
+        vk::ShaderStageFlags::FRAGMENT,
+    );
+    assert!(!allocator.fits_pools(&[sampled]));
+
+    // more samplers than the 4 reserved per set still fit in a pool
+    let mut textures = binding(
+        0,
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        vk::ShaderStageFlags::FRAGMENT,
+    );
+    textures.descriptor_count = 3;
+    let mut normals = textures;
+    normals.binding = 1;
+    normals.descriptor_count = 2;
+    assert!(allocator.fits_pools(&[textures, normals]));
+
+    // but not more than the first pool holds, spread over two bindings
+    normals.descriptor_count = 4 * INITIAL_SETS_PER_POOL - 2;
+    assert!(!allocator.fits_pools(&[textures, normals]));
+
+    let sizes = set_sizes(&[textures, normals]);
+    assert_eq!(sizes.len(), 1);
+    assert_eq!(sizes[0].type_, vk::DescriptorType::COMBINED_IMAGE_SAMPLER);
+    assert_eq!(sizes[0].descriptor_count, 4 * INITIAL_SETS_PER_POOL + 1);
+}
+
+#[test]
+fn allocator_grows_and_resets_its_pools() -> Result<()> {
+    if !common::vulkan_available() {
+        return Ok(());
+    }
+
+    let mut config = AppConfig::from_env()?;
+    config.pipeline_cache = None;
+    config.track_resources = true;
+
+    unsafe {
+        let mut app = App::create_headless(16, 16, &config)?;
+        let (device, data) = (&app.device, app.data());
+        let mut allocator = DescriptorAllocator::new(DEFAULT_POOL_RATIOS);
+
+        // one set more than the first pool holds runs it out of memory
+        let allocate = |allocator: &mut DescriptorAllocator| -> Result<Vec<_>> {
+            (0..=INITIAL_SETS_PER_POOL)
+                .map(|_| {
+                    allocator.allocate(
+                        device,
+                        &data.registry,
+                        &data.descriptor_layouts,
+                        data.descriptor_set_layout,
+                    )
+                })
+                .collect()
+        };
+
+        let sets = allocate(&mut allocator)?;
+        assert_eq!(allocator.pools().count(), 2);
+        assert!(sets.iter().all(|s| !s.is_null()));
+
+        // the reset pools have room for the same sets again
+        allocator.reset(device, &data.registry)?;
+        allocate(&mut allocator)?;
+        assert_eq!(allocator.pools().count(), 2);
+
+        // a set too large for the pools gets one of its own until the reset
+        let mut layouts = DescriptorLayoutCache::default();
+        let mut textures = binding(
+            0,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            vk::ShaderStageFlags::FRAGMENT,
+        );
+        textures.descriptor_count = 4 * INITIAL_SETS_PER_POOL + 1;
+        let layout = layouts.get(device, &data.registry, &[textures])?;
+        let set = allocator.allocate(device, &data.registry, &layouts, layout)?;
+        assert!(!set.is_null());
+        assert_eq!(allocator.pools().count(), 3);
+
+        allocator.reset(device, &data.registry)?;
+        assert_eq!(allocator.pools().count(), 2);
+        layouts.destroy(device, &data.registry);
+
+        // layouts the cache does not know can not be checked
+        let mut layouts = DescriptorLayoutCache::default();
+        let storage = binding(
+            0,
+            vk::DescriptorType::STORAGE_BUFFER,
+            vk::ShaderStageFlags::COMPUTE,
+        );
+        let layout = layouts.get(device, &data.registry, &[storage])?;
+        assert!(
+            allocator
+                .allocate(device, &data.registry, &data.descriptor_layouts, layout)
+                .is_err()
+        );
+
+        layouts.destroy(device, &data.registry);
+        allocator.destroy(device, &data.registry);
+        app.destroy();
+        assert!(app.resources().live().is_empty());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn cache_returns_the_same_layout_for_the_same_bindings() -> Result<()> {
+    if !common::vulkan_available() {
+        return Ok(());
+    }
+
+    let mut config = AppConfig::from_env()?;
+    config.pipeline_cache = None;
+    config.track_resources = true;
+
+    unsafe {
+        let mut app = App::create_headless(16, 16, &config)?;
+        let (device, data) = (&app.device, app.data());
+        let mut layouts = DescriptorLayoutCache::default();
+
+        let buffer = binding(
+            0,
+            vk::DescriptorType::STORAGE_BUFFER,
+            vk::ShaderStageFlags::COMPUTE,
+        );
+        let image = binding(
+            1,
+            vk::DescriptorType::STORAGE_IMAGE,
+            vk::ShaderStageFlags::COMPUTE,
+        );
+
+        let first = layouts.get(device, &data.registry, &[buffer, image])?;
+        let second = layouts.get(device, &data.registry, &[image, buffer])?;
+        assert_eq!(first, second);
+        assert_eq!(layouts.len(), 1);
+
+        let other = layouts.get(device, &data.registry, &[buffer])?;
+        assert_ne!(first, other);
+        assert_eq!(layouts.len(), 2);
+        assert_eq!(
+            layout_key(&layouts.bindings(first).unwrap()),
+            layout_key(&[buffer, image])
+        );
+
+        layouts.destroy(device, &data.registry);
+        assert!(layouts.is_empty());
+        app.destroy();
+        assert!(app.resources().live().is_empty());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+
+use choam::foundation::requirements::{
+    DYNAMIC_RENDERING_EXTENSION, DeviceRequirements, Feature, FeatureStructs,
+};
+use vulkanalia::{Version, vk};
+
+fn supported(features: &[Feature]) -> FeatureStructs {
+    let mut structs = FeatureStructs::default();
+    features.iter().for_each(|f| f.set(&mut structs));
+    structs
+}
+
+fn requirements() -> DeviceRequirements {
+    DeviceRequirements::new()
+        .require_extension(vk::KHR_SWAPCHAIN_EXTENSION.name)
+        .optional_extension(vk::KHR_DYNAMIC_RENDERING_EXTENSION.name)
+        .require_feature(Feature::SampleRateShading)
+        .require_feature(Feature::TimelineSemaphore)
+        .optional_feature(Feature::SamplerAnisotropy)
+        .optional_feature(Feature::Synchronization2)
+}
+
+#[test]
+fn missing_required_features_are_reported() {
+    let requirements = requirements();
+
+    let features = supported(&[Feature::SampleRateShading, Feature::SamplerAnisotropy]);
+    assert_eq!(
+        requirements.missing_features(&features),
+        vec![Feature::TimelineSemaphore]
+    );
+
+    // optional features are never missing
+    let features = supported(&[Feature::SampleRateShading, Feature::TimelineSemaphore]);
+    assert!(requirements.missing_features(&features).is_empty());
+}
+
+#[test]
+fn missing_required_extensions_are_reported() {
+    let requirements = requirements();
+
+    let extensions = HashSet::from([vk::KHR_DYNAMIC_RENDERING_EXTENSION.name]);
+    assert_eq!(
+        requirements.missing_extensions(&extensions),
+        vec![vk::KHR_SWAPCHAIN_EXTENSION.name]
+    );
+
+    let extensions = HashSet::from([vk::KHR_SWAPCHAIN_EXTENSION.name]);
+    assert!(requirements.missing_extensions(&extensions).is_empty());
+}
+
+#[test]
+fn unsupported_optional_features_are_dropped() {
+    let extensions = HashSet::from([vk::KHR_SWAPCHAIN_EXTENSION.name]);
+    let features = supported(&[
+        Feature::SampleRateShading,
+        Feature::TimelineSemaphore,
+        Feature::Synchronization2,
+    ]);
+
+    let enabled = requirements().resolve(&extensions, &features);
+
+    assert!(enabled.has(Feature::SampleRateShading));
+    assert!(enabled.has(Feature::TimelineSemaphore));
+    assert!(enabled.has(Feature::Synchronization2));
+    assert!(!enabled.has(Feature::SamplerAnisotropy));
+
+    assert!(enabled.has_extension(vk::KHR_SWAPCHAIN_EXTENSION.name));
+    assert!(!enabled.has_extension(vk::KHR_DYNAMIC_RENDERING_EXTENSION.name));
+}
+
+#[test]
+fn supported_optional_features_are_enabled() {
+    let extensions = HashSet::from([
+        vk::KHR_SWAPCHAIN_EXTENSION.name,
+        vk::KHR_DYNAMIC_RENDERING_EXTENSION.name,
+    ]);
+    let features = supported(&[
+        Feature::SampleRateShading,
+        Feature::TimelineSemaphore,
+        Feature::SamplerAnisotropy,
+        Feature::Synchronization2,
+        // supported but never requested
+        Feature::GeometryShader,
+    ]);
+
+    let enabled = requirements().resolve(&extensions, &features);
+
+    assert!(enabled.has(Feature::SamplerAnisotropy));
+    assert!(enabled.has_extension(vk::KHR_DYNAMIC_RENDERING_EXTENSION.name));
+    assert!(!enabled.has(Feature::GeometryShader));
+    assert_eq!(enabled.extensions.len(), 2);
+}
+
+#[test]
+fn dynamic_rendering_falls_back_to_the_extension_before_1_3() {
+    let requirements = DeviceRequirements::new().optional_feature(Feature::DynamicRendering);
+    let features = supported(&[Feature::DynamicRendering]);
+
+    let mut enabled = requirements.resolve(&HashSet::new(), &features);
+    enabled.enable_promoted(Version::new(1, 2, 0));
+    assert!(enabled.has(Feature::DynamicRendering));
+    assert!(enabled.has_extension(DYNAMIC_RENDERING_EXTENSION));
+    assert_eq!(
+        enabled.features.khr_dynamic_rendering.dynamic_rendering,
+        vk::TRUE
+    );
+
+    // 1.3 devices have it in core and need no extension
+    let mut enabled = requirements.resolve(&HashSet::new(), &features);
+    enabled.enable_promoted(Version::new(1, 3, 0));
+    assert!(!enabled.has_extension(DYNAMIC_RENDERING_EXTENSION));
+
+    // nothing is enabled for a feature the device lacks
+    let mut enabled = requirements.resolve(&HashSet::new(), &FeatureStructs::default());
+    enabled.enable_promoted(Version::new(1, 2, 0));
+    assert!(enabled.extensions.is_empty());
+}
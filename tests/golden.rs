@@ -0,0 +1,42 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{GoldenScene, check_scene};
+
+#[test]
+#[cfg_attr(
+    not(golden_reference = "single_model"),
+    ignore = "tests/golden/single_model.png is not blessed yet, see tests/golden/README.md"
+)]
+fn single_model() {
+    check_scene(GoldenScene::new("single_model", 1)).unwrap();
+}
+
+#[test]
+#[cfg_attr(
+    not(golden_reference = "single_model"),
+    ignore = "tests/golden/single_model.png is not blessed yet, see tests/golden/README.md"
+)]
+fn single_model_render_pass() {
+    check_scene(GoldenScene::new("single_model", 1).render_pass()).unwrap();
+}
+
+#[test]
+#[cfg_attr(
+    not(golden_reference = "four_models"),
+    ignore = "tests/golden/four_models.png is not blessed yet, see tests/golden/README.md"
+)]
+fn four_models() {
+    check_scene(GoldenScene::new("four_models", 4).tolerance(4)).unwrap();
+}
+
+#[test]
+#[cfg_attr(
+    not(golden_reference = "single_model_rotated"),
+    ignore = "tests/golden/single_model_rotated.png is not blessed yet, see tests/golden/README.md"
+)]
+fn single_model_rotated() {
+    check_scene(GoldenScene::new("single_model_rotated", 1).at(Duration::from_millis(1500)))
+        .unwrap();
+}
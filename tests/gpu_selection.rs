@@ -0,0 +1,158 @@
+use choam::foundation::selection::{DeviceScore, GpuSelector, score_device};
+use vulkanalia::vk;
+
+fn properties(name: &str, device_type: vk::PhysicalDeviceType) -> vk::PhysicalDeviceProperties {
+    let mut device_name = [0; vk::MAX_PHYSICAL_DEVICE_NAME_SIZE];
+    for (c, b) in device_name.iter_mut().zip(name.bytes()) {
+        *c = b as i8;
+    }
+
+    vk::PhysicalDeviceProperties {
+        device_name: device_name.into(),
+        device_type,
+        vendor_id: 0x10de,
+        device_id: 0x2484,
+        ..Default::default()
+    }
+}
+
+fn memory(device_local: &[vk::DeviceSize]) -> vk::PhysicalDeviceMemoryProperties {
+    let mut memory = vk::PhysicalDeviceMemoryProperties {
+        memory_heap_count: device_local.len() as u32 + 1,
+        ..Default::default()
+    };
+    for (heap, size) in memory.memory_heaps.iter_mut().zip(device_local) {
+        heap.size = *size;
+        heap.flags = vk::MemoryHeapFlags::DEVICE_LOCAL;
+    }
+    // a host heap, which does not count towards the score
+    memory.memory_heaps[device_local.len()].size = 1 << 40;
+    memory
+}
+
+fn score(
+    device_type: vk::PhysicalDeviceType,
+    device_local: &[vk::DeviceSize],
+    samples: vk::SampleCountFlags,
+) -> DeviceScore {
+    score_device(
+        &properties("gpu", device_type),
+        &memory(device_local),
+        samples,
+    )
+}
+
+#[test]
+fn selectors_are_parsed() {
+    assert_eq!(
+        "index:1".parse::<GpuSelector>().unwrap(),
+        GpuSelector::Index(1)
+    );
+    assert_eq!(
+        "10de:2484".parse::<GpuSelector>().unwrap(),
+        GpuSelector::Id {
+            vendor_id: 0x10de,
+            device_id: 0x2484
+        }
+    );
+    assert_eq!(
+        "0x10DE:0x2484".parse::<GpuSelector>().unwrap(),
+        GpuSelector::Id {
+            vendor_id: 0x10de,
+            device_id: 0x2484
+        }
+    );
+    assert_eq!(
+        " lavapipe ".parse::<GpuSelector>().unwrap(),
+        GpuSelector::Name("lavapipe".into())
+    );
+
+    assert!("".parse::<GpuSelector>().is_err());
+    assert!("index:first".parse::<GpuSelector>().is_err());
+}
+
+#[test]
+fn digit_names_are_names() {
+    let selector = "1080".parse::<GpuSelector>().unwrap();
+    assert_eq!(selector, GpuSelector::Name("1080".into()));
+
+    let gtx = properties(
+        "NVIDIA GeForce GTX 1080",
+        vk::PhysicalDeviceType::DISCRETE_GPU,
+    );
+    assert!(selector.matches(0, &gtx));
+    assert!(!GpuSelector::Index(1080).matches(0, &gtx));
+}
+
+#[test]
+fn selectors_match_devices() {
+    let device = properties("llvmpipe (LLVM 15.0.6)", vk::PhysicalDeviceType::CPU);
+
+    assert!(GpuSelector::Index(2).matches(2, &device));
+    assert!(!GpuSelector::Index(2).matches(0, &device));
+    assert!(GpuSelector::Name("LLVMPIPE".into()).matches(0, &device));
+    assert!(!GpuSelector::Name("lavapipe".into()).matches(0, &device));
+    assert!(
+        GpuSelector::Id {
+            vendor_id: 0x10de,
+            device_id: 0x2484
+        }
+        .matches(0, &device)
+    );
+}
+
+#[test]
+fn device_type_outranks_memory_and_msaa() {
+    let discrete = score(
+        vk::PhysicalDeviceType::DISCRETE_GPU,
+        &[2 << 30],
+        vk::SampleCountFlags::_4,
+    );
+    let integrated = score(
+        vk::PhysicalDeviceType::INTEGRATED_GPU,
+        &[16 << 30],
+        vk::SampleCountFlags::_8,
+    );
+    let virtual_gpu = score(
+        vk::PhysicalDeviceType::VIRTUAL_GPU,
+        &[32 << 30],
+        vk::SampleCountFlags::_8,
+    );
+    let cpu = score(
+        vk::PhysicalDeviceType::CPU,
+        &[64 << 30],
+        vk::SampleCountFlags::_8,
+    );
+
+    assert!(discrete > integrated);
+    assert!(integrated > virtual_gpu);
+    assert!(virtual_gpu > cpu);
+}
+
+#[test]
+fn memory_then_msaa_break_ties() {
+    let small = score(
+        vk::PhysicalDeviceType::DISCRETE_GPU,
+        &[4 << 30],
+        vk::SampleCountFlags::_8,
+    );
+    let large = score(
+        vk::PhysicalDeviceType::DISCRETE_GPU,
+        &[4 << 30, 4 << 30],
+        vk::SampleCountFlags::_2,
+    );
+    assert_eq!(large.device_local_memory, 8 << 30);
+    assert!(large > small);
+
+    let more_msaa = score(
+        vk::PhysicalDeviceType::DISCRETE_GPU,
+        &[4 << 30],
+        vk::SampleCountFlags::_8,
+    );
+    let less_msaa = score(
+        vk::PhysicalDeviceType::DISCRETE_GPU,
+        &[4 << 30],
+        vk::SampleCountFlags::_4,
+    );
+    assert!(more_msaa > less_msaa);
+}
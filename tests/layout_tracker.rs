@@ -0,0 +1,119 @@
+use choam::pipeline::barriers::{ImageState, LayoutTracker, Subresources};
+use vulkanalia::vk::{self, Handle};
+
+fn tracked_image(mip_levels: u32, array_layers: u32) -> (LayoutTracker, vk::Image) {
+    let image = vk::Image::from_raw(1);
+    let mut tracker = LayoutTracker::new();
+    tracker.register(
+        image,
+        vk::ImageAspectFlags::COLOR,
+        mip_levels,
+        array_layers,
+        ImageState::UNDEFINED,
+    );
+
+    (tracker, image)
+}
+
+#[test]
+fn whole_image_transitions_in_one_barrier() {
+    let (mut tracker, image) = tracked_image(4, 2);
+
+    let barriers = tracker
+        .transition(image, Subresources::ALL, ImageState::TRANSFER_DST)
+        .unwrap();
+
+    assert_eq!(barriers.len(), 1);
+    assert_eq!(barriers[0].old, ImageState::UNDEFINED);
+    assert_eq!(barriers[0].new, ImageState::TRANSFER_DST);
+    assert_eq!(barriers[0].range.level_count, 4);
+    assert_eq!(barriers[0].range.layer_count, 2);
+}
+
+#[test]
+fn repeated_reads_need_no_barrier() {
+    let (mut tracker, image) = tracked_image(1, 1);
+    tracker
+        .transition(image, Subresources::ALL, ImageState::FRAGMENT_SHADER_READ)
+        .unwrap();
+
+    let barriers = tracker
+        .transition(image, Subresources::ALL, ImageState::FRAGMENT_SHADER_READ)
+        .unwrap();
+
+    assert!(barriers.is_empty());
+}
+
+#[test]
+fn writes_need_a_barrier_without_a_layout_change() {
+    let (mut tracker, image) = tracked_image(1, 1);
+    tracker
+        .transition(image, Subresources::ALL, ImageState::TRANSFER_DST)
+        .unwrap();
+
+    let barriers = tracker
+        .transition(image, Subresources::ALL, ImageState::TRANSFER_DST)
+        .unwrap();
+
+    assert_eq!(barriers.len(), 1);
+    assert_eq!(barriers[0].old.layout, barriers[0].new.layout);
+}
+
+#[test]
+fn mip_levels_are_tracked_separately() {
+    let (mut tracker, image) = tracked_image(3, 1);
+    tracker
+        .transition(image, Subresources::ALL, ImageState::TRANSFER_DST)
+        .unwrap();
+    tracker
+        .transition(image, Subresources::mip(0), ImageState::TRANSFER_SRC)
+        .unwrap();
+
+    assert_eq!(tracker.state(image, 0, 0), Some(ImageState::TRANSFER_SRC));
+    assert_eq!(tracker.state(image, 1, 0), Some(ImageState::TRANSFER_DST));
+
+    // the first level and the rest were in different states, so they each
+    // need their own barrier
+    let barriers = tracker
+        .transition(image, Subresources::ALL, ImageState::FRAGMENT_SHADER_READ)
+        .unwrap();
+
+    assert_eq!(barriers.len(), 2);
+    assert_eq!(barriers[0].old, ImageState::TRANSFER_SRC);
+    assert_eq!(barriers[0].range.level_count, 1);
+    assert_eq!(barriers[1].old, ImageState::TRANSFER_DST);
+    assert_eq!(barriers[1].range.base_mip_level, 1);
+    assert_eq!(barriers[1].range.level_count, 2);
+}
+
+#[test]
+fn discarded_images_transition_from_undefined() {
+    let (mut tracker, image) = tracked_image(1, 1);
+    tracker
+        .transition(image, Subresources::ALL, ImageState::COLOR_ATTACHMENT)
+        .unwrap();
+    tracker.discard(image);
+
+    let barriers = tracker
+        .transition(image, Subresources::ALL, ImageState::COLOR_ATTACHMENT)
+        .unwrap();
+
+    assert_eq!(barriers.len(), 1);
+    assert_eq!(barriers[0].old.layout, vk::ImageLayout::UNDEFINED);
+    assert_eq!(barriers[0].old.stages, ImageState::COLOR_ATTACHMENT.stages);
+}
+
+#[test]
+fn untracked_images_are_an_error() {
+    let mut tracker = LayoutTracker::new();
+
+    assert!(
+        tracker
+            .transition(
+                vk::Image::from_raw(1),
+                Subresources::ALL,
+                ImageState::TRANSFER_DST
+            )
+            .is_err()
+    );
+}
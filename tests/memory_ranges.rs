@@ -0,0 +1,123 @@
+use choam::pipeline::memory::{BlockRanges, align_up, same_page};
+use vulkanalia::vk;
+
+const GRANULARITY: vk::DeviceSize = 1024;
+
+fn requirements(size: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::MemoryRequirements {
+    vk::MemoryRequirements {
+        size,
+        alignment,
+        memory_type_bits: !0,
+    }
+}
+
+#[test]
+fn offsets_are_aligned_up() {
+    assert_eq!(align_up(0, 256), 0);
+    assert_eq!(align_up(1, 256), 256);
+    assert_eq!(align_up(256, 256), 256);
+    assert_eq!(align_up(257, 16), 272);
+    // an alignment of 0 leaves the value as it is
+    assert_eq!(align_up(13, 0), 13);
+}
+
+#[test]
+fn pages_split_at_the_granularity() {
+    assert!(same_page(0, 1023, GRANULARITY));
+    assert!(!same_page(1023, 1024, GRANULARITY));
+    assert!(same_page(2048, 3000, GRANULARITY));
+    // a granularity of 0 puts every byte on its own page
+    assert!(!same_page(5, 6, 0));
+}
+
+#[test]
+fn ranges_are_aligned_and_packed() {
+    let mut ranges = BlockRanges::new(4096);
+
+    assert_eq!(
+        ranges.allocate(requirements(100, 64), true, GRANULARITY),
+        Some(0)
+    );
+    // the next range starts at the alignment after the first
+    assert_eq!(
+        ranges.allocate(requirements(100, 64), true, GRANULARITY),
+        Some(128)
+    );
+    assert_eq!(
+        ranges.allocate(requirements(10, 256), true, GRANULARITY),
+        Some(256)
+    );
+    assert_eq!(ranges.len(), 3);
+    assert_eq!(ranges.used(), 210);
+}
+
+#[test]
+fn linear_and_optimal_ranges_do_not_share_a_page() {
+    let mut ranges = BlockRanges::new(4096);
+
+    // a buffer followed by an optimal image moves the image to the next page
+    assert_eq!(
+        ranges.allocate(requirements(100, 16), true, GRANULARITY),
+        Some(0)
+    );
+    assert_eq!(
+        ranges.allocate(requirements(100, 16), false, GRANULARITY),
+        Some(GRANULARITY)
+    );
+
+    // a buffer may not go into the gap before the image either, as it would
+    // end on the image's page
+    let mut ranges = BlockRanges::new(4096);
+    assert_eq!(
+        ranges.allocate(requirements(2000, 16), false, GRANULARITY),
+        Some(0)
+    );
+    assert_eq!(
+        ranges.allocate(requirements(100, 16), true, GRANULARITY),
+        Some(2048)
+    );
+
+    // ranges of the same kind share pages freely
+    assert_eq!(
+        ranges.allocate(requirements(100, 16), true, GRANULARITY),
+        Some(2160)
+    );
+}
+
+#[test]
+fn freed_gaps_are_reused() {
+    let mut ranges = BlockRanges::new(1024);
+
+    assert_eq!(
+        ranges.allocate(requirements(512, 1), true, GRANULARITY),
+        Some(0)
+    );
+    assert_eq!(
+        ranges.allocate(requirements(512, 1), true, GRANULARITY),
+        Some(512)
+    );
+    assert_eq!(ranges.allocate(requirements(1, 1), true, GRANULARITY), None);
+
+    assert!(ranges.free(0, 512));
+    let (index, offset) = ranges
+        .find(requirements(256, 1), true, GRANULARITY)
+        .unwrap();
+    assert_eq!((index, offset), (0, 0));
+}
+
+#[test]
+fn only_live_ranges_are_freed() {
+    let mut ranges = BlockRanges::new(1024);
+    ranges.allocate(requirements(256, 1), true, GRANULARITY);
+    ranges.allocate(requirements(256, 1), true, GRANULARITY);
+
+    // the offset alone does not identify a range
+    assert!(!ranges.free(0, 128));
+    assert!(!ranges.free(128, 256));
+    assert_eq!(ranges.len(), 2);
+
+    assert!(ranges.free(256, 256));
+    // freeing the same range twice does not take another one with it
+    assert!(!ranges.free(256, 256));
+    assert_eq!(ranges.len(), 1);
+}
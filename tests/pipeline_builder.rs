@@ -0,0 +1,224 @@
+mod common;
+
+use anyhow::Result;
+use choam::app::App;
+use choam::config::AppConfig;
+use choam::pipeline::builder::{BlendMode, DepthBias, PipelineBuilder};
+use choam::pipeline::descriptor_allocator::DescriptorLayoutCache;
+use choam::pipeline::{SceneShaders, scene_pipeline};
+use vulkanalia::vk::{self, HasBuilder};
+
+#[test]
+fn opaque_does_not_blend() {
+    let state = BlendMode::Opaque.attachment();
+
+    assert_eq!(state.blend_enable, vk::FALSE);
+    assert_eq!(state.color_write_mask, vk::ColorComponentFlags::all());
+}
+
+#[test]
+fn alpha_is_the_default() {
+    let state = BlendMode::default().attachment();
+
+    assert_eq!(state.blend_enable, vk::TRUE);
+    assert_eq!(state.src_color_blend_factor, vk::BlendFactor::SRC_ALPHA);
+    assert_eq!(
+        state.dst_color_blend_factor,
+        vk::BlendFactor::ONE_MINUS_SRC_ALPHA
+    );
+}
+
+#[test]
+fn additive_adds_to_the_attachment() {
+    let state = BlendMode::Additive.attachment();
+
+    assert_eq!(state.dst_color_blend_factor, vk::BlendFactor::ONE);
+    assert_eq!(state.color_blend_op, vk::BlendOp::ADD);
+}
+
+#[test]
+fn premultiplied_does_not_scale_the_color() {
+    let state = BlendMode::Premultiplied.attachment();
+
+    assert_eq!(state.src_color_blend_factor, vk::BlendFactor::ONE);
+    assert_eq!(
+        state.dst_color_blend_factor,
+        vk::BlendFactor::ONE_MINUS_SRC_ALPHA
+    );
+}
+
+#[test]
+fn defaults_match_the_scene_pipeline() {
+    let builder = PipelineBuilder::new();
+
+    let input_assembly = builder.input_assembly_state();
+    assert_eq!(
+        input_assembly.topology,
+        vk::PrimitiveTopology::TRIANGLE_LIST
+    );
+
+    let rasterization = builder.rasterization_state();
+    assert_eq!(rasterization.polygon_mode, vk::PolygonMode::FILL);
+    assert_eq!(rasterization.line_width, 1.0);
+    assert_eq!(rasterization.cull_mode, vk::CullModeFlags::BACK);
+    assert_eq!(rasterization.front_face, vk::FrontFace::COUNTER_CLOCKWISE);
+    assert_eq!(rasterization.depth_bias_enable, vk::FALSE);
+
+    let depth_stencil = builder.depth_stencil_state();
+    assert_eq!(depth_stencil.depth_test_enable, vk::TRUE);
+    assert_eq!(depth_stencil.depth_write_enable, vk::TRUE);
+    assert_eq!(depth_stencil.depth_compare_op, vk::CompareOp::LESS);
+    assert_eq!(depth_stencil.stencil_test_enable, vk::FALSE);
+
+    assert_eq!(
+        builder.multisample_state().rasterization_samples,
+        vk::SampleCountFlags::_1
+    );
+
+    // a single alpha blended attachment without any color formats
+    let attachments = builder.color_blend_attachments();
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].blend_enable, vk::TRUE);
+}
+
+#[test]
+fn viewport_and_scissor_are_always_dynamic() {
+    let builder = PipelineBuilder::new();
+
+    assert_eq!(
+        builder.dynamic_states(),
+        [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]
+    );
+}
+
+#[test]
+fn dynamic_states_are_added_once() {
+    let builder = PipelineBuilder::new()
+        .dynamic_state(vk::DynamicState::LINE_WIDTH)
+        .dynamic_state(vk::DynamicState::VIEWPORT)
+        .dynamic_state(vk::DynamicState::LINE_WIDTH)
+        .dynamic_state(vk::DynamicState::DEPTH_BIAS);
+
+    assert_eq!(
+        builder.dynamic_states(),
+        [
+            vk::DynamicState::VIEWPORT,
+            vk::DynamicState::SCISSOR,
+            vk::DynamicState::LINE_WIDTH,
+            vk::DynamicState::DEPTH_BIAS,
+        ]
+    );
+}
+
+#[test]
+fn setters_change_the_pipeline_state() {
+    let builder = PipelineBuilder::new()
+        .topology(vk::PrimitiveTopology::LINE_LIST)
+        .polygon_mode(vk::PolygonMode::LINE, 2.0)
+        .cull_mode(vk::CullModeFlags::NONE, vk::FrontFace::CLOCKWISE)
+        .depth_bias(DepthBias {
+            constant_factor: 1.25,
+            clamp: 0.0,
+            slope_factor: 1.75,
+        })
+        .depth(true, false, vk::CompareOp::LESS_OR_EQUAL)
+        .stencil(vk::StencilOpState::default(), vk::StencilOpState::default())
+        .blend(BlendMode::Opaque)
+        .samples(vk::SampleCountFlags::_4)
+        .formats(
+            &[vk::Format::R8G8B8A8_SRGB, vk::Format::R16G16B16A16_SFLOAT],
+            vk::Format::D32_SFLOAT,
+        );
+
+    assert_eq!(
+        builder.input_assembly_state().topology,
+        vk::PrimitiveTopology::LINE_LIST
+    );
+
+    let rasterization = builder.rasterization_state();
+    assert_eq!(rasterization.polygon_mode, vk::PolygonMode::LINE);
+    assert_eq!(rasterization.line_width, 2.0);
+    assert_eq!(rasterization.cull_mode, vk::CullModeFlags::NONE);
+    assert_eq!(rasterization.front_face, vk::FrontFace::CLOCKWISE);
+    assert_eq!(rasterization.depth_bias_enable, vk::TRUE);
+    assert_eq!(rasterization.depth_bias_constant_factor, 1.25);
+    assert_eq!(rasterization.depth_bias_slope_factor, 1.75);
+
+    let depth_stencil = builder.depth_stencil_state();
+    assert_eq!(depth_stencil.depth_write_enable, vk::FALSE);
+    assert_eq!(depth_stencil.depth_compare_op, vk::CompareOp::LESS_OR_EQUAL);
+    assert_eq!(depth_stencil.stencil_test_enable, vk::TRUE);
+
+    assert_eq!(
+        builder.multisample_state().rasterization_samples,
+        vk::SampleCountFlags::_4
+    );
+
+    // one opaque attachment per color format
+    let attachments = builder.color_blend_attachments();
+    assert_eq!(attachments.len(), 2);
+    assert!(attachments.iter().all(|a| a.blend_enable == vk::FALSE));
+}
+
+#[test]
+fn set_layouts_are_checked_against_the_shaders() -> Result<()> {
+    if !common::vulkan_available() {
+        return Ok(());
+    }
+
+    let mut config = AppConfig::from_env()?;
+    config.pipeline_cache = None;
+    config.track_resources = true;
+
+    unsafe {
+        let mut app = App::create_headless(16, 16, &config)?;
+        let (device, data) = (&app.device, app.data());
+        let shaders = SceneShaders::default();
+
+        let scene = scene_pipeline(data, &shaders);
+        assert!(
+            scene
+                .check_set_layouts(&scene.reflect()?, &data.descriptor_layouts)
+                .is_ok()
+        );
+
+        // the uniform buffer the vertex shader reads bound as a storage buffer
+        let mut layouts = DescriptorLayoutCache::default();
+        let bindings = [
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::VERTEX)
+                .build(),
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(1)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build(),
+        ];
+        let mismatched = layouts.get(device, &data.registry, &bindings)?;
+
+        let builder = PipelineBuilder::new()
+            .shader(vk::ShaderStageFlags::VERTEX, &shaders.vert)
+            .shader(vk::ShaderStageFlags::FRAGMENT, &shaders.frag)
+            .set_layout(mismatched);
+        let error = builder
+            .check_set_layouts(&builder.reflect()?, &layouts)
+            .unwrap_err();
+        assert!(error.to_string().contains("`ubo`"), "{}", error);
+
+        // building checks the layouts before creating anything
+        let error = builder
+            .build(device, data, data.pipeline_layout)
+            .unwrap_err();
+        assert!(error.to_string().contains("set 0"), "{}", error);
+
+        layouts.destroy(device, &data.registry);
+        app.destroy();
+        assert!(app.resources().live().is_empty());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use choam::pipeline::cache::{encode_cache_file, validate_cache_file};
+use vulkanalia::vk;
+
+fn properties() -> vk::PhysicalDeviceProperties {
+    vk::PhysicalDeviceProperties {
+        driver_version: 7,
+        vendor_id: 0x10de,
+        device_id: 0x2484,
+        pipeline_cache_uuid: [3; 16].into(),
+        ..Default::default()
+    }
+}
+
+/// cache data as the driver returns it, a header followed by the pipelines
+fn driver_data(properties: &vk::PhysicalDeviceProperties) -> Vec<u8> {
+    let mut data = vec![];
+    data.extend_from_slice(&32u32.to_le_bytes());
+    data.extend_from_slice(&1u32.to_le_bytes());
+    data.extend_from_slice(&properties.vendor_id.to_le_bytes());
+    data.extend_from_slice(&properties.device_id.to_le_bytes());
+    data.extend_from_slice(&properties.pipeline_cache_uuid[..]);
+    data.extend_from_slice(&[0xab; 64]);
+    data
+}
+
+#[test]
+fn encoded_cache_round_trips() {
+    let properties = properties();
+    let data = driver_data(&properties);
+    let contents = encode_cache_file(&data, &properties);
+
+    assert_eq!(validate_cache_file(&contents, &properties), Ok(&data[..]));
+}
+
+#[test]
+fn other_driver_versions_are_rejected() {
+    let properties = properties();
+    let contents = encode_cache_file(&driver_data(&properties), &properties);
+    let updated = vk::PhysicalDeviceProperties {
+        driver_version: 8,
+        ..properties
+    };
+
+    assert!(validate_cache_file(&contents, &updated).is_err());
+}
+
+#[test]
+fn other_devices_are_rejected() {
+    let properties = properties();
+    let contents = encode_cache_file(&driver_data(&properties), &properties);
+
+    let other_device = vk::PhysicalDeviceProperties {
+        device_id: 0x2485,
+        ..properties
+    };
+    let other_uuid = vk::PhysicalDeviceProperties {
+        pipeline_cache_uuid: [4; 16].into(),
+        ..properties
+    };
+
+    assert!(validate_cache_file(&contents, &other_device).is_err());
+    assert!(validate_cache_file(&contents, &other_uuid).is_err());
+}
+
+#[test]
+fn truncated_and_foreign_files_are_rejected() {
+    let properties = properties();
+    let contents = encode_cache_file(&driver_data(&properties), &properties);
+
+    assert!(validate_cache_file(&contents[..20], &properties).is_err());
+    assert!(validate_cache_file(&contents[4..], &properties).is_err());
+    assert!(validate_cache_file(&[], &properties).is_err());
+}
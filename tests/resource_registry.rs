@@ -0,0 +1,66 @@
+use choam::foundation::registry::ResourceRegistry;
+use vulkanalia::vk::{self, Handle};
+
+#[test]
+fn destroyed_resources_are_not_live() {
+    let registry = ResourceRegistry::new(true);
+    let buffer = vk::Buffer::from_raw(1);
+    let image = vk::Image::from_raw(2);
+
+    registry.created(buffer, 256);
+    registry.created(image, 1024);
+    registry.destroyed(buffer);
+
+    let live = registry.live();
+    assert_eq!(live.len(), 1);
+    assert_eq!(live[0].object_type, vk::ObjectType::IMAGE);
+    assert_eq!(live[0].size, 1024);
+    assert_eq!(live[0].location.file(), file!());
+}
+
+#[test]
+fn handles_of_different_types_are_separate() {
+    let registry = ResourceRegistry::new(true);
+
+    registry.created(vk::Buffer::from_raw(1), 0);
+    registry.created(vk::ImageView::from_raw(1), 0);
+    registry.destroyed(vk::ImageView::from_raw(1));
+
+    let live = registry.live();
+    assert_eq!(live.len(), 1);
+    assert_eq!(live[0].object_type, vk::ObjectType::BUFFER);
+}
+
+#[test]
+fn live_resources_keep_their_name() {
+    let registry = ResourceRegistry::new(true);
+    let sampler = vk::Sampler::from_raw(3);
+
+    registry.created(sampler, 0);
+    registry.named(sampler, "texture");
+
+    assert_eq!(registry.live()[0].name.as_deref(), Some("texture"));
+    assert_eq!(registry.report_leaks(), 1);
+}
+
+#[test]
+fn footprint_counts_device_memory() {
+    let registry = ResourceRegistry::new(true);
+
+    registry.created(vk::DeviceMemory::from_raw(1), 4096);
+    registry.created(vk::DeviceMemory::from_raw(2), 1024);
+    registry.created(vk::Buffer::from_raw(3), 512);
+    registry.destroyed(vk::DeviceMemory::from_raw(2));
+
+    assert_eq!(registry.memory_footprint(), 4096);
+}
+
+#[test]
+fn disabled_registry_records_nothing() {
+    let registry = ResourceRegistry::new(false);
+
+    registry.created(vk::Buffer::from_raw(1), 256);
+
+    assert!(registry.live().is_empty());
+    assert_eq!(registry.report_leaks(), 0);
+}
@@ -0,0 +1,150 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use choam::pipeline::reflect::reflect_shader;
+use choam::pipeline::shader::{GLSLC_ENV, ShaderCompileError, ShaderSource, parse_diagnostics};
+use vulkanalia::vk;
+
+#[test]
+fn diagnostics_keep_their_file_and_line() {
+    let output = "\
+shaders/shader.frag:12: error: 'colour' : undeclared identifier
+shaders/common.glsl:3: warning: unused variable
+2 errors generated.
+";
+
+    let diagnostics = parse_diagnostics(output);
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(
+        diagnostics[0].file,
+        Some(PathBuf::from("shaders/shader.frag"))
+    );
+    assert_eq!(diagnostics[0].line, Some(12));
+    assert_eq!(
+        diagnostics[0].message,
+        "error: 'colour' : undeclared identifier"
+    );
+    assert_eq!(
+        diagnostics[1].to_string(),
+        "shaders/common.glsl:3: warning: unused variable"
+    );
+}
+
+#[test]
+fn diagnostics_without_a_line_are_kept() {
+    let diagnostics = parse_diagnostics("glslc: error: cannot find file `missing.frag`");
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].file, None);
+    assert_eq!(diagnostics[0].line, None);
+}
+
+#[test]
+fn stage_comes_from_the_extension() {
+    assert_eq!(
+        ShaderSource::new("shaders/shader.vert").stage(),
+        Some(vk::ShaderStageFlags::VERTEX)
+    );
+    assert_eq!(
+        ShaderSource::new("shaders/shader.frag").stage(),
+        Some(vk::ShaderStageFlags::FRAGMENT)
+    );
+    assert_eq!(ShaderSource::new("shaders/common.glsl").stage(), None);
+}
+
+#[test]
+fn permutations_differ_by_their_defines() {
+    let base = ShaderSource::new("shaders/shader.frag");
+    let alpha_tested = base.clone().define("ALPHA_TEST", "1");
+
+    assert_ne!(base, alpha_tested);
+    assert_eq!(
+        alpha_tested.defines,
+        vec![("ALPHA_TEST".to_string(), "1".to_string())]
+    );
+}
+
+/// a stand-in for glslc that runs the shell script, written next to the
+/// other test outputs
+#[cfg(unix)]
+fn fake_glslc(name: &str, script: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("fake_glslc");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+#[test]
+#[cfg(unix)]
+fn compiling_runs_glslc_with_the_permutation() {
+    // echoes its arguments in place of the SPIR-V it writes to stdout
+    let glslc = fake_glslc("echo_args", "echo \"$@\"");
+    let output = ShaderSource::new("shaders/shader.frag")
+        .define("ALPHA_TEST", "1")
+        .compile_with(&glslc)
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(output).unwrap().trim(),
+        "--target-env=vulkan1.2 -I shaders -DALPHA_TEST=1 -o - shaders/shader.frag"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn compile_errors_carry_the_diagnostics() {
+    let glslc = fake_glslc(
+        "fail",
+        "echo \"shaders/shader.frag:7: error: 'colour' : undeclared identifier\" >&2\nexit 1",
+    );
+    let error = ShaderSource::new("shaders/shader.frag")
+        .compile_with(&glslc)
+        .unwrap_err();
+
+    let error = error.downcast::<ShaderCompileError>().unwrap();
+    assert_eq!(error.path, PathBuf::from("shaders/shader.frag"));
+    assert_eq!(error.diagnostics.len(), 1);
+    assert_eq!(error.diagnostics[0].line, Some(7));
+}
+
+#[test]
+fn missing_glslc_is_reported() {
+    let error = ShaderSource::new("shaders/shader.frag")
+        .compile_with("/nonexistent/glslc")
+        .unwrap_err();
+
+    assert!(
+        error.to_string().contains("glslc was not found"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn scene_shaders_compile_with_glslc() {
+    let glslc = std::env::var_os(GLSLC_ENV).unwrap_or_else(|| "glslc".into());
+    if Command::new(&glslc).arg("--version").output().is_err() {
+        eprintln!("Skipping shader compile test, glslc was not found");
+        return;
+    }
+
+    let shaders = Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders");
+    for name in ["shader.vert", "shader.frag", "scale.comp"] {
+        let spirv = ShaderSource::new(shaders.join(name))
+            .compile_with(&glslc)
+            .unwrap();
+        reflect_shader(&spirv).unwrap();
+    }
+
+    // a syntax error is reported with the line it is on
+    let broken = Path::new(env!("CARGO_TARGET_TMPDIR")).join("broken.frag");
+    fs::write(&broken, "#version 450\nvoid main() {\n    oops\n}\n").unwrap();
+    let error = ShaderSource::new(&broken).compile_with(&glslc).unwrap_err();
+    let error = error.downcast::<ShaderCompileError>().unwrap();
+    assert!(error.diagnostics.iter().any(|d| d.line.is_some()));
+}
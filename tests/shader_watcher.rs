@@ -0,0 +1,80 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use choam::pipeline::reload::ShaderWatcher;
+
+/// an empty directory for the test to write shaders into
+fn shader_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("shader_watcher")
+        .join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn touch(path: &Path, seconds: u64) {
+    let file = File::options()
+        .create(true)
+        .append(true)
+        .open(path)
+        .unwrap();
+    file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+        .unwrap();
+}
+
+#[test]
+fn unchanged_files_are_not_reported() {
+    let dir = shader_dir("unchanged");
+    touch(&dir.join("shader.frag"), 1);
+
+    let mut watcher = ShaderWatcher::new(&dir, Duration::ZERO);
+
+    assert!(watcher.changed().is_empty());
+}
+
+#[test]
+fn modified_and_added_files_are_reported_once() {
+    let dir = shader_dir("modified");
+    touch(&dir.join("shader.vert"), 1);
+    touch(&dir.join("shader.frag"), 1);
+
+    let mut watcher = ShaderWatcher::new(&dir, Duration::ZERO);
+    touch(&dir.join("shader.frag"), 2);
+    touch(&dir.join("common.glsl"), 2);
+
+    assert_eq!(
+        watcher.changed(),
+        vec![dir.join("common.glsl"), dir.join("shader.frag")]
+    );
+    assert!(watcher.changed().is_empty());
+}
+
+#[test]
+fn changes_wait_for_the_interval() {
+    let dir = shader_dir("interval");
+    touch(&dir.join("shader.frag"), 1);
+
+    let mut watcher = ShaderWatcher::new(&dir, Duration::from_secs(3600));
+    assert!(watcher.changed().is_empty());
+
+    touch(&dir.join("shader.frag"), 2);
+    assert!(watcher.changed().is_empty());
+}
+
+#[test]
+fn only_sources_are_watched() {
+    let dir = shader_dir("sources");
+    touch(&dir.join("shader.frag"), 1);
+
+    let mut watcher = ShaderWatcher::new(&dir, Duration::ZERO);
+    // compiling the shaders writes their SPIR-V next to them
+    touch(&dir.join("shader.frag.spv"), 2);
+    touch(&dir.join("scale.comp"), 2);
+    touch(&dir.join("notes.txt"), 2);
+    assert!(watcher.changed().is_empty());
+
+    touch(&dir.join("shader.vert"), 2);
+    assert_eq!(watcher.changed(), vec![dir.join("shader.vert")]);
+}
@@ -0,0 +1,262 @@
+use choam::pipeline::reflect::{NumericType, ShaderInterface, numeric_type, reflect_shader};
+use choam::pipeline::vertex::Vertex;
+use vulkanalia::vk::{self, HasBuilder};
+
+// the SPIR-V the build script compiled and the app embeds
+const VERT: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/shader.vert.spv"));
+const FRAG: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/shader.frag.spv"));
+
+fn scene_interface() -> ShaderInterface {
+    let stages = [reflect_shader(VERT).unwrap(), reflect_shader(FRAG).unwrap()];
+    ShaderInterface::new(&stages).unwrap()
+}
+
+/// a vertex shader module made of the instructions, each an opcode and its
+/// operands
+fn vertex_module(instructions: &[(u32, &[u32])]) -> Vec<u8> {
+    // magic, version 1.0, generator, id bound, schema
+    let mut words = vec![0x0723_0203, 0x0001_0000, 0, 100, 0];
+    // OpEntryPoint Vertex %1
+    words.extend([(3 << 16) | 15, 0, 1]);
+    for (opcode, operands) in instructions {
+        words.push(((operands.len() as u32 + 1) << 16) | opcode);
+        words.extend(*operands);
+    }
+
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+#[test]
+fn vertex_shader_is_reflected() {
+    let vert = reflect_shader(VERT).unwrap();
+
+    assert_eq!(vert.stage, vk::ShaderStageFlags::VERTEX);
+    assert_eq!(vert.bindings.len(), 1);
+    assert_eq!(vert.bindings[0].binding, 0);
+    assert_eq!(
+        vert.bindings[0].descriptor_type,
+        vk::DescriptorType::UNIFORM_BUFFER
+    );
+    assert_eq!(vert.bindings[0].name, "ubo");
+
+    let push_constants = vert.push_constants.unwrap();
+    assert_eq!((push_constants.offset, push_constants.size), (0, 64));
+
+    let inputs = vert
+        .inputs
+        .iter()
+        .map(|i| (i.location, i.format))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        inputs,
+        vec![
+            (0, vk::Format::R32G32B32_SFLOAT),
+            (1, vk::Format::R32G32B32_SFLOAT),
+            (2, vk::Format::R32G32_SFLOAT),
+        ]
+    );
+}
+
+#[test]
+fn fragment_shader_is_reflected() {
+    let frag = reflect_shader(FRAG).unwrap();
+
+    assert_eq!(frag.stage, vk::ShaderStageFlags::FRAGMENT);
+    assert_eq!(frag.bindings.len(), 1);
+    assert_eq!(frag.bindings[0].binding, 1);
+    assert_eq!(
+        frag.bindings[0].descriptor_type,
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+    );
+    assert!(frag.inputs.is_empty());
+
+    let push_constants = frag.push_constants.unwrap();
+    assert_eq!((push_constants.offset, push_constants.size), (64, 4));
+}
+
+#[test]
+fn scene_shaders_match_the_vertex_format() {
+    let interface = scene_interface();
+
+    assert!(
+        interface
+            .check_vertex_input(&Vertex::attribute_descriptions())
+            .is_ok()
+    );
+
+    let error = interface
+        .check_vertex_input(&Vertex::attribute_descriptions()[..2])
+        .unwrap_err();
+    assert!(error.to_string().contains("location 2"), "{}", error);
+}
+
+#[test]
+fn mismatched_layouts_are_reported() {
+    let interface = scene_interface();
+
+    let bindings = interface.set_layout_bindings(0);
+    assert!(interface.check_set_layout(0, &bindings).is_ok());
+
+    let ubo = vk::DescriptorSetLayoutBinding::builder()
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::VERTEX)
+        .build();
+    let error = interface.check_set_layout(0, &[ubo]).unwrap_err();
+    assert!(error.to_string().contains("binding 1"), "{}", error);
+
+    let ranges = [vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::VERTEX)
+        .offset(0)
+        .size(64)
+        .build()];
+    assert!(interface.check_push_constants(&ranges).is_err());
+    assert!(
+        interface
+            .check_push_constants(&interface.push_constant_ranges)
+            .is_ok()
+    );
+}
+
+#[test]
+fn garbage_is_not_spirv() {
+    assert!(reflect_shader(&[0; 64]).is_err());
+    assert!(reflect_shader(&VERT[..VERT.len() - 2]).is_err());
+}
+
+#[test]
+fn empty_vectors_are_rejected() {
+    let module = vertex_module(&[
+        (5, &[5, u32::from_le_bytes(*b"inP\0")]), // OpName %5 "inP"
+        (22, &[2, 32]),                           // %2 = OpTypeFloat 32
+        (23, &[3, 2, 0]),                         // %3 = OpTypeVector %2 0
+        (32, &[4, 1, 3]),                         // %4 = OpTypePointer Input %3
+        (59, &[4, 5, 1]),                         // %5 = OpVariable %4 Input
+        (71, &[5, 30, 0]),                        // OpDecorate %5 Location 0
+    ]);
+
+    let error = reflect_shader(&module).unwrap_err();
+    assert!(error.to_string().contains("`inP`"), "{}", error);
+    assert!(error.to_string().contains("0 components"), "{}", error);
+}
+
+#[test]
+fn spec_constant_array_lengths_are_rejected() {
+    let module = vertex_module(&[
+        (22, &[2, 32]),       // %2 = OpTypeFloat 32
+        (21, &[6, 32, 0]),    // %6 = OpTypeInt 32 0
+        (50, &[6, 7, 4]),     // %7 = OpSpecConstant %6 4
+        (28, &[3, 2, 7]),     // %3 = OpTypeArray %2 %7
+        (30, &[4, 3]),        // %4 = OpTypeStruct %3
+        (71, &[3, 6, 4]),     // OpDecorate %3 ArrayStride 4
+        (71, &[4, 2]),        // OpDecorate %4 Block
+        (72, &[4, 0, 35, 0]), // OpMemberDecorate %4 0 Offset 0
+        (32, &[5, 2, 4]),     // %5 = OpTypePointer Uniform %4
+        (59, &[5, 8, 2]),     // %8 = OpVariable %5 Uniform
+    ]);
+
+    let error = reflect_shader(&module).unwrap_err();
+    assert!(
+        error.to_string().contains("specialization constants"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn row_major_matrices_are_sized_by_their_rows() {
+    let module = vertex_module(&[
+        (22, &[2, 32]),        // %2 = OpTypeFloat 32
+        (23, &[3, 2, 4]),      // %3 = OpTypeVector %2 4
+        (24, &[4, 3, 2]),      // %4 = OpTypeMatrix %3 2, a mat2x4
+        (30, &[5, 4, 4]),      // %5 = OpTypeStruct %4 %4
+        (71, &[5, 2]),         // OpDecorate %5 Block
+        (72, &[5, 0, 35, 0]),  // OpMemberDecorate %5 0 Offset 0
+        (72, &[5, 0, 5]),      // OpMemberDecorate %5 0 ColMajor
+        (72, &[5, 0, 7, 16]),  // OpMemberDecorate %5 0 MatrixStride 16
+        (72, &[5, 1, 35, 32]), // OpMemberDecorate %5 1 Offset 32
+        (72, &[5, 1, 4]),      // OpMemberDecorate %5 1 RowMajor
+        (72, &[5, 1, 7, 16]),  // OpMemberDecorate %5 1 MatrixStride 16
+        (32, &[6, 9, 5]),      // %6 = OpTypePointer PushConstant %5
+        (59, &[6, 7, 9]),      // %7 = OpVariable %6 PushConstant
+    ]);
+
+    let reflection = reflect_shader(&module).unwrap();
+
+    // two columns of 4 floats, against four rows of 2 floats padded to 16
+    let sizes = reflection
+        .push_constant_members
+        .iter()
+        .map(|m| m.size)
+        .collect::<Vec<_>>();
+    assert_eq!(sizes, [32, 64]);
+
+    let range = reflection.push_constants.unwrap();
+    assert_eq!((range.offset, range.size), (0, 96));
+}
+
+#[test]
+fn formats_are_classified_by_numeric_type() {
+    let cases = [
+        (vk::Format::R32G32B32_SFLOAT, Some(NumericType::Float)),
+        (vk::Format::R8G8B8A8_UNORM, Some(NumericType::Float)),
+        (vk::Format::B8G8R8A8_SRGB, Some(NumericType::Float)),
+        (vk::Format::R16G16_SSCALED, Some(NumericType::Float)),
+        (
+            vk::Format::A2B10G10R10_SNORM_PACK32,
+            Some(NumericType::Float),
+        ),
+        (vk::Format::R16_SINT, Some(NumericType::SInt)),
+        (vk::Format::A2R10G10B10_UINT_PACK32, Some(NumericType::UInt)),
+        (vk::Format::R64G64_SFLOAT, Some(NumericType::Float64)),
+        (vk::Format::R64_UINT, Some(NumericType::UInt64)),
+        (vk::Format::D32_SFLOAT, None),
+        (vk::Format::BC1_RGB_UNORM_BLOCK, None),
+        (vk::Format::UNDEFINED, None),
+    ];
+
+    for (format, numeric) in cases {
+        assert_eq!(numeric_type(format), numeric, "{:?}", format);
+    }
+}
+
+#[test]
+fn vertex_formats_only_have_to_match_the_numeric_type() {
+    let interface = scene_interface();
+    let mut attributes = Vertex::attribute_descriptions();
+
+    // fewer and more components than read, and a normalized format
+    attributes[0].format = vk::Format::R32G32_SFLOAT;
+    attributes[1].format = vk::Format::R8G8B8A8_UNORM;
+    assert!(interface.check_vertex_input(&attributes).is_ok());
+
+    attributes[2].format = vk::Format::R32G32_UINT;
+    let error = interface.check_vertex_input(&attributes).unwrap_err();
+    assert!(error.to_string().contains("location 2"), "{}", error);
+}
+
+#[test]
+fn unsupported_vertex_inputs_are_named() {
+    let matrix = vertex_module(&[
+        (5, &[5, u32::from_le_bytes(*b"mod\0")]), // OpName %5 "mod"
+        (22, &[2, 32]),                           // %2 = OpTypeFloat 32
+        (23, &[3, 2, 4]),                         // %3 = OpTypeVector %2 4
+        (24, &[6, 3, 4]),                         // %6 = OpTypeMatrix %3 4
+        (32, &[4, 1, 6]),                         // %4 = OpTypePointer Input %6
+        (59, &[4, 5, 1]),                         // %5 = OpVariable %4 Input
+        (71, &[5, 30, 0]),                        // OpDecorate %5 Location 0
+    ]);
+    let error = reflect_shader(&matrix).unwrap_err();
+    assert!(error.to_string().contains("`mod`"), "{}", error);
+    assert!(error.to_string().contains("Matrices"), "{}", error);
+
+    let half = vertex_module(&[
+        (22, &[2, 16]),    // %2 = OpTypeFloat 16
+        (32, &[4, 1, 2]),  // %4 = OpTypePointer Input %2
+        (59, &[4, 5, 1]),  // %5 = OpVariable %4 Input
+        (71, &[5, 30, 0]), // OpDecorate %5 Location 0
+    ]);
+    let error = reflect_shader(&half).unwrap_err();
+    assert!(error.to_string().contains("16 bit floats"), "{}", error);
+}
@@ -0,0 +1,131 @@
+use choam::foundation::info::{DeviceError, DeviceReport, QueueFamilyReport, SystemReport};
+use vulkanalia::{Version, vk};
+
+fn device() -> DeviceReport {
+    DeviceReport {
+        index: 0,
+        name: "llvmpipe \"lavapipe\"".into(),
+        device_type: vk::PhysicalDeviceType::CPU,
+        vendor_id: 0x10005,
+        device_id: 0,
+        api_version: Version::new(1, 3, 230),
+        driver_version: 1,
+        limits: vk::PhysicalDeviceLimits {
+            max_push_constants_size: 128,
+            max_compute_work_group_count: [65535, 65535, 65535],
+            max_sampler_anisotropy: 16.0,
+            timestamp_period: 0.5,
+            ..Default::default()
+        },
+        queue_families: vec![QueueFamilyReport {
+            flags: vk::QueueFlags::GRAPHICS,
+            queue_count: 1,
+            timestamp_valid_bits: 64,
+            present: None,
+        }],
+        memory_heaps: vec![vk::MemoryHeap {
+            size: 2 << 30,
+            flags: vk::MemoryHeapFlags::DEVICE_LOCAL,
+        }],
+        memory_types: vec![],
+        surface: None,
+        depth_format: Some(vk::Format::D32_SFLOAT),
+        max_msaa_samples: vk::SampleCountFlags::_4,
+        extensions: vec!["VK_KHR_swapchain".into()],
+    }
+}
+
+fn report() -> SystemReport {
+    SystemReport {
+        instance_version: Version::new(1, 3, 0),
+        devices: vec![
+            Ok(device()),
+            Err(DeviceError {
+                index: 1,
+                name: "broken".into(),
+                error: "ERROR_INITIALIZATION_FAILED".into(),
+            }),
+        ],
+    }
+}
+
+#[test]
+fn json_reports_every_device() {
+    let json = report().to_json();
+
+    assert!(json.starts_with(r#"{"instance_version":"1.3.0","devices":[{"index":0,"#));
+    assert!(
+        json.contains(r#""name":"llvmpipe \"lavapipe\"""#),
+        "{}",
+        json
+    );
+    assert!(json.contains(r#""device_type":"CPU""#), "{}", json);
+    assert!(json.contains(r#""present":null"#), "{}", json);
+    assert!(json.contains(r#""surface":null"#), "{}", json);
+    assert!(json.contains(r#""max_msaa_samples":4"#), "{}", json);
+    assert!(
+        json.contains(r#""extensions":["VK_KHR_swapchain"]"#),
+        "{}",
+        json
+    );
+    assert!(json.ends_with("]}"), "{}", json);
+}
+
+#[test]
+fn json_reports_device_limits() {
+    let json = report().to_json();
+
+    assert!(json.contains(r#""maxPushConstantsSize":128"#), "{}", json);
+    assert!(
+        json.contains(r#""maxComputeWorkGroupCount":[65535,65535,65535]"#),
+        "{}",
+        json
+    );
+    assert!(json.contains(r#""maxSamplerAnisotropy":16"#), "{}", json);
+    assert!(json.contains(r#""timestampPeriod":0.5"#), "{}", json);
+}
+
+#[test]
+fn failed_devices_are_reported_with_their_error() {
+    let report = report();
+
+    let json = report.to_json();
+    assert!(
+        json.contains(r#"{"index":1,"name":"broken","error":"ERROR_INITIALIZATION_FAILED"}"#),
+        "{}",
+        json
+    );
+
+    let text = report.to_string();
+    assert!(text.contains("Physical devices: 2"), "{}", text);
+    assert!(
+        text.contains("#1 `broken`\n  Error: ERROR_INITIALIZATION_FAILED"),
+        "{}",
+        text
+    );
+    assert!(text.contains("maxPushConstantsSize: 128"), "{}", text);
+}
+
+#[test]
+fn json_brackets_and_quotes_balance() {
+    let json = report().to_json();
+
+    // outside of strings every bracket has to be closed in order
+    let mut stack = vec![];
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in json.chars() {
+        match (in_string, escaped, c) {
+            (true, false, '\\') => escaped = true,
+            (true, true, _) => escaped = false,
+            (_, _, '"') => in_string = !in_string,
+            (false, _, '{' | '[') => stack.push(c),
+            (false, _, '}') => assert_eq!(stack.pop(), Some('{')),
+            (false, _, ']') => assert_eq!(stack.pop(), Some('[')),
+            _ => {}
+        }
+    }
+
+    assert!(!in_string);
+    assert!(stack.is_empty());
+}
@@ -0,0 +1,120 @@
+use std::ptr::NonNull;
+
+use cgmath::Vector4;
+use choam::gpu_block;
+use choam::pipeline::uniforms::UniformRing;
+
+gpu_block! {
+    struct Tint {
+        color: Vector4<f32>,
+    }
+}
+
+const ALIGNMENT: u64 = 256;
+
+fn tint(value: f32) -> Tint {
+    Tint {
+        color: Vector4::new(value, value, value, value),
+    }
+}
+
+fn read(memory: &[u8], offset: u32) -> f32 {
+    let offset = offset as usize;
+    f32::from_ne_bytes(memory[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn pushes_start_at_aligned_offsets() {
+    let mut memory = vec![0u8; 2 * 1024];
+    let mut ring = unsafe {
+        UniformRing::from_mapped(
+            NonNull::new(memory.as_mut_ptr()).unwrap(),
+            1024,
+            ALIGNMENT,
+            2,
+        )
+    };
+
+    ring.begin_frame(0);
+    let offsets = (0..3)
+        .map(|i| ring.push(&tint(i as f32)).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(offsets, vec![0, 256, 512]);
+
+    // the second frame hands out its own region
+    ring.begin_frame(1);
+    assert_eq!(ring.push(&tint(7.0)).unwrap(), 1024);
+
+    drop(ring);
+    assert_eq!(read(&memory, 256), 1.0);
+    assert_eq!(read(&memory, 512), 2.0);
+    assert_eq!(read(&memory, 1024), 7.0);
+}
+
+#[test]
+fn offsets_stay_aligned_when_regions_are_not() {
+    // the second region starts at 1000, which is not a multiple of 256
+    let mut memory = vec![0u8; 2 * 1000];
+    let mut ring = unsafe {
+        UniformRing::from_mapped(
+            NonNull::new(memory.as_mut_ptr()).unwrap(),
+            1000,
+            ALIGNMENT,
+            2,
+        )
+    };
+
+    ring.begin_frame(1);
+    let offset = ring.push(&tint(1.0)).unwrap();
+    assert_eq!(offset, 1024);
+    assert_eq!(offset as u64 % ALIGNMENT, 0);
+}
+
+#[test]
+fn full_regions_report_out_of_space() {
+    let mut memory = vec![0u8; 2 * 1024];
+    let mut ring = unsafe {
+        UniformRing::from_mapped(
+            NonNull::new(memory.as_mut_ptr()).unwrap(),
+            1024,
+            ALIGNMENT,
+            2,
+        )
+    };
+
+    ring.begin_frame(0);
+    for _ in 0..4 {
+        ring.push(&tint(1.0)).unwrap();
+    }
+
+    // a fifth block would spill into the region of the next frame
+    let error = ring.push(&tint(2.0)).unwrap_err();
+    assert!(error.to_string().contains("out of space"), "{}", error);
+
+    // the region is handed out again from the start next time
+    ring.begin_frame(0);
+    assert_eq!(ring.push(&tint(3.0)).unwrap(), 0);
+    drop(ring);
+    assert_eq!(read(&memory, 1024), 0.0);
+}
+
+#[test]
+fn rings_without_memory_fail_to_push() {
+    let mut ring = UniformRing::default();
+    assert!(ring.push(&tint(1.0)).is_err());
+}
+
+#[test]
+#[should_panic(expected = "no region for frame 2")]
+fn frames_past_the_last_region_panic() {
+    let mut memory = vec![0u8; 2 * 1024];
+    let mut ring = unsafe {
+        UniformRing::from_mapped(
+            NonNull::new(memory.as_mut_ptr()).unwrap(),
+            1024,
+            ALIGNMENT,
+            2,
+        )
+    };
+    ring.begin_frame(2);
+}